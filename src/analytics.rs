@@ -1,87 +1,175 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
-use crate::models::{DashboardStats, LogEntry};
+use chrono::{NaiveDateTime, Timelike};
 
-pub fn compute_dashboard_stats(logs: &[LogEntry]) -> DashboardStats {
-    if logs.is_empty() {
-        return DashboardStats::default();
+use crate::models::{DashboardStats, LogEntry, TimeDisplay};
+
+/// Entry counts per quarter hour, kept in UTC so they can be grouped into the hours
+/// of whichever zone the times are shown in (zone offsets are whole quarter hours)
+#[derive(Debug, Default)]
+pub struct TimeHistogram {
+    quarters: BTreeMap<NaiveDateTime, u64>,
+}
+
+impl TimeHistogram {
+    pub fn add_all(&mut self, logs: &[LogEntry]) {
+        for time in logs.iter().filter_map(|log| log.time) {
+            self.add(time);
+        }
+    }
+
+    /// Count an entry at local `time`
+    pub fn add(&mut self, time: NaiveDateTime) {
+        let utc = TimeDisplay::Utc.in_zone(time);
+        let quarter = utc
+            .date()
+            .and_hms_opt(utc.hour(), utc.minute() / 15 * 15, 0)
+            .unwrap_or(utc);
+        *self.quarters.entry(quarter).or_insert(0) += 1;
+    }
+
+    /// Counts per hour of `display`'s zone in time order, labelled "月-日 时:00"
+    pub fn hourly(&self, display: TimeDisplay) -> Vec<(String, u64)> {
+        let mut hours: Vec<(String, u64)> = Vec::new();
+        for (&quarter, &count) in &self.quarters {
+            let label = display.utc_to_zone(quarter).format("%m-%d %H:00").to_string();
+            match hours.last_mut() {
+                Some((last, n)) if *last == label => *n += count,
+                _ => hours.push((label, count)),
+            }
+        }
+        hours
     }
+}
+
+/// Running totals behind [`DashboardStats`], so entries can be added as they arrive
+#[derive(Debug, Default)]
+pub struct StatsCounter {
+    total_logs: usize,
+    error_count: usize,
+    warn_count: usize,
+    info_count: usize,
+    source_counts: HashMap<Arc<str>, u64>,
+    thread_counts: HashMap<Arc<str>, u64>,
+    errors: TimeHistogram,
+    first_ts: Option<NaiveDateTime>,
+    last_ts: Option<NaiveDateTime>,
+}
 
-    let mut error_count = 0;
-    let mut warn_count = 0;
-    let mut info_count = 0;
-    let mut source_counts: HashMap<String, u64> = HashMap::new();
-    let mut thread_counts: HashMap<String, u64> = HashMap::new();
-    let mut error_by_hour: HashMap<String, u64> = HashMap::new();
+impl StatsCounter {
+    pub fn add_all(&mut self, logs: &[LogEntry]) {
+        for log in logs {
+            self.add(log);
+        }
+    }
 
-    for log in logs {
+    pub fn add(&mut self, log: &LogEntry) {
+        self.total_logs += 1;
         let level = log.level.to_lowercase();
         if level.contains("error") {
-            error_count += 1;
-            let hour_key = if log.timestamp.len() >= 13 {
-                format!(
-                    "{}-{} {}:00",
-                    &log.timestamp[5..7],
-                    &log.timestamp[8..10],
-                    &log.timestamp[11..13]
-                )
-            } else {
-                log.timestamp.clone()
-            };
-            *error_by_hour.entry(hour_key).or_insert(0) += 1;
+            self.error_count += 1;
+            if let Some(ts) = log.time {
+                self.errors.add(ts);
+            }
         } else if level.contains("warn") {
-            warn_count += 1;
+            self.warn_count += 1;
         } else if level.contains("info") {
-            info_count += 1;
+            self.info_count += 1;
+        }
+
+        *self.source_counts.entry(log.source_file.clone()).or_insert(0) += 1;
+        *self.thread_counts.entry(log.tid.clone()).or_insert(0) += 1;
+
+        if let Some(ts) = log.time {
+            self.first_ts = Some(self.first_ts.map_or(ts, |t| t.min(ts)));
+            self.last_ts = Some(self.last_ts.map_or(ts, |t| t.max(ts)));
+        }
+    }
+
+    /// The stats, with hours and times in `display`'s zone
+    pub fn stats(&self, display: TimeDisplay) -> DashboardStats {
+        if self.total_logs == 0 {
+            return DashboardStats::default();
         }
 
-        *source_counts.entry(log.source_file.clone()).or_insert(0) += 1;
-        *thread_counts.entry(log.tid.clone()).or_insert(0) += 1;
+        let top_sources = top_five(&self.source_counts);
+        let top_threads = top_five(&self.thread_counts);
+
+        let mut error_trend = self.errors.hourly(display);
+        error_trend.drain(..error_trend.len().saturating_sub(12));
+
+        let log_duration = match (self.first_ts, self.last_ts) {
+            (Some(first), Some(last)) => format!(
+                "{} ~ {}",
+                display.in_zone(first).format("%H:%M"),
+                display.in_zone(last).format("%H:%M")
+            ),
+            _ => "N/A".into(),
+        };
+
+        // Calculate health score (50-100, never below 50)
+        let health_score = 100u16
+            .saturating_sub((self.error_count / 10) as u16)
+            .saturating_sub((self.warn_count / 50) as u16)
+            .max(50);
+
+        // Generate sparkline data from error trend
+        let sparkline_data: Vec<u64> = error_trend.iter().map(|(_, v)| *v).collect();
+
+        DashboardStats {
+            total_logs: self.total_logs,
+            error_count: self.error_count,
+            warn_count: self.warn_count,
+            info_count: self.info_count,
+            log_duration,
+            error_trend,
+            top_sources,
+            top_threads,
+            health_score,
+            sparkline_data,
+        }
     }
+}
+
+fn top_five(counts: &HashMap<Arc<str>, u64>) -> Vec<(String, u64)> {
+    let mut top: Vec<_> = counts.iter().collect();
+    top.sort_by(|a, b| b.1.cmp(a.1));
+    top.truncate(5);
+    top.into_iter().map(|(s, n)| (s.to_string(), *n)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut top_sources: Vec<_> = source_counts.into_iter().collect();
-    top_sources.sort_by(|a, b| b.1.cmp(&a.1));
-    top_sources.truncate(5);
-
-    let mut top_threads: Vec<_> = thread_counts.into_iter().collect();
-    top_threads.sort_by(|a, b| b.1.cmp(&a.1));
-    top_threads.truncate(5);
-
-    let mut error_trend: Vec<_> = error_by_hour.into_iter().collect();
-    error_trend.sort_by(|a, b| a.0.cmp(&b.0));
-    error_trend = error_trend.into_iter().rev().take(12).collect();
-    error_trend.reverse();
-
-    let first_ts = &logs
-        .first()
-        .map(|l| l.timestamp.clone())
-        .unwrap_or_default();
-    let last_ts = &logs.last().map(|l| l.timestamp.clone()).unwrap_or_default();
-    let log_duration = if first_ts.len() >= 16 && last_ts.len() >= 16 {
-        format!("{} ~ {}", &first_ts[11..16], &last_ts[11..16])
-    } else {
-        "N/A".into()
-    };
-
-    // Calculate health score (50-100, never below 50)
-    let health_score = 100u16
-        .saturating_sub((error_count / 10) as u16)
-        .saturating_sub((warn_count / 50) as u16)
-        .max(50);
-
-    // Generate sparkline data from error trend
-    let sparkline_data: Vec<u64> = error_trend.iter().map(|(_, v)| *v).collect();
-
-    DashboardStats {
-        total_logs: logs.len(),
-        error_count,
-        warn_count,
-        info_count,
-        log_duration,
-        error_trend,
-        top_sources,
-        top_threads,
-        health_score,
-        sparkline_data,
+    #[test]
+    fn test_histogram_hours_follow_display_zone() {
+        let times: Vec<NaiveDateTime> = [
+            "2024-01-15 09:59:59",
+            "2024-01-15 10:00:00",
+            "2024-01-15 10:45:10",
+            "2024-01-15 12:30:00",
+        ]
+        .iter()
+        .map(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").unwrap())
+        .collect();
+        let mut histogram = TimeHistogram::default();
+        for &time in &times {
+            histogram.add(time);
+        }
+        // Each zone's hours agree with how the list shows the same times
+        for display in [TimeDisplay::Local, TimeDisplay::Utc] {
+            let mut expected: Vec<(String, u64)> = Vec::new();
+            for &time in &times {
+                let label = display.in_zone(time).format("%m-%d %H:00").to_string();
+                match expected.last_mut() {
+                    Some((last, n)) if *last == label => *n += 1,
+                    _ => expected.push((label, 1)),
+                }
+            }
+            assert_eq!(histogram.hourly(display), expected);
+        }
+        assert_eq!(histogram.hourly(TimeDisplay::Local).len(), 3);
     }
 }
@@ -1,4 +1,5 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 use std::sync::mpsc as std_mpsc;
 use std::time::{Duration, Instant};
 
@@ -8,21 +9,52 @@ use ratatui::widgets::ListState;
 use regex::Regex;
 use tokio::sync::mpsc;
 
+use crate::context::{ContextLines, ContextMark, ContextWindow, RowRole};
+use crate::config::HighlightRule;
+use crate::entry_list::EntryList;
+use crate::filter_stack::{FilterLayer, FilterStack};
+use crate::filtering::matches_criteria;
 use crate::history::HistoryManager;
+use crate::index::TrigramIndex;
 use crate::models::{
     AiState, ChatContext, ChatMessage, ChatRole, CurrentView, DashboardStats, DisplayEntry,
-    ExportResult, ExportState, ExportType, FileInfo, Focus, InputMode, LevelVisibility, LogEntry,
+    EntryKind, ExportResult, ExportState, ExportType, FileInfo, Focus, InputMode,
+    LevelVisibility, LogEntry, ParseDiagnostics, TimeDisplay,
 };
+use crate::parser::{diagnose, ParserProfile};
+use crate::pipeline::Pipeline;
 use crate::report::{ReportCache, ReportPeriod};
+use crate::search::{SearchCriteria, SearchQuery};
 use crate::search_form::SearchFormState;
+use crate::search_job::SearchJob;
+use crate::templates::{self, TemplateManager};
+use crate::tui::syntax::Highlight;
+use crate::web::state::WebSharedState;
+
+/// How long a search runs before its progress and time are shown
+const SLOW_SEARCH: Duration = Duration::from_millis(300);
+
+/// Focus mode waiting for the search of its layer's query
+struct FocusJob {
+    query: String,
+    /// The rows searched, which the match positions refer to
+    rows: Vec<usize>,
+    /// Entries from here on were replaced by folding while searching
+    replaced_from: usize,
+    job: SearchJob,
+}
 
 /// Focus mode state for isolated search results
 #[derive(Default)]
 pub struct FocusModeState {
-    /// Isolated search results
-    pub focus_logs: Vec<DisplayEntry>,
-    /// Original focus logs before any sub-search
-    pub original_focus_logs: Vec<DisplayEntry>,
+    /// Isolated search results and their context, as indices into `App::all_entries`
+    pub focus_logs: Vec<usize>,
+    /// Original focus matches before any sub-search
+    pub original_focus_logs: Vec<usize>,
+    /// Whether a query picked `original_focus_logs`, so they get context lines
+    pub focus_searching: bool,
+    /// Role of each focus row while context lines are on
+    pub context_marks: HashMap<usize, ContextMark>,
     /// Separate scroll state for focus mode
     pub focus_table_state: ListState,
     /// Query that generated the focus results
@@ -39,6 +71,8 @@ impl FocusModeState {
         Self {
             focus_logs: Vec::new(),
             original_focus_logs: Vec::new(),
+            focus_searching: false,
+            context_marks: HashMap::new(),
             focus_table_state: ListState::default(),
             focus_query: String::new(),
             focus_match_indices: Vec::new(),
@@ -50,6 +84,8 @@ impl FocusModeState {
     pub fn reset(&mut self) {
         self.focus_logs.clear();
         self.original_focus_logs.clear();
+        self.focus_searching = false;
+        self.context_marks.clear();
         self.focus_table_state = ListState::default();
         self.focus_query.clear();
         self.focus_match_indices.clear();
@@ -59,19 +95,49 @@ impl FocusModeState {
 }
 
 pub struct App {
-    pub all_entries: Vec<DisplayEntry>,
-    pub filtered_entries: Vec<DisplayEntry>,
+    /// Shared with a running background search, which reads a snapshot of it
+    pub all_entries: EntryList,
+    /// Trigram index over `all_entries`, narrowing searches to candidate blocks
+    pub search_index: TrigramIndex,
+    /// Indices into `all_entries` of the entries shown in the log list
+    pub filtered_entries: Vec<usize>,
     pub list_state: ListState,
     pub focus_mode: FocusModeState,
+    /// Filter actions as layers; the thread, trace, level, criteria and focus state
+    /// below is derived from it
+    pub filter_stack: FilterStack,
+    /// Layers the log list was last filtered with
+    applied_filters: Vec<u64>,
     pub filter_tid: Option<String>,
     pub filter_trace: Option<String>,
     pub correlation_regexes: Vec<Regex>,
     pub search_mode: bool,
     pub search_query: String,
+    /// Highlights the text terms of the search query
     pub search_regex: Option<Regex>,
-    pub negative_search: bool,
+    /// The compiled search query, as criteria so it shares the advanced filter's predicate
+    pub search_filter: SearchCriteria,
+    /// Why the search query doesn't compile, shown in the search bar
+    pub search_error: Option<String>,
     pub match_indices: Vec<usize>,
     pub current_match: usize,
+    /// The quick search running off the UI thread, filling `match_indices` when done
+    search_job: Option<SearchJob>,
+    /// Leading rows of `filtered_entries` the running search still covers; rows
+    /// after them arrived (or were replaced) while it ran and are matched as they come
+    search_rows: usize,
+    /// Focus mode waiting for its matches
+    focus_job: Option<FocusJob>,
+    /// Patterns highlighted in every view: `[[highlight]]` rules and searches kept with `*`
+    pub highlights: Vec<Highlight>,
+    /// Conditions from the advanced search form, applied on top of the other filters
+    pub search_criteria: SearchCriteria,
+    /// Entries shown around advanced search and focus matches
+    pub context_lines: ContextLines,
+    /// Role of each filtered row while context lines are on
+    pub context_marks: HashMap<usize, ContextMark>,
+    /// Context state at the end of the filtered list, for entries that arrive later
+    context_window: ContextWindow,
     pub status_msg: Option<(String, Instant)>,
     pub clipboard: Option<Clipboard>,
     pub histogram: Vec<(String, u64)>,
@@ -86,19 +152,29 @@ pub struct App {
     pub visible_levels: LevelVisibility,
     pub show_help: bool,
     pub files: Vec<FileInfo>,
+    /// Each file's parser profile by source id, with its per-file clock adjustments,
+    /// as it was parsed; `None` while stdin's is still being detected
+    pub profiles: Vec<Option<ParserProfile>>,
+    pub parse_diagnostics: Option<ParseDiagnostics>,
     pub focus: Focus,
     pub file_list_state: ListState,
     pub input_mode: InputMode,
     pub input_buffer: String,
     pub is_tailing: bool,
+    /// Rows added below the selection while it was pinned away from the last row
+    pub unseen_lines: usize,
     pub current_view: CurrentView,
     pub stats: DashboardStats,
+    /// Processing state for entries that arrive while tailing
+    pub pipeline: Pipeline,
+    pub web_state: Option<WebSharedState>,
     pub page_size: usize,
     pub error_indices: Vec<usize>,
     pub chart_scroll: usize,
     // Horizontal scroll and wrap settings
     pub horizontal_scroll: usize,
     pub wrap_lines: bool,
+    pub time_display: TimeDisplay,
     // Chat state
     pub chat_history: Vec<ChatMessage>,
     pub chat_context: ChatContext,
@@ -107,6 +183,7 @@ pub struct App {
     pub chat_spinner: usize,
     pub export_state: ExportState,
     pub history: HistoryManager,
+    pub template_manager: TemplateManager,
     // Advanced search form state
     pub search_form: SearchFormState,
     // Report state
@@ -120,7 +197,7 @@ pub struct App {
 
 impl App {
     pub fn new(
-        entries: Vec<DisplayEntry>,
+        entries: EntryList,
         histogram: Vec<(String, u64)>,
         files: Vec<FileInfo>,
         ai_tx: mpsc::Sender<(String, Option<String>)>,
@@ -141,21 +218,33 @@ impl App {
         if !files.is_empty() {
             file_list_state.select(Some(0));
         }
-        let error_indices = Self::compute_error_indices(&entries);
+        let error_indices = Self::compute_error_indices(entries.iter());
         Self {
-            all_entries: entries.clone(),
-            filtered_entries: entries,
+            filtered_entries: (0..entries.len()).collect(),
+            search_index: TrigramIndex::build(&entries),
+            all_entries: entries,
             list_state,
             focus_mode: FocusModeState::new(),
+            filter_stack: FilterStack::default(),
+            applied_filters: Vec::new(),
             filter_tid: None,
             filter_trace: None,
             correlation_regexes: Vec::new(),
             search_mode: false,
             search_query: String::new(),
             search_regex: None,
-            negative_search: false,
+            search_filter: SearchCriteria::default(),
+            search_error: None,
             match_indices: Vec::new(),
             current_match: 0,
+            search_job: None,
+            search_rows: 0,
+            focus_job: None,
+            highlights: Vec::new(),
+            search_criteria: SearchCriteria::default(),
+            context_lines: ContextLines::default(),
+            context_marks: HashMap::new(),
+            context_window: ContextWindow::default(),
             status_msg: None,
             clipboard: Clipboard::new().ok(),
             histogram,
@@ -170,18 +259,24 @@ impl App {
             visible_levels: LevelVisibility::default(),
             show_help: false,
             files,
+            profiles: Vec::new(),
+            parse_diagnostics: None,
             focus: Focus::LogList,
             file_list_state,
             input_mode: InputMode::Normal,
             input_buffer: String::new(),
             is_tailing: false,
+            unseen_lines: 0,
             current_view: CurrentView::Logs,
             stats: DashboardStats::default(),
+            pipeline: Pipeline::default(),
+            web_state: None,
             page_size,
             error_indices,
             chart_scroll: 0,
             horizontal_scroll: 0,
             wrap_lines: false,
+            time_display: TimeDisplay::default(),
             chat_history: Vec::new(),
             chat_context: ChatContext::default(),
             chat_input: String::new(),
@@ -189,6 +284,7 @@ impl App {
             chat_spinner: 0,
             export_state: ExportState::Idle,
             history: HistoryManager::new(),
+            template_manager: TemplateManager::default(),
             search_form: SearchFormState::new(),
             report_period: ReportPeriod::default(),
             report_content: String::new(),
@@ -199,9 +295,8 @@ impl App {
         }
     }
 
-    fn compute_error_indices(entries: &[DisplayEntry]) -> Vec<usize> {
+    fn compute_error_indices<'a>(entries: impl Iterator<Item = &'a DisplayEntry>) -> Vec<usize> {
         entries
-            .iter()
             .enumerate()
             .filter_map(|(i, e)| match e {
                 DisplayEntry::Normal(log) if log.level.to_lowercase().contains("error") => Some(i),
@@ -210,8 +305,21 @@ impl App {
             .collect()
     }
 
-    pub fn entries(&self) -> &Vec<DisplayEntry> {
-        &self.filtered_entries
+    /// The `i`-th entry of the filtered log list
+    pub fn filtered_entry(&self, i: usize) -> Option<&DisplayEntry> {
+        self.filtered_entries.get(i).map(|&idx| &self.all_entries[idx])
+    }
+
+    pub fn filtered_iter(&self) -> impl Iterator<Item = &DisplayEntry> {
+        self.filtered_entries.iter().map(|&idx| &self.all_entries[idx])
+    }
+
+    /// The `i`-th entry of the focus mode list
+    pub fn focus_entry(&self, i: usize) -> Option<&DisplayEntry> {
+        self.focus_mode
+            .focus_logs
+            .get(i)
+            .map(|&idx| &self.all_entries[idx])
     }
 
     pub fn next(&mut self) {
@@ -299,10 +407,24 @@ impl App {
         self.status_msg = Some((format!("自动换行: {}", status), Instant::now()));
     }
 
+    /// Show times in the next zone; the timeline and dashboard hours follow it
+    pub fn cycle_time_display(&mut self) {
+        self.time_display = self.time_display.next();
+        self.histogram = self.pipeline.histogram(self.time_display);
+        self.stats = self.pipeline.stats(self.time_display);
+        if let Some(web) = &self.web_state {
+            web.update_stats(self.stats.clone());
+        }
+        self.status_msg = Some((
+            format!("时间显示: {}", self.time_display.label()),
+            Instant::now(),
+        ));
+    }
+
     pub fn selected_entry(&self) -> Option<&DisplayEntry> {
         self.list_state
             .selected()
-            .and_then(|i| self.filtered_entries.get(i))
+            .and_then(|i| self.filtered_entry(i))
     }
 
     /// Load correlation regex patterns from config
@@ -334,148 +456,468 @@ impl App {
 
     pub fn toggle_trace_filter(&mut self) {
         if self.filter_trace.is_some() {
-            self.filter_trace = None;
-            self.status_msg = Some(("已清除链路追踪".into(), Instant::now()));
-            self.apply_filter();
+            self.clear_trace_filter();
         } else if let Some(entry) = self.selected_entry().cloned() {
             if let Some(trace_id) = self.extract_correlation_id(&entry) {
+                self.push_filter(FilterLayer::Trace(trace_id.clone()));
                 self.status_msg = Some((format!("追踪链路: {}", &trace_id), Instant::now()));
-                self.filter_trace = Some(trace_id);
-                self.apply_filter();
             } else {
                 self.status_msg = Some(("未找到关联 ID (traceId/requestId/UUID)".into(), Instant::now()));
             }
         }
     }
 
-    /// Clear trace filter directly (reserved for future use)
-    #[allow(dead_code)]
+    /// Remove the trace filter layer
     pub fn clear_trace_filter(&mut self) {
-        if self.filter_trace.is_some() {
-            self.filter_trace = None;
+        if let Some(pos) = self.filter_stack.position(|l| matches!(l, FilterLayer::Trace(_))) {
+            self.remove_filter(pos);
             self.status_msg = Some(("已清除链路追踪".into(), Instant::now()));
-            self.apply_filter();
         }
     }
 
     pub fn toggle_thread_filter(&mut self) {
-        if self.filter_tid.is_some() {
-            self.filter_tid = None;
-            self.apply_filter();
+        if let Some(pos) = self.filter_stack.position(|l| matches!(l, FilterLayer::Thread(_))) {
+            self.remove_filter(pos);
         } else if let Some(tid) = self
             .selected_entry()
             .and_then(|e| e.get_tid())
             .map(String::from)
         {
-            self.filter_tid = Some(tid);
-            self.apply_filter();
+            self.push_filter(FilterLayer::Thread(tid));
         }
     }
 
-    pub fn apply_filter(&mut self) {
-        let enabled_files: Vec<usize> = self
-            .files
+    /// Add a filter layer on top of the stack and apply it
+    pub fn push_filter(&mut self, layer: FilterLayer) {
+        let label = layer.label();
+        self.filter_stack.push(layer);
+        self.apply_filter_stack();
+        self.status_msg = Some((format!("过滤: {}", label), Instant::now()));
+    }
+
+    /// Remove the layer at `pos`, counting from the bottom of the stack
+    pub fn remove_filter(&mut self, pos: usize) {
+        if let Some(layer) = self.filter_stack.remove(pos) {
+            self.apply_filter_stack();
+            self.status_msg = Some((format!("已移除过滤: {}", layer.label()), Instant::now()));
+        }
+    }
+
+    /// Remove every filter layer, as one step that can be undone
+    pub fn clear_filters(&mut self) {
+        self.filter_stack.clear();
+        self.apply_filter_stack();
+    }
+
+    pub fn undo_filter(&mut self) {
+        let msg = if self.filter_stack.undo() {
+            self.apply_filter_stack();
+            "已撤销过滤"
+        } else {
+            "没有可撤销的过滤"
+        };
+        self.status_msg = Some((msg.to_string(), Instant::now()));
+    }
+
+    pub fn redo_filter(&mut self) {
+        let msg = if self.filter_stack.redo() {
+            self.apply_filter_stack();
+            "已重做过滤"
+        } else {
+            "没有可重做的过滤"
+        };
+        self.status_msg = Some((msg.to_string(), Instant::now()));
+    }
+
+    /// Set the advanced search criteria as a layer; empty criteria remove it
+    pub fn set_search_criteria(&mut self, criteria: SearchCriteria) {
+        if !criteria.is_empty() {
+            self.push_filter(FilterLayer::Search(criteria));
+        } else if let Some(pos) = self.filter_stack.position(|l| matches!(l, FilterLayer::Search(_))) {
+            self.remove_filter(pos);
+        }
+    }
+
+    /// Derive the filters from the stack, refiltering (keeping the selected entry)
+    /// only when a layer other than focus changed, then enter or leave focus mode
+    fn apply_filter_stack(&mut self) {
+        let state = self.filter_stack.state();
+        let ids = self.filter_stack.filter_ids();
+        let refilter = ids != self.applied_filters;
+        if refilter {
+            self.applied_filters = ids;
+            self.filter_tid = state.tid;
+            self.filter_trace = state.trace;
+            self.visible_levels = state.levels;
+            self.search_criteria = state.criteria;
+            self.apply_filter_keeping_selection();
+        }
+        match state.focus {
+            Some((query, criteria)) if refilter || !self.is_focus_mode() => {
+                self.enter_focus_mode(query, criteria)
+            }
+            Some(_) => {}
+            None => {
+                self.stop_focus_job();
+                if self.is_focus_mode() {
+                    self.exit_focus_mode();
+                }
+            }
+        }
+    }
+
+    /// Show the focus view of the current quick search as a layer over the filters
+    pub fn push_focus(&mut self, query: String) {
+        // Already focused, the layer is replaced without refiltering; show the new query
+        let refocus = self.is_focus_mode();
+        let criteria = self.search_filter.clone();
+        self.push_filter(FilterLayer::Focus {
+            query: query.clone(),
+            criteria: criteria.clone(),
+        });
+        if refocus {
+            self.enter_focus_mode(query, criteria);
+        }
+    }
+
+    /// Leave the focus view, removing its layer
+    pub fn pop_focus(&mut self) {
+        match self.filter_stack.position(|l| matches!(l, FilterLayer::Focus { .. })) {
+            Some(pos) => self.remove_filter(pos),
+            None => self.exit_focus_mode(),
+        }
+    }
+
+    /// Switch views; leaving the focus view removes its layer
+    pub fn switch_view(&mut self, view: CurrentView) {
+        if self.is_focus_mode() && view != CurrentView::Focus {
+            self.pop_focus();
+        }
+        self.current_view = view;
+    }
+
+    /// Source ids of the files currently shown
+    fn enabled_files(&self) -> Vec<usize> {
+        self.files
             .iter()
             .filter(|f| f.enabled)
             .map(|f| f.id)
-            .collect();
-        self.filtered_entries = self
-            .all_entries
-            .iter()
-            .enumerate()
-            .filter(|(_, e)| {
-                if let Some(sid) = e.get_source_id() {
-                    if !enabled_files.contains(&sid) {
-                        return false;
-                    }
-                }
-                if let Some(tid) = &self.filter_tid {
-                    if e.get_tid() != Some(tid) {
-                        return false;
-                    }
-                }
-                if let Some(trace_id) = &self.filter_trace {
-                    if let DisplayEntry::Normal(log) = e {
-                        if !log.content.contains(trace_id.as_str())
-                            && !log.tid.contains(trace_id.as_str())
-                        {
-                            return false;
-                        }
-                    } else {
-                        return false;
-                    }
-                }
-                if let DisplayEntry::Normal(log) = e {
-                    let level = log.level.to_lowercase();
-                    if level.contains("info") && !self.visible_levels.info {
-                        return false;
-                    }
-                    if level.contains("warn") && !self.visible_levels.warn {
-                        return false;
-                    }
-                    if level.contains("error") && !self.visible_levels.error {
-                        return false;
-                    }
-                    if level.contains("debug") && !self.visible_levels.debug {
-                        return false;
-                    }
+            .collect()
+    }
+
+    /// Whether `e` passes the file, thread, trace and level filters
+    fn passes_filters(&self, e: &DisplayEntry, enabled_files: &[usize]) -> bool {
+        if let Some(sid) = e.get_source_id() {
+            if !enabled_files.contains(&sid) {
+                return false;
+            }
+        }
+        if let Some(tid) = &self.filter_tid {
+            if e.get_tid() != Some(tid) {
+                return false;
+            }
+        }
+        if let Some(trace_id) = &self.filter_trace {
+            if let DisplayEntry::Normal(log) = e {
+                if !log.content.contains(trace_id.as_str())
+                    && !log.tid.contains(trace_id.as_str())
+                {
+                    return false;
                 }
-                true
-            })
-            .map(|(_, e)| e.clone())
-            .collect();
+            } else {
+                return false;
+            }
+        }
+        if let DisplayEntry::Normal(log) = e {
+            let level = log.level.to_lowercase();
+            if level.contains("info") && !self.visible_levels.info {
+                return false;
+            }
+            if level.contains("warn") && !self.visible_levels.warn {
+                return false;
+            }
+            if level.contains("error") && !self.visible_levels.error {
+                return false;
+            }
+            if level.contains("debug") && !self.visible_levels.debug {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Context lines to use for a view whose rows are picked by a search, none otherwise
+    fn active_context(&self, searching: bool) -> ContextLines {
+        if searching {
+            self.context_lines
+        } else {
+            ContextLines::default()
+        }
+    }
+
+    /// Run the entries of `range` that pass the filters through `window`, returning
+    /// the rows to show; matches are the entries meeting the advanced search criteria
+    fn filter_rows(
+        &self,
+        window: &mut ContextWindow,
+        range: std::ops::Range<usize>,
+    ) -> Vec<(usize, ContextMark)> {
+        let enabled_files = self.enabled_files();
+        let criteria_re = self.search_criteria.compile_content_regex();
+        let candidates = self.search_index.criteria_candidates(&self.search_criteria);
+        let mut rows = Vec::new();
+        for idx in range {
+            let e = &self.all_entries[idx];
+            if self.passes_filters(e, &enabled_files) {
+                let is_match = self.search_criteria.is_empty()
+                    || (candidates.contains(idx)
+                        && matches_criteria(e, &self.search_criteria, &criteria_re));
+                window.push(idx, is_match, &mut rows);
+            }
+        }
+        rows
+    }
+
+    pub fn apply_filter(&mut self) {
+        let lines = self.active_context(!self.search_criteria.is_empty());
+        let mut window = ContextWindow::new(lines);
+        let rows = self.filter_rows(&mut window, 0..self.all_entries.len());
+        self.context_window = window;
+        self.context_marks = if lines.is_empty() {
+            HashMap::new()
+        } else {
+            rows.iter().copied().collect()
+        };
+        self.filtered_entries = rows.into_iter().map(|(i, _)| i).collect();
         self.list_state.select(if self.filtered_entries.is_empty() {
             None
         } else {
             Some(0)
         });
         self.update_search_matches();
-        self.error_indices = Self::compute_error_indices(&self.filtered_entries);
+        self.error_indices = Self::compute_error_indices(self.filtered_iter());
+    }
+
+    /// Add entries that arrived while tailing
+    ///
+    /// They go through the same pipeline as loaded entries (deltas, stats, histogram,
+    /// folding) and are shown if they pass the active filters, extending the error
+    /// and search match lists. Folding may replace entries at the end of the list.
+    /// A search still running (for the list or for focus mode) carries on over the
+    /// entries it started with: the new rows are matched here, and matches among
+    /// replaced entries are dropped when it finishes.
+    pub fn append_entries(&mut self, entries: Vec<LogEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+        for entry in &entries {
+            if entry.kind == EntryKind::Unparsed {
+                if let Some(file) = self.files.get_mut(entry.source_id) {
+                    file.parse_failures += 1;
+                }
+            }
+        }
+        let following = self.is_following();
+        let shown_before = self.filtered_entries.len();
+        let changed = self.pipeline.process(&mut self.all_entries, entries);
+        self.histogram = self.pipeline.histogram(self.time_display);
+        self.search_index.update(&self.all_entries, changed);
+
+        // Forget entries that were folded away or replaced
+        let kept = self.filtered_entries.partition_point(|&i| i < changed);
+        self.filtered_entries.truncate(kept);
+        self.search_rows = self.search_rows.min(kept);
+        if let Some(focus) = &mut self.focus_job {
+            focus.replaced_from = focus.replaced_from.min(changed);
+        }
+        self.error_indices.retain(|&i| i < kept);
+        self.match_indices.retain(|&i| i < kept);
+        self.current_match = self.current_match.min(self.match_indices.len().saturating_sub(1));
+        self.bookmarks.retain(|&i| i < kept);
+        self.focus_mode.focus_logs.retain(|&i| i < changed);
+        self.focus_mode.original_focus_logs.retain(|&i| i < changed);
+        self.focus_mode.context_marks.retain(|&i, _| i < changed);
+        self.context_marks.retain(|&i, _| i < changed);
+
+        // Continue the context window; the first new rows may be after-context of an
+        // earlier match
+        let mut window = std::mem::take(&mut self.context_window);
+        window.rewind(changed, self.filtered_entries.last().copied());
+        let rows = self.filter_rows(&mut window, changed..self.all_entries.len());
+        self.context_window = window;
+        let with_context = !self.active_context(!self.search_criteria.is_empty()).is_empty();
+        for (idx, mark) in rows {
+            if with_context {
+                self.context_marks.insert(idx, mark);
+            }
+            let entry = &self.all_entries[idx];
+            let pos = self.filtered_entries.len();
+            if let DisplayEntry::Normal(log) = entry {
+                if log.level.to_lowercase().contains("error") {
+                    self.error_indices.push(pos);
+                }
+            }
+            if self.is_search_match(entry) {
+                self.match_indices.push(pos);
+            }
+            self.filtered_entries.push(idx);
+        }
+
+        // Stay on the last row when already there; otherwise keep the selection and
+        // count what arrived below it
+        if following {
+            self.jump_to_bottom();
+        } else {
+            self.unseen_lines += self.filtered_entries.len().saturating_sub(shown_before);
+            if let Some(i) = self.list_state.selected() {
+                let last = self.filtered_entries.len().saturating_sub(1);
+                self.list_state.select(Some(i.min(last)));
+            }
+        }
+
+        self.stats = self.pipeline.stats(self.time_display);
+        if let Some(web) = &self.web_state {
+            web.update_stats(self.stats.clone());
+        }
     }
 
     pub fn start_search(&mut self) {
         self.search_mode = true;
         self.search_query.clear();
-        self.negative_search = false;
+        self.search_error = None;
     }
 
     pub fn exit_search(&mut self) {
         self.search_mode = false;
     }
 
-    pub fn update_search(&mut self) {
-        if self.search_query.starts_with('!') {
-            self.negative_search = true;
-            let pattern = &self.search_query[1..];
-            self.search_regex = if pattern.is_empty() {
+    /// Compile the search query, noting why it fails in `search_error`
+    pub fn check_search_query(&mut self) -> Option<SearchQuery> {
+        match SearchQuery::parse(&self.search_query, self.time_display) {
+            Ok(query) => {
+                self.search_error = None;
+                query
+            }
+            Err(e) => {
+                self.search_error = Some(e.to_string());
                 None
-            } else {
-                Regex::new(pattern).ok()
-            };
-        } else {
-            self.negative_search = false;
-            self.search_regex = Regex::new(&self.search_query).ok();
+            }
         }
+    }
+
+    pub fn update_search(&mut self) {
+        let query = self.check_search_query();
+        self.search_regex = query.as_ref().and_then(SearchQuery::highlight_regex);
+        self.search_filter.query = query;
         self.update_search_matches();
     }
 
+    /// Start finding the filtered entries that match the search query, off the UI
+    /// thread; `poll_search` picks up the matches
     pub fn update_search_matches(&mut self) {
+        self.stop_search_job();
         self.match_indices.clear();
-        if let Some(re) = &self.search_regex {
-            for (i, entry) in self.filtered_entries.iter().enumerate() {
-                let matches = re.is_match(&entry.get_searchable_text());
-                if self.negative_search {
-                    if !matches {
-                        self.match_indices.push(i);
-                    }
-                } else {
-                    if matches {
-                        self.match_indices.push(i);
-                    }
-                }
-            }
+        self.current_match = 0;
+        if self.search_filter.is_empty() {
+            return;
         }
+        self.search_rows = self.filtered_entries.len();
+        self.search_job = Some(SearchJob::spawn(
+            self.all_entries.clone(),
+            self.search_index.clone(),
+            self.filtered_entries.clone(),
+            self.search_filter.clone(),
+        ));
+    }
+
+    /// Take the matches of a finished search, and enter focus mode once its matches
+    /// are found
+    pub fn poll_search(&mut self) {
+        self.poll_focus_job();
+        let Some(job) = &mut self.search_job else {
+            return;
+        };
+        let Some((matches, took)) = job.try_finish() else {
+            if job.elapsed() >= SLOW_SEARCH {
+                self.status_msg = Some(("搜索中… (Esc 取消)".to_string(), Instant::now()));
+            }
+            return;
+        };
+        self.search_job = None;
+        // Matches among rows added since the search started are already in
+        let mut matches: Vec<usize> =
+            matches.into_iter().filter(|&pos| pos < self.search_rows).collect();
+        matches.append(&mut self.match_indices);
+        self.match_indices = matches;
         self.current_match = 0;
+        if took >= SLOW_SEARCH {
+            self.status_msg = Some((
+                format!("搜索完成: {} 条匹配 ({} ms)", self.match_indices.len(), took.as_millis()),
+                Instant::now(),
+            ));
+        }
+    }
+
+    fn poll_focus_job(&mut self) {
+        let Some(focus) = &mut self.focus_job else {
+            return;
+        };
+        let Some((positions, _)) = focus.job.try_finish() else {
+            if focus.job.elapsed() >= SLOW_SEARCH {
+                self.status_msg = Some(("搜索中… (Esc 取消)".to_string(), Instant::now()));
+            }
+            return;
+        };
+        let FocusJob {
+            query,
+            rows,
+            replaced_from,
+            ..
+        } = self.focus_job.take().expect("focus job");
+        let matches = positions
+            .into_iter()
+            .map(|pos| rows[pos])
+            .filter(|&idx| idx < replaced_from)
+            .collect();
+        self.show_focus(query, matches, true);
+    }
+
+    /// Cancel the running search and focus mode waiting for its matches, if any
+    pub fn cancel_search(&mut self) -> bool {
+        let focus = self.stop_focus_job();
+        if focus {
+            // The focus layer waiting for the matches goes with them
+            if let Some(pos) = self.filter_stack.position(|l| matches!(l, FilterLayer::Focus { .. })) {
+                self.remove_filter(pos);
+            }
+        }
+        self.stop_search_job() || focus
+    }
+
+    fn stop_focus_job(&mut self) -> bool {
+        match self.focus_job.take() {
+            Some(focus) => {
+                focus.job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop the search worker; returns whether one was running
+    fn stop_search_job(&mut self) -> bool {
+        match self.search_job.take() {
+            Some(job) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether `entry` matches the search query
+    fn is_search_match(&self, entry: &DisplayEntry) -> bool {
+        !self.search_filter.is_empty() && matches_criteria(entry, &self.search_filter, &None)
     }
 
     pub fn next_match(&mut self) {
@@ -499,6 +941,29 @@ impl App {
             .select(Some(self.match_indices[self.current_match]));
     }
 
+    /// Keep highlighting the current search as a rule and clear the search, so its
+    /// text stays marked without the search; on a search already kept, drop the rule
+    pub fn toggle_search_highlight(&mut self) {
+        let Some(pattern) = self.search_regex.as_ref().map(|re| re.as_str().to_string()) else {
+            self.status_msg = Some(("没有可高亮的搜索".to_string(), Instant::now()));
+            return;
+        };
+        if let Some(pos) = self.highlights.iter().position(|h| h.rule.pattern == pattern) {
+            self.highlights.remove(pos);
+            self.status_msg = Some((format!("已移除高亮: {}", pattern), Instant::now()));
+            return;
+        }
+        match Highlight::from_search(&pattern, self.highlights.len()) {
+            Ok(highlight) => {
+                self.highlights.push(highlight);
+                self.search_query.clear();
+                self.update_search();
+                self.status_msg = Some((format!("已添加高亮: {}", pattern), Instant::now()));
+            }
+            Err(e) => self.status_msg = Some((e.to_string(), Instant::now())),
+        }
+    }
+
     pub fn toggle_bookmark(&mut self) {
         if let Some(idx) = self.list_state.selected() {
             if !self.bookmarks.remove(&idx) {
@@ -537,15 +1002,18 @@ impl App {
         }
     }
 
+    /// Hide a level as a filter layer, or show it again by removing the layer
     pub fn toggle_level(&mut self, level: u8) {
-        match level {
-            1 => self.visible_levels.info = !self.visible_levels.info,
-            2 => self.visible_levels.warn = !self.visible_levels.warn,
-            3 => self.visible_levels.error = !self.visible_levels.error,
-            4 => self.visible_levels.debug = !self.visible_levels.debug,
-            _ => {}
+        if !(1..=4).contains(&level) {
+            return;
+        }
+        match self
+            .filter_stack
+            .position(|l| matches!(l, FilterLayer::HideLevel(n) if *n == level))
+        {
+            Some(pos) => self.remove_filter(pos),
+            None => self.push_filter(FilterLayer::HideLevel(level)),
         }
-        self.apply_filter();
     }
 
     pub fn copy_line(&mut self) {
@@ -575,7 +1043,7 @@ impl App {
                 .json_payload
                 .as_ref()
                 .map(|j| serde_json::to_string_pretty(j).unwrap_or_default())
-                .unwrap_or_else(|| log.content.clone()),
+                .unwrap_or_else(|| log.content.to_string()),
             DisplayEntry::Folded { summary_text, .. } => summary_text.clone(),
         });
         if let (Some(clip), Some(text)) = (self.clipboard.as_mut(), text) {
@@ -610,6 +1078,60 @@ impl App {
         }
     }
 
+    /// Explain why records of the selected entry's file fail to parse, using the
+    /// selected entry when it is unparsed and the file's first failure otherwise
+    pub fn diagnose_selected(&mut self) {
+        let sample = match self.selected_entry() {
+            Some(DisplayEntry::Normal(log)) if log.kind == EntryKind::Unparsed => Some(log.clone()),
+            Some(entry) => entry.get_source_id().and_then(|id| self.first_unparsed(id)),
+            None => None,
+        };
+        self.show_parse_diagnostics(sample);
+    }
+
+    /// Explain why records of the file selected in the sidebar fail to parse
+    pub fn diagnose_selected_file(&mut self) {
+        let sample = self
+            .file_list_state
+            .selected()
+            .and_then(|i| self.files.get(i))
+            .and_then(|f| self.first_unparsed(f.id));
+        self.show_parse_diagnostics(sample);
+    }
+
+    fn first_unparsed(&self, source_id: usize) -> Option<LogEntry> {
+        self.all_entries.iter().find_map(|e| match e {
+            DisplayEntry::Normal(log)
+                if log.kind == EntryKind::Unparsed && log.source_id == source_id =>
+            {
+                Some(log.clone())
+            }
+            _ => None,
+        })
+    }
+
+    fn show_parse_diagnostics(&mut self, sample: Option<LogEntry>) {
+        let Some(log) = sample else {
+            self.status_msg = Some(("该文件没有解析失败的记录".into(), Instant::now()));
+            return;
+        };
+        let Some(file) = self.files.get(log.source_id) else {
+            return;
+        };
+        let Some(profile) = self.profiles.get(log.source_id).and_then(Option::as_ref) else {
+            self.status_msg = Some((format!("未知的解析配置: {}", file.profile), Instant::now()));
+            return;
+        };
+        self.parse_diagnostics = Some(ParseDiagnostics {
+            file: file.name.clone(),
+            profile: file.profile.clone(),
+            failures: file.parse_failures,
+            line_index: log.line_index,
+            diagnosis: diagnose(&log.content, profile),
+            record: log.content.to_string(),
+        });
+    }
+
     #[allow(dead_code)]
     pub fn get_file_color(&self, source_id: usize) -> Color {
         self.files
@@ -631,11 +1153,10 @@ impl App {
 
     pub fn submit_jump(&mut self) {
         if let Ok(line_num) = self.input_buffer.parse::<usize>() {
-            if let Some(idx) = self
-                .filtered_entries
-                .iter()
-                .position(|e| e.get_line_index() == Some(line_num))
-            {
+            let found = self
+                .filtered_iter()
+                .position(|e| e.get_line_index() == Some(line_num));
+            if let Some(idx) = found {
                 self.list_state.select(Some(idx));
             } else {
                 self.status_msg = Some(("Line not found".into(), Instant::now()));
@@ -644,6 +1165,76 @@ impl App {
         self.exit_jump_mode();
     }
 
+    /// Refilter, then select the selected entry again, or the next one shown after it
+    fn apply_filter_keeping_selection(&mut self) {
+        let selected = self
+            .list_state
+            .selected()
+            .and_then(|i| self.filtered_entries.get(i).copied());
+        self.apply_filter();
+        if let Some(idx) = selected {
+            let pos = self.filtered_entries.partition_point(|&i| i < idx);
+            if pos < self.filtered_entries.len() {
+                self.list_state.select(Some(pos));
+            }
+        }
+    }
+
+    pub fn enter_layer_mode(&mut self) {
+        if self.filter_stack.is_empty() {
+            self.status_msg = Some(("没有过滤层".to_string(), Instant::now()));
+            return;
+        }
+        self.input_mode = InputMode::LayerInput;
+        self.input_buffer = self.filter_stack.layers().count().to_string();
+    }
+
+    /// Remove the layer numbered as typed in the prompt (as in the breadcrumb)
+    pub fn submit_layer(&mut self) {
+        match self.input_buffer.trim().parse::<usize>() {
+            Ok(n) if (1..=self.filter_stack.layers().count()).contains(&n) => self.remove_filter(n - 1),
+            _ => {
+                self.status_msg = Some((
+                    format!("无效的过滤层: {}", self.input_buffer.trim()),
+                    Instant::now(),
+                ))
+            }
+        }
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+    }
+
+    pub fn enter_context_mode(&mut self) {
+        self.input_mode = InputMode::ContextInput;
+        self.input_buffer = if self.context_lines.is_empty() {
+            String::new()
+        } else {
+            self.context_lines.label()
+        };
+    }
+
+    /// Apply the context setting typed in the prompt, keeping the selected entry
+    pub fn submit_context(&mut self) {
+        match ContextLines::parse(&self.input_buffer) {
+            Ok(lines) => {
+                self.context_lines = lines;
+                self.apply_filter_keeping_selection();
+                if self.is_focus_mode() {
+                    self.focus_update_search();
+                }
+                let label = if lines.is_empty() {
+                    "关闭".to_string()
+                } else {
+                    lines.label()
+                };
+                self.status_msg = Some((format!("上下文: {}", label), Instant::now()));
+            }
+            Err(e) => self.status_msg = Some((e.to_string(), Instant::now())),
+        }
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+    }
+
     pub fn jump_to_top(&mut self) {
         if !self.filtered_entries.is_empty() {
             self.list_state.select(Some(0));
@@ -657,6 +1248,26 @@ impl App {
         }
     }
 
+    /// Whether the selection is on the last row, so tailing keeps it there
+    pub fn is_following(&self) -> bool {
+        self.list_state
+            .selected()
+            .is_none_or(|i| i + 1 >= self.filtered_entries.len())
+    }
+
+    /// Jump to the newest row and keep following it
+    pub fn follow_tail(&mut self) {
+        self.unseen_lines = 0;
+        self.jump_to_bottom();
+    }
+
+    pub fn toggle_tailing(&mut self) {
+        self.is_tailing = !self.is_tailing;
+        if self.is_tailing {
+            self.follow_tail();
+        }
+    }
+
     pub fn enter_ai_prompt_mode(&mut self) {
         self.input_mode = InputMode::AiPromptInput;
         self.input_buffer.clear();
@@ -754,7 +1365,7 @@ impl App {
         if let ExportState::Confirm(export_type) = self.export_state.clone() {
             self.export_state = ExportState::Exporting(export_type.clone());
 
-            let filtered_entries = self.filtered_entries.clone();
+            let filtered_entries: Vec<DisplayEntry> = self.filtered_iter().cloned().collect();
             let stats = self.stats.clone();
             let chat_history = self.chat_history.clone();
             let export_type_clone = export_type.clone();
@@ -779,6 +1390,127 @@ impl App {
         self.export_state = ExportState::Idle;
     }
 
+    /// Replace the highlights with a template's rules; a template saved without any
+    /// leaves them as they are
+    pub fn load_template_highlights(&mut self, rules: Vec<HighlightRule>) {
+        if !rules.is_empty() {
+            self.highlights = rules
+                .into_iter()
+                .filter_map(|rule| Highlight::new(rule).ok())
+                .collect();
+        }
+    }
+
+    // ========== Template Manager Methods ==========
+
+    pub fn open_template_manager(&mut self) {
+        self.template_manager.reload();
+        self.switch_view(CurrentView::Templates);
+    }
+
+    /// Open the advanced search form filled from the selected template
+    pub fn load_selected_template(&mut self) {
+        let Some(template) = self.template_manager.selected_template().cloned() else {
+            return;
+        };
+        self.switch_view(CurrentView::Logs);
+        self.search_form.open();
+        self.search_form.load_from_criteria(&template.criteria);
+        self.load_template_highlights(template.highlights);
+        self.search_form.set_status(format!("已加载模板 '{}'", template.name));
+    }
+
+    /// Delete the selected template on the second press, asking on the first
+    pub fn delete_selected_template(&mut self) {
+        let Some(name) = self.template_manager.selected_template().map(|t| t.name.clone()) else {
+            return;
+        };
+        if !self.template_manager.confirm_delete {
+            self.template_manager.confirm_delete = true;
+            self.status_msg = Some((format!("再按 d 删除模板 '{}'", name), Instant::now()));
+            return;
+        }
+        let msg = match templates::delete_template(&name) {
+            Ok(()) => format!("已删除模板 '{}'", name),
+            Err(e) => e,
+        };
+        self.template_manager.reload();
+        self.status_msg = Some((msg, Instant::now()));
+    }
+
+    pub fn duplicate_selected_template(&mut self) {
+        let Some(name) = self.template_manager.selected_template().map(|t| t.name.clone()) else {
+            return;
+        };
+        let msg = match templates::duplicate_template(&name) {
+            Ok(copy) => {
+                self.template_manager.reload_at(&copy);
+                format!("已复制为 '{}'", copy)
+            }
+            Err(e) => e,
+        };
+        self.status_msg = Some((msg, Instant::now()));
+    }
+
+    /// Prompt for a new name (rename) or a file path (export, import)
+    pub fn start_template_input(&mut self, mode: InputMode) {
+        self.input_buffer = match mode {
+            InputMode::TemplateRenameInput => match self.template_manager.selected_template() {
+                Some(t) => t.name.clone(),
+                None => return,
+            },
+            InputMode::TemplateExportInput => "loginsight_templates.toml".to_string(),
+            _ => String::new(),
+        };
+        self.template_manager.overwrite_import = None;
+        self.input_mode = mode;
+    }
+
+    pub fn submit_template_input(&mut self) {
+        let input = self.input_buffer.trim().to_string();
+        let result = match self.input_mode {
+            InputMode::TemplateRenameInput => {
+                let name = self
+                    .template_manager
+                    .selected_template()
+                    .map(|t| t.name.clone())
+                    .unwrap_or_default();
+                templates::rename_template(&name, &input).map(|()| {
+                    self.template_manager.reload_at(&input);
+                    format!("已重命名为 '{}'", input)
+                })
+            }
+            InputMode::TemplateExportInput => templates::export_templates(Path::new(&input))
+                .map(|n| format!("已导出 {} 个模板到 {}", n, input)),
+            InputMode::TemplateImportInput => {
+                let confirmed = self
+                    .template_manager
+                    .overwrite_import
+                    .as_ref()
+                    .is_some_and(|(path, _)| *path == input);
+                match templates::import_conflicts(Path::new(&input)) {
+                    Ok(names) if !names.is_empty() && !confirmed => {
+                        // Keep the prompt open; Enter again on the same file overwrites
+                        let msg = format!("将覆盖同名模板: {}，再按 Enter 确认导入", names.join(", "));
+                        self.status_msg = Some((msg, Instant::now()));
+                        self.template_manager.overwrite_import = Some((input, names));
+                        return;
+                    }
+                    Ok(_) => templates::import_templates(Path::new(&input)).map(|(n, replaced)| {
+                        self.template_manager.reload();
+                        format!("已导入 {} 个模板 (覆盖 {} 个同名模板)", n, replaced)
+                    }),
+                    Err(e) => Err(e),
+                }
+            }
+            _ => return,
+        };
+        self.status_msg = Some((result.unwrap_or_else(|e| e), Instant::now()));
+        self.template_manager.overwrite_import = None;
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+    }
+
     pub fn execute_history_entry(&mut self, entry: &crate::history::HistoryEntry) {
         use crate::history::CommandType;
         match entry.kind {
@@ -790,11 +1522,11 @@ impl App {
             CommandType::Jump => {
                 self.current_view = CurrentView::Logs;
                 if let Ok(line) = entry.content.parse::<u32>() {
-                    for (i, e) in self.filtered_entries.iter().enumerate() {
-                        if e.get_line_num() == Some(line) {
-                            self.list_state.select(Some(i));
-                            break;
-                        }
+                    let found = self
+                        .filtered_iter()
+                        .position(|e| e.get_line_num() == Some(line));
+                    if let Some(i) = found {
+                        self.list_state.select(Some(i));
                     }
                 }
             }
@@ -807,30 +1539,37 @@ impl App {
 
     // ========== Focus Mode Methods ==========
 
-    /// Enter focus mode with the current search query
-    /// Creates a filtered view containing only matching log lines
-    pub fn enter_focus_mode(&mut self, query: String) {
-        // Clone the matching entries to focus_logs
-        self.focus_mode.focus_logs = if let Some(re) = &self.search_regex {
-            self.filtered_entries
-                .iter()
-                .filter(|e| {
-                    let matches = re.is_match(&e.get_searchable_text());
-                    if self.negative_search {
-                        !matches
-                    } else {
-                        matches
-                    }
-                })
-                .cloned()
-                .collect()
-        } else {
-            // If no search regex, enter focus mode with all currently filtered entries
-            self.filtered_entries.clone()
-        };
+    /// Enter focus mode on the filtered entries matching `criteria`
+    /// Creates a filtered view containing only matching log lines; the matches are
+    /// found off the UI thread and the view is shown once they are in
+    fn enter_focus_mode(&mut self, query: String, criteria: SearchCriteria) {
+        self.stop_focus_job();
+        if criteria.is_empty() {
+            // If no search query, enter focus mode with all currently filtered entries
+            self.show_focus(query, self.filtered_entries.clone(), false);
+            return;
+        }
+        let rows = self.filtered_entries.clone();
+        let job = SearchJob::spawn(
+            self.all_entries.clone(),
+            self.search_index.clone(),
+            rows.clone(),
+            criteria,
+        );
+        self.focus_job = Some(FocusJob {
+            query,
+            rows,
+            replaced_from: usize::MAX,
+            job,
+        });
+    }
 
-        // Store original focus logs for sub-search
-        self.focus_mode.original_focus_logs = self.focus_mode.focus_logs.clone();
+    /// Show the focus view of `matches` (sorted indices into `all_entries`)
+    fn show_focus(&mut self, query: String, matches: Vec<usize>, searching: bool) {
+        // Store the matching entries as the base for sub-searches
+        self.focus_mode.original_focus_logs = matches.clone();
+        self.focus_mode.focus_searching = searching;
+        self.show_focus_matches(matches, searching);
 
         // Store the query for display
         self.focus_mode.focus_query = query;
@@ -841,8 +1580,20 @@ impl App {
             self.focus_mode.focus_table_state.select(Some(0));
         }
 
-        // Store match indices (all indices in focus mode are "matches")
-        self.focus_mode.focus_match_indices = (0..self.focus_mode.focus_logs.len()).collect();
+        // Store match indices (every row unless context rows are shown)
+        self.focus_mode.focus_match_indices = self
+            .focus_mode
+            .focus_logs
+            .iter()
+            .enumerate()
+            .filter(|(_, idx)| {
+                self.focus_mode
+                    .context_marks
+                    .get(idx)
+                    .is_none_or(|m| m.role == RowRole::Match)
+            })
+            .map(|(i, _)| i)
+            .collect();
         self.focus_mode.focus_current_match = 0;
 
         // Switch to focus view
@@ -853,21 +1604,23 @@ impl App {
     pub fn focus_update_search(&mut self) {
         if self.search_query.is_empty() {
             // Reset to original logs if search is cleared
-            self.focus_mode.focus_logs = self.focus_mode.original_focus_logs.clone();
-        } else {
-            let negative = self.search_query.starts_with('!');
-            let pattern = if negative { &self.search_query[1..] } else { &self.search_query };
-            
-            if let Ok(re) = Regex::new(pattern) {
-                self.focus_mode.focus_logs = self.focus_mode.original_focus_logs
-                    .iter()
-                    .filter(|e| {
-                        let matches = re.is_match(&e.get_searchable_text());
-                        if negative { !matches } else { matches }
-                    })
-                    .cloned()
-                    .collect();
-            }
+            let matches = self.focus_mode.original_focus_logs.clone();
+            self.show_focus_matches(matches, self.focus_mode.focus_searching);
+        } else if let Some(query) = self.check_search_query() {
+            let criteria = SearchCriteria {
+                query: Some(query),
+                ..SearchCriteria::default()
+            };
+            let candidates = self.search_index.criteria_candidates(&criteria);
+            let matches = self.focus_mode.original_focus_logs
+                .iter()
+                .copied()
+                .filter(|&idx| {
+                    candidates.contains(idx)
+                        && matches_criteria(&self.all_entries[idx], &criteria, &None)
+                })
+                .collect();
+            self.show_focus_matches(matches, true);
         }
         
         // Update focus query display
@@ -884,6 +1637,24 @@ impl App {
         }
     }
 
+    /// Show `matches` (sorted indices into `all_entries`) in the focus list, with
+    /// context from the filtered list around them when `searching`
+    fn show_focus_matches(&mut self, matches: Vec<usize>, searching: bool) {
+        let lines = self.active_context(searching);
+        if lines.is_empty() {
+            self.focus_mode.context_marks.clear();
+            self.focus_mode.focus_logs = matches;
+            return;
+        }
+        let mut window = ContextWindow::new(lines);
+        let mut rows = Vec::new();
+        for &idx in &self.filtered_entries {
+            window.push(idx, matches.binary_search(&idx).is_ok(), &mut rows);
+        }
+        self.focus_mode.context_marks = rows.iter().copied().collect();
+        self.focus_mode.focus_logs = rows.into_iter().map(|(i, _)| i).collect();
+    }
+
     /// Exit focus mode and return to normal log view
     pub fn exit_focus_mode(&mut self) {
         self.focus_mode.reset();
@@ -896,9 +1667,9 @@ impl App {
         matches!(self.current_view, CurrentView::Focus)
     }
 
-    /// Get the current entries based on view mode
+    /// Get the current entries (indices into `all_entries`) based on view mode
     #[allow(dead_code)]
-    pub fn get_current_entries(&self) -> &[DisplayEntry] {
+    pub fn get_current_entries(&self) -> &[usize] {
         if self.is_focus_mode() {
             &self.focus_mode.focus_logs
         } else {
@@ -923,11 +1694,9 @@ impl App {
             self.focus_mode
                 .focus_table_state
                 .selected()
-                .and_then(|i| self.focus_mode.focus_logs.get(i))
+                .and_then(|i| self.focus_entry(i))
         } else {
-            self.list_state
-                .selected()
-                .and_then(|i| self.filtered_entries.get(i))
+            self.selected_entry()
         }
     }
 
@@ -1010,3 +1779,86 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: usize) -> LogEntry {
+        LogEntry {
+            timestamp: "".into(),
+            time: None,
+            pid: "".into(),
+            tid: "".into(),
+            level: "Info".into(),
+            content: format!("line {}", n).into(),
+            source_file: "".into(),
+            line_num: n as u32,
+            json_payload: None,
+            delta_ms: None,
+            source_id: 0,
+            line_index: n,
+            kind: EntryKind::Record,
+        }
+    }
+
+    fn app(count: usize) -> App {
+        let (ai_tx, _) = mpsc::channel(1);
+        let (_, ai_rx) = mpsc::channel(1);
+        let (chat_tx, _) = mpsc::channel(1);
+        let (_, chat_rx) = mpsc::channel(1);
+        let (export_tx, export_rx) = std_mpsc::channel();
+        let (report_tx, _) = mpsc::channel(1);
+        let (_, report_rx) = mpsc::channel(1);
+        // Loaded through the pipeline as at startup, so tailing continues from it
+        let mut pipeline = Pipeline::default();
+        let mut entries = EntryList::default();
+        pipeline.process(&mut entries, (0..count).map(entry).collect());
+        let file = FileInfo {
+            id: 0,
+            name: "app.log".into(),
+            color: Color::White,
+            enabled: true,
+            profile: "default".into(),
+            encoding: crate::encoding::TextEncoding::Utf8,
+            parse_failures: 0,
+        };
+        let mut app = App::new(
+            entries,
+            Vec::new(),
+            vec![file],
+            ai_tx,
+            ai_rx,
+            chat_tx,
+            chat_rx,
+            export_rx,
+            export_tx,
+            report_tx,
+            report_rx,
+            20,
+        );
+        app.pipeline = pipeline;
+        app
+    }
+
+    #[test]
+    fn test_tail_follows_only_from_last_row() {
+        let mut app = app(3);
+        app.list_state.select(Some(2));
+        app.append_entries(vec![entry(3), entry(4)]);
+        assert_eq!(app.list_state.selected(), Some(4));
+        assert_eq!(app.unseen_lines, 0);
+
+        // Looking at an earlier row pins the selection and counts what arrives
+        app.list_state.select(Some(1));
+        app.append_entries(vec![entry(5), entry(6)]);
+        assert_eq!(app.list_state.selected(), Some(1));
+        assert_eq!(app.unseen_lines, 2);
+        assert!(!app.is_following());
+
+        app.follow_tail();
+        assert_eq!(app.list_state.selected(), Some(6));
+        assert_eq!(app.unseen_lines, 0);
+        assert!(app.is_following());
+    }
+}
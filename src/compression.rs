@@ -0,0 +1,116 @@
+//! Transparent decompression of rotated log archives (`.gz`, `.zst`, `.bz2`)
+//!
+//! Formats are recognised by their magic bytes, so renamed archives still work and
+//! plain files whose names merely end in `.gz` are left alone.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Identify a compressed stream from its first bytes
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if header.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    /// Decompress a whole archive into memory
+    ///
+    /// Concatenated gzip members (as produced by `cat a.gz b.gz`) are all read.
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self {
+            Compression::Gzip => {
+                flate2::read::MultiGzDecoder::new(data).read_to_end(&mut out)?;
+            }
+            Compression::Zstd => {
+                zstd::stream::read::Decoder::new(data)?.read_to_end(&mut out)?;
+            }
+            Compression::Bzip2 => {
+                bzip2::read::MultiBzDecoder::new(data).read_to_end(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Whether the file at `path` starts with a known compression header
+pub fn is_compressed(path: &Path) -> bool {
+    let mut header = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read(&mut header))
+        .is_ok_and(|n| Compression::detect(&header[..n]).is_some())
+}
+
+/// Decompress `data` if it is an archive, naming the file in errors
+pub fn decompress_if_needed(data: &[u8], path: &Path) -> Result<Option<Vec<u8>>> {
+    Compression::detect(data)
+        .map(|c| {
+            c.decompress(data)
+                .with_context(|| format!("无法解压 ({}): {:?}", c.name(), path))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TEXT: &[u8] = b"2024-01-15 10:00:00.000 first\n2024-01-15 10:00:01.000 second\n";
+
+    #[test]
+    fn test_round_trip_each_format() {
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(TEXT).unwrap();
+        let gz = gz.finish().unwrap();
+
+        let zst = zstd::stream::encode_all(TEXT, 0).unwrap();
+
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(TEXT).unwrap();
+        let bz = bz.finish().unwrap();
+
+        for (data, kind) in [
+            (gz, Compression::Gzip),
+            (zst, Compression::Zstd),
+            (bz, Compression::Bzip2),
+        ] {
+            assert_eq!(Compression::detect(&data), Some(kind));
+            let path = Path::new("x");
+            assert_eq!(decompress_if_needed(&data, path).unwrap().unwrap(), TEXT);
+        }
+    }
+
+    #[test]
+    fn test_plain_text_untouched() {
+        assert_eq!(Compression::detect(TEXT), None);
+        assert!(decompress_if_needed(TEXT, Path::new("a.log"))
+            .unwrap()
+            .is_none());
+        assert!(Compression::Gzip.decompress(b"\x1f\x8bgarbage").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
@@ -11,12 +12,106 @@ pub struct AppConfig {
     pub parser: ParserConfig,
     pub filters: FiltersConfig,
     pub theme: ThemeConfig,
+    /// Patterns always highlighted in the log list, detail pane and focus mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlight: Vec<HighlightRule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParserConfig {
     pub log_pattern: String,
     pub timestamp_format: String,
+    /// Number of leading lines sampled when auto-detecting a file's profile
+    #[serde(default = "default_detect_sample_lines")]
+    pub detect_sample_lines: usize,
+    /// Regex marking the first line of a record for the default profile;
+    /// unset means "starts with a `timestamp_format` timestamp"
+    #[serde(default)]
+    pub record_start: Option<String>,
+    /// Regexes for lines that always continue the previous record, in every profile
+    #[serde(default = "default_continuation_patterns")]
+    pub continuation_patterns: Vec<String>,
+    /// Named parser profiles, tried alongside the default `log_pattern`
+    #[serde(default)]
+    pub profiles: Vec<ProfileConfig>,
+    /// Settings forced on files whose path or name matches a glob
+    #[serde(default)]
+    pub file_overrides: Vec<FileOverrideConfig>,
+    /// Milliseconds without new data after which live tail releases a record it is
+    /// still holding back as possibly incomplete; 0 releases every read immediately
+    #[serde(default = "default_tail_quiet_ms")]
+    pub tail_quiet_ms: u64,
+    /// Zone of the default profile's timestamps when they carry no offset; unset means local
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// Per-file settings, matched by glob against the full path or the file name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileOverrideConfig {
+    pub pattern: String,
+    /// Text encoding, replacing detection and the profile's encoding
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Zone of offset-less timestamps, replacing the profile's timezone
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Milliseconds added to every timestamp, correcting a machine whose clock is off
+    #[serde(default)]
+    pub clock_skew_ms: Option<i64>,
+}
+
+impl FileOverrideConfig {
+    pub fn applies_to(&self, path: &Path) -> bool {
+        glob::Pattern::new(&self.pattern).is_ok_and(|p| {
+            p.matches_path(path)
+                || path
+                    .file_name()
+                    .is_some_and(|n| p.matches(&n.to_string_lossy()))
+        })
+    }
+}
+
+/// A named log format and the mapping from its parts to `LogEntry` fields
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    pub name: String,
+    #[serde(default)]
+    pub format: ProfileFormat,
+    /// Line regex (regex format only)
+    #[serde(default)]
+    pub pattern: String,
+    /// `LogEntry` field name -> capture group name/index (regex) or key (json/logfmt).
+    /// Keys may list alternatives separated by `|`; JSON keys may be dotted paths.
+    /// Empty means capture groups are named after the fields / the default JSON keys apply.
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+    /// strftime format of the timestamp field; ISO-8601, epoch and syslog are always tried
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+    /// Regex marking the first line of a record, replacing the format's default
+    #[serde(default)]
+    pub record_start: Option<String>,
+    /// Extra continuation regexes for this profile, on top of `parser.continuation_patterns`
+    #[serde(default)]
+    pub continuation: Vec<String>,
+    /// Text encoding of files using this profile; unset means detect per file
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Zone of timestamps without a UTC offset (`UTC`, `+08:00`, ...); unset means local
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    #[default]
+    Regex,
+    /// JSON Lines: one object per line
+    Json,
+    /// logfmt: `key=value` pairs per line
+    Logfmt,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,16 +142,51 @@ pub struct ThemeConfig {
     pub page_size: usize,
 }
 
+/// A `[[highlight]]` rule: text matching `pattern` is drawn in `color` and `style`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    /// Color name (`Red`, `LightCyan`, ...) or `#rrggbb`
+    #[serde(default = "default_highlight_color")]
+    pub color: String,
+    /// Modifiers separated by spaces or commas: bold, italic, underline, reverse, dim
+    #[serde(default)]
+    pub style: String,
+}
+
+fn default_highlight_color() -> String {
+    "Magenta".into()
+}
+
 fn default_page_size() -> usize {
     20
 }
 
+fn default_detect_sample_lines() -> usize {
+    50
+}
+
+fn default_tail_quiet_ms() -> u64 {
+    500
+}
+
+fn default_continuation_patterns() -> Vec<String> {
+    vec![
+        r"^\s".into(),
+        r"^at ".into(),
+        r"^Caused by:".into(),
+        r"^\.\.\. \d+ more".into(),
+        r"^[}\]]".into(),
+    ]
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             parser: ParserConfig::default(),
             filters: FiltersConfig::default(),
             theme: ThemeConfig::default(),
+            highlight: vec![],
         }
     }
 }
@@ -66,6 +196,13 @@ impl Default for ParserConfig {
         Self {
             log_pattern: r"^(\d{4}-\d{2}-\d{2}\s\d{2}:\d{2}:\d{2}\.\d+)\[([0-9a-f]+):([0-9a-f]+)\]\[(\w+)\]:\s*(.*)\((.+):(\d+)\)\s*$".into(),
             timestamp_format: "%Y-%m-%d %H:%M:%S%.3f".into(),
+            detect_sample_lines: default_detect_sample_lines(),
+            record_start: None,
+            continuation_patterns: default_continuation_patterns(),
+            profiles: vec![],
+            file_overrides: vec![],
+            tail_quiet_ms: default_tail_quiet_ms(),
+            timezone: None,
         }
     }
 }
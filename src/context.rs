@@ -0,0 +1,194 @@
+//! grep-style context lines around filter matches
+//!
+//! With context set (`-B`/`-A`/`-C`), the filtered and focus views also show the
+//! entries just before and after each match, the way `grep -C` does. Rows that don't
+//! directly follow the previous shown row start a new group, drawn with a separator.
+
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, bail, Result};
+
+/// How many entries to show before and after each match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextLines {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl ContextLines {
+    pub fn is_empty(&self) -> bool {
+        self.before == 0 && self.after == 0
+    }
+
+    /// Parse `3` (both sides), `B2 A5`, `C3` or `-B2 -A5`; empty input turns it off
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut lines = Self::default();
+        for word in input.split_whitespace() {
+            let word = word.trim_start_matches('-');
+            let (kind, count) = match word.find(|c: char| c.is_ascii_digit()) {
+                Some(at) => word.split_at(at),
+                None => bail!("无效的上下文: {} (如 3、B2 A5)", word),
+            };
+            let count: usize = count
+                .parse()
+                .map_err(|_| anyhow!("无效的上下文: {} (如 3、B2 A5)", word))?;
+            match kind.to_ascii_uppercase().as_str() {
+                "" | "C" => lines = Self { before: count, after: count },
+                "B" => lines.before = count,
+                "A" => lines.after = count,
+                _ => bail!("无效的上下文: {} (如 3、B2 A5)", word),
+            }
+        }
+        Ok(lines)
+    }
+
+    /// `-C3`, or `-B2 -A5` when the sides differ
+    pub fn label(&self) -> String {
+        if self.before == self.after {
+            format!("-C{}", self.before)
+        } else {
+            format!("-B{} -A{}", self.before, self.after)
+        }
+    }
+}
+
+/// What a shown row is, when context is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowRole {
+    Match,
+    Context,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextMark {
+    pub role: RowRole,
+    /// Not adjacent to the row shown before it, so a separator goes above it
+    pub group_start: bool,
+}
+
+/// Picks the rows to show from the entries a filter runs over, in order
+///
+/// Entries are pushed one at a time, so a batch that arrives while tailing carries
+/// on where the previous one ended: its first entries may be after-context of an
+/// earlier match, and a new match brings in the held entries before it.
+#[derive(Debug, Default)]
+pub struct ContextWindow {
+    lines: ContextLines,
+    /// Recent non-matching entries that become context if a match follows, each with
+    /// the entry pushed before it
+    held: VecDeque<(usize, Option<usize>)>,
+    after_left: usize,
+    last_pushed: Option<usize>,
+    last_shown: Option<usize>,
+}
+
+impl ContextWindow {
+    pub fn new(lines: ContextLines) -> Self {
+        Self {
+            lines,
+            ..Self::default()
+        }
+    }
+
+    /// Feed the next entry, adding the rows it makes visible to `out`
+    pub fn push(&mut self, idx: usize, is_match: bool, out: &mut Vec<(usize, ContextMark)>) {
+        let prev = self.last_pushed.replace(idx);
+        if is_match {
+            while let Some((held, held_prev)) = self.held.pop_front() {
+                self.show(held, held_prev, RowRole::Context, out);
+            }
+            self.show(idx, prev, RowRole::Match, out);
+            self.after_left = self.lines.after;
+        } else if self.after_left > 0 {
+            self.after_left -= 1;
+            self.show(idx, prev, RowRole::Context, out);
+        } else if self.lines.before > 0 {
+            if self.held.len() == self.lines.before {
+                self.held.pop_front();
+            }
+            self.held.push_back((idx, prev));
+        }
+    }
+
+    fn show(
+        &mut self,
+        idx: usize,
+        prev: Option<usize>,
+        role: RowRole,
+        out: &mut Vec<(usize, ContextMark)>,
+    ) {
+        let group_start =
+            !self.lines.is_empty() && self.last_shown.is_some() && self.last_shown != prev;
+        self.last_shown = Some(idx);
+        out.push((idx, ContextMark { role, group_start }));
+    }
+
+    /// Forget entries from `changed` on, which were replaced; `last_kept` is the last
+    /// shown row before them
+    pub fn rewind(&mut self, changed: usize, last_kept: Option<usize>) {
+        self.held.retain(|&(idx, _)| idx < changed);
+        self.last_shown = last_kept;
+        if self.last_pushed.is_some_and(|idx| idx >= changed) {
+            self.last_pushed = self.held.back().map(|&(idx, _)| idx).max(last_kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lines: ContextLines, matches: &[usize], len: usize) -> Vec<(usize, ContextMark)> {
+        let mut window = ContextWindow::new(lines);
+        let mut out = Vec::new();
+        for idx in 0..len {
+            window.push(idx, matches.contains(&idx), &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn test_context_groups() {
+        let lines = ContextLines::parse("-B1 -A2").unwrap();
+        let rows = run(lines, &[3, 5, 12], 15);
+        let shown: Vec<usize> = rows.iter().map(|(i, _)| *i).collect();
+        assert_eq!(shown, vec![2, 3, 4, 5, 6, 7, 11, 12, 13, 14]);
+        assert_eq!(rows[1].1.role, RowRole::Match);
+        assert_eq!(rows[2].1.role, RowRole::Context);
+        let starts: Vec<usize> = rows
+            .iter()
+            .filter(|(_, m)| m.group_start)
+            .map(|(i, _)| *i)
+            .collect();
+        assert_eq!(starts, vec![11]);
+
+        // Without context only matches are shown, with no separators
+        let rows = run(ContextLines::default(), &[3, 12], 15);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|(_, m)| !m.group_start));
+    }
+
+    #[test]
+    fn test_rewind_replaced_tail() {
+        let mut window = ContextWindow::new(ContextLines { before: 1, after: 1 });
+        let mut out = Vec::new();
+        for idx in 0..4 {
+            window.push(idx, idx == 1, &mut out);
+        }
+        // Entry 3 was held; it gets replaced by a fold, which then matches
+        window.rewind(3, Some(2));
+        out.clear();
+        window.push(3, true, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].1, ContextMark { role: RowRole::Match, group_start: false });
+    }
+
+    #[test]
+    fn test_parse_context() {
+        assert_eq!(ContextLines::parse("3").unwrap(), ContextLines { before: 3, after: 3 });
+        assert_eq!(ContextLines::parse("b2 A5").unwrap().label(), "-B2 -A5");
+        assert!(ContextLines::parse("").unwrap().is_empty());
+        assert!(ContextLines::parse("X2").is_err());
+        assert!(ContextLines::parse("B").is_err());
+    }
+}
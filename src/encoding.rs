@@ -0,0 +1,181 @@
+//! Per-file text encoding detection and decoding
+//!
+//! Records are split on `\n` bytes, so UTF-16 input is transcoded to UTF-8 up front
+//! by [`TextEncoding::line_bytes`]; every other supported encoding is ASCII-compatible
+//! and decoded record by record.
+
+use std::borrow::Cow;
+
+use encoding_rs::{GB18030, UTF_16BE, UTF_16LE};
+
+/// Bytes inspected by [`detect_encoding`]
+pub const SAMPLE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Gb18030,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 5] = [
+        TextEncoding::Utf8,
+        TextEncoding::Gb18030,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+        TextEncoding::Latin1,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Gb18030 => "gb18030",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Latin1 => "latin-1",
+        }
+    }
+
+    /// Look up an encoding by name, ignoring case and `-`/`_` (so `UTF8`, `gbk` and
+    /// `iso-8859-1` work too)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let key: String = name
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+        match key.as_str() {
+            "utf8" => Some(TextEncoding::Utf8),
+            "gb18030" | "gbk" | "gb2312" => Some(TextEncoding::Gb18030),
+            "utf16le" | "utf16" => Some(TextEncoding::Utf16Le),
+            "utf16be" => Some(TextEncoding::Utf16Be),
+            "latin1" | "iso88591" => Some(TextEncoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Parse a configured encoding name, failing with the list of supported names
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        Self::from_name(name).ok_or_else(|| {
+            let names: Vec<_> = Self::ALL.iter().map(|e| e.name()).collect();
+            anyhow::anyhow!("未知的编码: {} (支持: {})", name, names.join(", "))
+        })
+    }
+
+    /// Raw file bytes in a form that can be split into lines on `b'\n'`
+    ///
+    /// UTF-16 is transcoded to UTF-8 and byte order marks are dropped.
+    pub fn line_bytes(self, data: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let encoding = if self == TextEncoding::Utf16Le { UTF_16LE } else { UTF_16BE };
+                let (decoded, _, _) = encoding.decode(data);
+                Cow::Owned(decoded.into_owned().into_bytes())
+            }
+            _ => Cow::Borrowed(data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data)),
+        }
+    }
+
+    /// Decode one record taken from [`line_bytes`](Self::line_bytes)
+    ///
+    /// Text that is already valid UTF-8 (including pure ASCII in any encoding) is
+    /// borrowed rather than copied.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            // UTF-16 was already transcoded to UTF-8
+            TextEncoding::Utf8 | TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                String::from_utf8_lossy(bytes)
+            }
+            TextEncoding::Gb18030 => {
+                let (decoded, _, _) = GB18030.decode(bytes);
+                decoded
+            }
+            TextEncoding::Latin1 => match std::str::from_utf8(bytes) {
+                Ok(ascii) if bytes.is_ascii() => Cow::Borrowed(ascii),
+                _ => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+            },
+        }
+    }
+}
+
+/// Guess a file's encoding from its BOM, NUL byte layout and byte validity
+pub fn detect_encoding(data: &[u8]) -> TextEncoding {
+    if data.starts_with(b"\xEF\xBB\xBF") {
+        return TextEncoding::Utf8;
+    }
+    if data.starts_with(b"\xFF\xFE") {
+        return TextEncoding::Utf16Le;
+    }
+    if data.starts_with(b"\xFE\xFF") {
+        return TextEncoding::Utf16Be;
+    }
+
+    let mut sample = &data[..data.len().min(SAMPLE_BYTES)];
+    // Don't let the sample boundary split a multi-byte character
+    if sample.len() < data.len() {
+        if let Some(end) = sample.iter().rposition(|&b| b == b'\n') {
+            sample = &sample[..end];
+        }
+    }
+    // ASCII text in UTF-16 has a NUL in every other byte
+    let pairs = sample.len() / 2;
+    if pairs > 0 {
+        let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd_nuls = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+        if odd_nuls * 10 > pairs * 3 && even_nuls * 10 < pairs {
+            return TextEncoding::Utf16Le;
+        }
+        if even_nuls * 10 > pairs * 3 && odd_nuls * 10 < pairs {
+            return TextEncoding::Utf16Be;
+        }
+    }
+
+    if std::str::from_utf8(sample).is_ok() {
+        return TextEncoding::Utf8;
+    }
+    if GB18030
+        .decode_without_bom_handling_and_without_replacement(sample)
+        .is_some()
+    {
+        TextEncoding::Gb18030
+    } else {
+        TextEncoding::Latin1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_by_bom_and_heuristics() {
+        assert_eq!(detect_encoding(b"\xEF\xBB\xBFhello"), TextEncoding::Utf8);
+        assert_eq!(detect_encoding("日志 ok\n".as_bytes()), TextEncoding::Utf8);
+        assert_eq!(detect_encoding(b"h\0i\0\n\0"), TextEncoding::Utf16Le);
+        assert_eq!(detect_encoding(b"\0h\0i\0\n"), TextEncoding::Utf16Be);
+        let (gbk, _, _) = GB18030.encode("错误: 连接失败\n");
+        assert_eq!(detect_encoding(&gbk), TextEncoding::Gb18030);
+        assert_eq!(detect_encoding(b"caf\xE9 au lait\n"), TextEncoding::Latin1);
+    }
+
+    #[test]
+    fn test_utf16_lines_and_decode() {
+        let data = b"\xFF\xFEa\0\n\0b\0";
+        let enc = detect_encoding(data);
+        let bytes = enc.line_bytes(data);
+        let lines: Vec<_> = bytes.split(|&b| b == b'\n').map(|l| enc.decode(l)).collect();
+        assert_eq!(lines, vec!["a", "b"]);
+        assert_eq!(TextEncoding::Latin1.decode(b"caf\xE9"), "café");
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(TextEncoding::from_name("UTF8"), Some(TextEncoding::Utf8));
+        assert_eq!(TextEncoding::from_name("gbk"), Some(TextEncoding::Gb18030));
+        assert_eq!(TextEncoding::from_name("ISO-8859-1"), Some(TextEncoding::Latin1));
+        assert!(TextEncoding::parse("ebcdic").is_err());
+    }
+}
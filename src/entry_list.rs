@@ -0,0 +1,151 @@
+//! Storage for the session's entries that snapshots can share
+//!
+//! Entries are kept in chunks of [`CHUNK_SIZE`] behind `Arc`s. Cloning the list, as a
+//! background search does to keep its snapshot, only clones the chunk pointers, and
+//! changing the end of the list while a snapshot is alive copies the last chunk at
+//! most, never the whole list.
+
+use std::ops::Index;
+use std::sync::Arc;
+
+use crate::models::DisplayEntry;
+
+/// Entries per chunk
+pub const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Default)]
+pub struct EntryList {
+    /// Every chunk but the last is full
+    chunks: Vec<Arc<Vec<DisplayEntry>>>,
+    len: usize,
+}
+
+impl EntryList {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&DisplayEntry> {
+        if idx < self.len {
+            Some(&self.chunks[idx / CHUNK_SIZE][idx % CHUNK_SIZE])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &DisplayEntry> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    /// The entries from `start` on
+    pub fn iter_from(&self, start: usize) -> impl Iterator<Item = &DisplayEntry> + '_ {
+        let chunks = self.chunks.get(start / CHUNK_SIZE..).unwrap_or(&[]);
+        let skip = start % CHUNK_SIZE;
+        chunks
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, chunk)| chunk[if i == 0 { skip.min(chunk.len()) } else { 0 }..].iter())
+    }
+
+    pub fn push(&mut self, entry: DisplayEntry) {
+        if self.chunks.last().is_none_or(|chunk| chunk.len() == CHUNK_SIZE) {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_SIZE)));
+        }
+        let last = self.chunks.last_mut().unwrap();
+        Arc::make_mut(last).push(entry);
+        self.len += 1;
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut DisplayEntry> {
+        self.chunks.last_mut().and_then(|chunk| Arc::make_mut(chunk).last_mut())
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.chunks.truncate(len.div_ceil(CHUNK_SIZE));
+        let keep = len - self.chunks.len().saturating_sub(1) * CHUNK_SIZE;
+        if let Some(last) = self.chunks.last_mut() {
+            if keep < last.len() {
+                Arc::make_mut(last).truncate(keep);
+            }
+        }
+        self.len = len;
+    }
+}
+
+impl Extend<DisplayEntry> for EntryList {
+    fn extend<I: IntoIterator<Item = DisplayEntry>>(&mut self, iter: I) {
+        for entry in iter {
+            self.push(entry);
+        }
+    }
+}
+
+impl From<Vec<DisplayEntry>> for EntryList {
+    fn from(entries: Vec<DisplayEntry>) -> Self {
+        let mut list = EntryList::default();
+        list.extend(entries);
+        list
+    }
+}
+
+impl Index<usize> for EntryList {
+    type Output = DisplayEntry;
+
+    fn index(&self, idx: usize) -> &DisplayEntry {
+        self.get(idx)
+            .unwrap_or_else(|| panic!("entry {} out of range ({})", idx, self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(i: usize) -> DisplayEntry {
+        DisplayEntry::Folded {
+            start_index: i,
+            end_index: i,
+            count: 1,
+            summary_text: i.to_string(),
+        }
+    }
+
+    fn start(e: &DisplayEntry) -> usize {
+        match e {
+            DisplayEntry::Folded { start_index, .. } => *start_index,
+            DisplayEntry::Normal(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_shares_all_but_the_last_chunk() {
+        let mut list = EntryList::from((0..CHUNK_SIZE * 2 + 10).map(entry).collect::<Vec<_>>());
+        let snapshot = list.clone();
+
+        // Replacing the end and appending leave the snapshot and the full chunks alone
+        list.truncate(CHUNK_SIZE * 2 + 5);
+        list.extend((100_000..100_003).map(entry));
+        assert_eq!(list.len(), CHUNK_SIZE * 2 + 8);
+        assert_eq!(snapshot.len(), CHUNK_SIZE * 2 + 10);
+        assert_eq!(start(&snapshot[CHUNK_SIZE * 2 + 5]), CHUNK_SIZE * 2 + 5);
+        assert_eq!(start(&list[CHUNK_SIZE * 2 + 5]), 100_000);
+        assert!(Arc::ptr_eq(&list.chunks[0], &snapshot.chunks[0]));
+        assert!(Arc::ptr_eq(&list.chunks[1], &snapshot.chunks[1]));
+
+        let tail: Vec<usize> = list.iter_from(CHUNK_SIZE * 2 + 3).map(start).collect();
+        assert_eq!(tail, vec![CHUNK_SIZE * 2 + 3, CHUNK_SIZE * 2 + 4, 100_000, 100_001, 100_002]);
+        assert_eq!(list.iter_from(list.len()).count(), 0);
+        assert_eq!(list.iter().rev().map(start).next(), Some(100_002));
+
+        list.truncate(CHUNK_SIZE);
+        assert_eq!(list.chunks.len(), 1);
+        assert_eq!(list.iter().count(), CHUNK_SIZE);
+    }
+}
@@ -1,10 +1,11 @@
 use anyhow::Result;
 use chrono::Local;
 use serde::Serialize;
+use std::cmp::Ordering;
 use std::fs::File;
 use std::io::Write;
 
-use crate::models::{ChatMessage, DashboardStats, DisplayEntry, ExportType};
+use crate::models::{ChatMessage, DashboardStats, DisplayEntry, ExportType, LogEntry};
 
 pub fn generate_filename(prefix: &str, extension: &str) -> String {
     let timestamp = Local::now().format("%Y%m%d_%H%M%S");
@@ -43,16 +44,16 @@ pub fn export_logs_to_csv(entries: &[DisplayEntry]) -> Result<String> {
 }
 
 #[derive(Serialize)]
-struct LogJson {
-    timestamp: String,
-    pid: String,
-    tid: String,
-    level: String,
-    content: String,
-    source_file: String,
+struct LogJson<'a> {
+    timestamp: &'a str,
+    pid: &'a str,
+    tid: &'a str,
+    level: &'a str,
+    content: &'a str,
+    source_file: &'a str,
     line_number: u32,
     delta_ms: Option<i64>,
-    json_payload: Option<serde_json::Value>,
+    json_payload: Option<&'a serde_json::Value>,
 }
 
 pub fn export_logs_to_json(entries: &[DisplayEntry]) -> Result<String> {
@@ -64,15 +65,15 @@ pub fn export_logs_to_json(entries: &[DisplayEntry]) -> Result<String> {
         .filter_map(|entry| {
             if let DisplayEntry::Normal(log) = entry {
                 Some(LogJson {
-                    timestamp: log.timestamp.clone(),
-                    pid: log.pid.clone(),
-                    tid: log.tid.clone(),
-                    level: log.level.clone(),
-                    content: log.content.clone(),
-                    source_file: log.source_file.clone(),
+                    timestamp: &log.timestamp,
+                    pid: &log.pid,
+                    tid: &log.tid,
+                    level: &log.level,
+                    content: &log.content,
+                    source_file: &log.source_file,
                     line_number: log.line_num,
                     delta_ms: log.delta_ms,
-                    json_payload: log.json_payload.clone(),
+                    json_payload: log.json_payload.as_deref(),
                 })
             } else {
                 None
@@ -167,7 +168,7 @@ pub fn export_report(entries: &[DisplayEntry], stats: &DashboardStats) -> Result
 }
 
 fn extract_error_patterns(entries: &[DisplayEntry]) -> Vec<ErrorSummary> {
-    let mut error_map: std::collections::HashMap<String, (usize, String, String)> =
+    let mut error_map: std::collections::HashMap<String, (usize, &LogEntry, &LogEntry)> =
         std::collections::HashMap::new();
 
     for entry in entries {
@@ -176,19 +177,17 @@ fn extract_error_patterns(entries: &[DisplayEntry]) -> Vec<ErrorSummary> {
                 let pattern = if log.content.len() > 100 {
                     format!("{}...", &log.content[..100])
                 } else {
-                    log.content.clone()
+                    log.content.to_string()
                 };
 
-                let count =
-                    error_map
-                        .entry(pattern.clone())
-                        .or_insert((0, String::new(), String::new()));
-                count.0 += 1;
-                if count.1.is_empty() || log.timestamp < count.1 {
-                    count.1 = log.timestamp.clone();
+                let seen = error_map.entry(pattern).or_insert((0, log, log));
+                seen.0 += 1;
+                // Merged files, stdin and unparsed rows don't come in time order
+                if supersedes(log, seen.1, Ordering::Less) {
+                    seen.1 = log;
                 }
-                if count.2.is_empty() || log.timestamp > count.2 {
-                    count.2 = log.timestamp.clone();
+                if supersedes(log, seen.2, Ordering::Greater) {
+                    seen.2 = log;
                 }
             }
         }
@@ -199,8 +198,8 @@ fn extract_error_patterns(entries: &[DisplayEntry]) -> Vec<ErrorSummary> {
         .map(|(pattern, (count, first, latest))| ErrorSummary {
             pattern,
             count,
-            first_occurrence: first,
-            latest_occurrence: latest,
+            first_occurrence: first.timestamp.to_string(),
+            latest_occurrence: latest.timestamp.to_string(),
         })
         .collect();
 
@@ -210,6 +209,16 @@ fn extract_error_patterns(entries: &[DisplayEntry]) -> Vec<ErrorSummary> {
     errors
 }
 
+/// Whether `new` should replace `old` as the occurrence furthest in `order`; rows
+/// without a parsed time are only kept when no timed row is known
+fn supersedes(new: &LogEntry, old: &LogEntry, order: Ordering) -> bool {
+    match (new.time, old.time) {
+        (Some(new), Some(old)) => new.cmp(&old) == order,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
 fn extract_performance_stats(entries: &[DisplayEntry]) -> PerformanceSection {
     let mut deltas: Vec<i64> = Vec::new();
     let mut slow_operations = 0;
@@ -250,7 +259,7 @@ fn extract_source_stats(entries: &[DisplayEntry]) -> Vec<SourceSummary> {
 
     for entry in entries {
         if let DisplayEntry::Normal(log) = entry {
-            let count = source_map.entry(log.source_file.clone()).or_insert((0, 0));
+            let count = source_map.entry(log.source_file.to_string()).or_insert((0, 0));
             count.0 += 1;
             if log.level.to_lowercase().contains("error") {
                 count.1 += 1;
@@ -0,0 +1,299 @@
+//! Filters as a stack of layers
+//!
+//! Every filter action (thread, trace, hiding a level, advanced search, focus) adds a
+//! layer, shown as a breadcrumb in the header. The thread/trace/level/criteria fields
+//! of `App` are derived by applying the layers in order, so any single layer can be
+//! removed, and each change to the stack can be undone and redone.
+
+use crate::models::LevelVisibility;
+use crate::search::{SearchCriteria, SearchQuery};
+
+/// Changes to the stack kept for undo
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum FilterLayer {
+    Thread(String),
+    Trace(String),
+    /// Hide a level: 1 Info, 2 Warn, 3 Error, 4 Debug (the number keys)
+    HideLevel(u8),
+    Search(SearchCriteria),
+    /// Focus mode on the matches of a quick search: the query as typed, for the
+    /// breadcrumb, and as compiled, so undo/redo shows the rows it was created with
+    Focus {
+        query: String,
+        criteria: SearchCriteria,
+    },
+}
+
+impl FilterLayer {
+    /// Short text for the breadcrumb
+    pub fn label(&self) -> String {
+        match self {
+            Self::Thread(tid) => format!("线程 {}", tid),
+            Self::Trace(id) => format!("链路 {}", shorten(id, 12)),
+            Self::HideLevel(level) => format!("隐藏 {}", level_name(*level)),
+            Self::Search(criteria) => format!("高级搜索 {}", criteria_label(criteria)),
+            Self::Focus { query, .. } => format!("专注 {}", shorten(query, 20)),
+        }
+    }
+
+    /// Whether `self` takes the place of `other` when pushed: there is one layer of
+    /// each kind, and one per hidden level
+    fn replaces(&self, other: &FilterLayer) -> bool {
+        match (self, other) {
+            (Self::HideLevel(a), Self::HideLevel(b)) => a == b,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+fn level_name(level: u8) -> &'static str {
+    match level {
+        1 => "Info",
+        2 => "Warn",
+        3 => "Error",
+        _ => "Debug",
+    }
+}
+
+fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() > max {
+        format!("{}…", text.chars().take(max).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn criteria_label(criteria: &SearchCriteria) -> String {
+    let mut parts = Vec::new();
+    if let Some(re) = &criteria.content_regex {
+        parts.push(shorten(re, 16));
+    }
+    if let Some(src) = &criteria.source_file {
+        parts.push(format!("src:{}", shorten(src, 12)));
+    }
+    if !criteria.levels.is_empty() {
+        let levels: Vec<String> = criteria.levels.iter().map(|l| format!("{:?}", l)).collect();
+        parts.push(levels.join("/"));
+    }
+    if criteria.start_time.is_some() || criteria.end_time.is_some() {
+        parts.push("时间".to_string());
+    }
+    if let Some(query) = &criteria.query {
+        let mut kinds = Vec::new();
+        query_kinds(query, &mut kinds);
+        parts.push(kinds.join("+"));
+    }
+    parts.join(" ")
+}
+
+/// The kinds of condition in `query`, each once
+fn query_kinds(query: &SearchQuery, kinds: &mut Vec<&'static str>) {
+    let kind = match query {
+        SearchQuery::Text(_) => "文本",
+        SearchQuery::Level(_) | SearchQuery::LevelName(_) => "级别",
+        SearchQuery::Source(_) => "来源",
+        SearchQuery::Tid(_) => "线程",
+        SearchQuery::Pid(_) => "进程",
+        SearchQuery::Line(..) => "行号",
+        SearchQuery::Delta(..) => "间隔",
+        SearchQuery::Time(..) => "时间",
+        SearchQuery::Json(_) => "JSON",
+        SearchQuery::Not(inner) => return query_kinds(inner, kinds),
+        SearchQuery::And(terms) | SearchQuery::Or(terms) => {
+            terms.iter().for_each(|t| query_kinds(t, kinds));
+            return;
+        }
+    };
+    if !kinds.contains(&kind) {
+        kinds.push(kind);
+    }
+}
+
+/// The filters that result from applying every layer
+#[derive(Default)]
+pub struct FilterState {
+    pub tid: Option<String>,
+    pub trace: Option<String>,
+    pub levels: LevelVisibility,
+    pub criteria: SearchCriteria,
+    /// Label and criteria of the focus layer
+    pub focus: Option<(String, SearchCriteria)>,
+}
+
+#[derive(Default)]
+pub struct FilterStack {
+    /// Layers bottom to top, each with an id that stays the same across undo/redo
+    layers: Vec<(u64, FilterLayer)>,
+    undo: Vec<Vec<(u64, FilterLayer)>>,
+    redo: Vec<Vec<(u64, FilterLayer)>>,
+    next_id: u64,
+}
+
+impl FilterStack {
+    pub fn layers(&self) -> impl Iterator<Item = &FilterLayer> {
+        self.layers.iter().map(|(_, layer)| layer)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Position of the first layer for which `f` holds
+    pub fn position(&self, f: impl Fn(&FilterLayer) -> bool) -> Option<usize> {
+        self.layers.iter().position(|(_, layer)| f(layer))
+    }
+
+    /// Add `layer` on top, replacing a layer of the same kind
+    pub fn push(&mut self, layer: FilterLayer) {
+        self.save();
+        self.layers.retain(|(_, l)| !layer.replaces(l));
+        self.next_id += 1;
+        self.layers.push((self.next_id, layer));
+    }
+
+    /// Take out the layer at `pos` (bottom is 0), keeping the ones above it
+    pub fn remove(&mut self, pos: usize) -> Option<FilterLayer> {
+        if pos >= self.layers.len() {
+            return None;
+        }
+        self.save();
+        Some(self.layers.remove(pos).1)
+    }
+
+    pub fn clear(&mut self) {
+        if !self.layers.is_empty() {
+            self.save();
+            self.layers.clear();
+        }
+    }
+
+    /// Go back to the layers before the last change; false if there is none
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(layers) => {
+                self.redo.push(std::mem::replace(&mut self.layers, layers));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(layers) => {
+                self.undo.push(std::mem::replace(&mut self.layers, layers));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn save(&mut self) {
+        if self.undo.len() == MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.undo.push(self.layers.clone());
+        self.redo.clear();
+    }
+
+    /// Ids of the layers that filter the log list, i.e. all but focus; the list only
+    /// needs refiltering when these change
+    pub fn filter_ids(&self) -> Vec<u64> {
+        self.layers
+            .iter()
+            .filter(|(_, layer)| !matches!(layer, FilterLayer::Focus { .. }))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn state(&self) -> FilterState {
+        let mut state = FilterState::default();
+        for layer in self.layers() {
+            match layer {
+                FilterLayer::Thread(tid) => state.tid = Some(tid.clone()),
+                FilterLayer::Trace(id) => state.trace = Some(id.clone()),
+                FilterLayer::HideLevel(level) => match level {
+                    1 => state.levels.info = false,
+                    2 => state.levels.warn = false,
+                    3 => state.levels.error = false,
+                    _ => state.levels.debug = false,
+                },
+                FilterLayer::Search(criteria) => state.criteria = criteria.clone(),
+                FilterLayer::Focus { query, criteria } => {
+                    state.focus = Some((query.clone(), criteria.clone()))
+                }
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TimeDisplay;
+
+    fn focus(query: &str) -> FilterLayer {
+        FilterLayer::Focus {
+            query: query.to_string(),
+            criteria: SearchCriteria {
+                query: SearchQuery::parse(query, TimeDisplay::Local).unwrap(),
+                ..SearchCriteria::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_layers_replace_and_remove() {
+        let mut stack = FilterStack::default();
+        stack.push(FilterLayer::Thread("1a".into()));
+        stack.push(FilterLayer::HideLevel(4));
+        stack.push(FilterLayer::HideLevel(1));
+        stack.push(FilterLayer::Thread("2b".into()));
+        let labels: Vec<String> = stack.layers().map(FilterLayer::label).collect();
+        assert_eq!(labels, vec!["隐藏 Debug", "隐藏 Info", "线程 2b"]);
+
+        // Removing a layer from the middle keeps the ones above it
+        assert!(matches!(stack.remove(0), Some(FilterLayer::HideLevel(4))));
+        let state = stack.state();
+        assert!(state.levels.debug && !state.levels.info);
+        assert_eq!(state.tid.as_deref(), Some("2b"));
+        assert!(stack.remove(5).is_none());
+
+        // Searches are labelled by what their query checks
+        let criteria = SearchCriteria {
+            query: SearchQuery::parse("level:error timeout OR tid:1a", TimeDisplay::Local).unwrap(),
+            ..SearchCriteria::default()
+        };
+        assert_eq!(FilterLayer::Search(criteria).label(), "高级搜索 级别+文本+线程");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut stack = FilterStack::default();
+        stack.push(FilterLayer::Trace("abc".into()));
+        let ids = stack.filter_ids();
+        stack.push(focus("timeout"));
+        // Focus doesn't change what the log list shows
+        assert_eq!(stack.filter_ids(), ids);
+        stack.clear();
+        assert!(stack.state().trace.is_none());
+
+        assert!(stack.undo());
+        let (label, criteria) = stack.state().focus.unwrap();
+        assert_eq!(label, "timeout");
+        assert!(criteria.query.is_some());
+        assert!(stack.undo());
+        assert!(stack.undo());
+        assert!(stack.is_empty());
+        assert!(!stack.undo());
+
+        assert!(stack.redo());
+        assert_eq!(stack.filter_ids(), ids);
+        // A new change drops what could be redone
+        stack.push(FilterLayer::HideLevel(2));
+        assert!(!stack.redo());
+    }
+}
@@ -5,19 +5,22 @@
 //! - Log level filtering
 //! - Source file filtering
 //! - Content regex filtering
+//! - Quick-search queries (`level:error AND NOT "timeout"`)
 //!
 //! All active conditions must match for an entry to pass the filter.
 
+use std::cell::RefCell;
+
 use crate::models::{DisplayEntry, LogEntry};
-use crate::search::SearchCriteria;
-use crate::time_parser::parse_log_timestamp;
+use crate::search::{SearchCriteria, SearchQuery};
+use glob::MatchOptions;
 use regex::Regex;
 
 /// Filter log entries based on search criteria
 ///
 /// All active conditions must match (AND logic).
 /// Returns references to matching entries.
-/// Note: Use `filter_logs_owned` when storing the result; this is kept for potential future use.
+/// Note: the log list applies criteria through `App::apply_filter`; this is kept for potential future use.
 #[allow(dead_code)]
 pub fn filter_logs<'a>(
     entries: &'a [DisplayEntry],
@@ -37,35 +40,29 @@ pub fn filter_logs<'a>(
         .collect()
 }
 
-/// Filter and clone matching entries
-///
-/// Same as `filter_logs` but returns owned entries.
-/// Use this when you need to store the filtered result.
-pub fn filter_logs_owned(
-    entries: &[DisplayEntry],
-    criteria: &SearchCriteria,
-) -> Vec<DisplayEntry> {
-    if criteria.is_empty() {
-        return entries.to_vec();
-    }
-
-    let content_re = criteria.compile_content_regex();
-
-    entries
-        .iter()
-        .filter(|entry| matches_criteria(entry, criteria, &content_re))
-        .cloned()
-        .collect()
+thread_local! {
+    /// Text built while checking one entry, reused so that verifying many candidates
+    /// doesn't allocate per row
+    static SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
 }
 
 /// Check if a single entry matches all active criteria
-fn matches_criteria(
+pub fn matches_criteria(
     entry: &DisplayEntry,
     criteria: &SearchCriteria,
     content_re: &Option<Regex>,
 ) -> bool {
-    match entry {
-        DisplayEntry::Normal(log) => matches_log_entry(log, criteria, content_re),
+    SCRATCH.with_borrow_mut(|scratch| matches_entry(entry, criteria, content_re, scratch))
+}
+
+fn matches_entry(
+    entry: &DisplayEntry,
+    criteria: &SearchCriteria,
+    content_re: &Option<Regex>,
+    scratch: &mut String,
+) -> bool {
+    let passes = match entry {
+        DisplayEntry::Normal(log) => matches_log_entry(log, criteria, content_re, scratch),
         DisplayEntry::Folded { summary_text, .. } => {
             // For folded entries, only check content regex if present
             // Time and level filters don't apply to folded entries
@@ -76,6 +73,72 @@ fn matches_criteria(
             }
             true
         }
+    };
+    passes
+        && criteria
+            .query
+            .as_ref()
+            .is_none_or(|query| matches_query(entry, query, &mut EntryText::new(scratch)))
+}
+
+/// An entry's searchable text, written into a scratch buffer the first time a text
+/// term needs it
+struct EntryText<'a> {
+    buf: &'a mut String,
+    ready: bool,
+}
+
+impl<'a> EntryText<'a> {
+    fn new(buf: &'a mut String) -> Self {
+        Self { buf, ready: false }
+    }
+
+    fn get(&mut self, entry: &DisplayEntry) -> &str {
+        if !self.ready {
+            self.buf.clear();
+            entry.write_searchable_text(self.buf);
+            self.ready = true;
+        }
+        self.buf
+    }
+}
+
+/// Evaluate a quick-search query
+///
+/// `text` caches the entry's searchable text across the query's text terms. Field
+/// terms never match folded entries; text terms match their summary.
+fn matches_query(entry: &DisplayEntry, query: &SearchQuery, text: &mut EntryText) -> bool {
+    let log = match (query, entry) {
+        (SearchQuery::Text(re), _) => return re.is_match(text.get(entry)),
+        (SearchQuery::Not(inner), _) => return !matches_query(entry, inner, text),
+        (SearchQuery::And(terms), _) => {
+            return terms.iter().all(|t| matches_query(entry, t, text))
+        }
+        (SearchQuery::Or(terms), _) => {
+            return terms.iter().any(|t| matches_query(entry, t, text))
+        }
+        (_, DisplayEntry::Normal(log)) => log,
+        (_, DisplayEntry::Folded { .. }) => return false,
+    };
+    match query {
+        SearchQuery::Level(level) => level.matches(&log.level),
+        SearchQuery::LevelName(name) => log.level.to_lowercase().contains(name.as_str()),
+        SearchQuery::Source(pattern) => pattern.matches_with(
+            &log.source_file,
+            MatchOptions {
+                case_sensitive: false,
+                ..MatchOptions::new()
+            },
+        ),
+        SearchQuery::Tid(tid) => log.tid.eq_ignore_ascii_case(tid),
+        SearchQuery::Pid(pid) => log.pid.eq_ignore_ascii_case(pid),
+        SearchQuery::Line(op, bound) => op.holds(log.line_index, *bound),
+        SearchQuery::Delta(op, bound) => log.delta_ms.is_some_and(|d| op.holds(d, *bound)),
+        SearchQuery::Time(op, span) => log.time.is_some_and(|t| op.holds_in(t, span)),
+        SearchQuery::Json(filter) => filter.matches(log.json_payload.as_deref()),
+        SearchQuery::Text(_) | SearchQuery::Not(_) | SearchQuery::And(_) | SearchQuery::Or(_) => {
+            unreachable!("handled above")
+        }
     }
 }
 
@@ -87,11 +150,12 @@ fn matches_log_entry(
     log: &LogEntry,
     criteria: &SearchCriteria,
     content_re: &Option<Regex>,
+    scratch: &mut String,
 ) -> bool {
     // 1. Time range check - start time (inclusive)
     if let Some(ref start) = criteria.start_time {
-        if let Some(ts) = parse_log_timestamp(&log.timestamp) {
-            if ts < *start {
+        if let Some(ts) = log.time {
+            if ts < start.naive_local() {
                 return false;
             }
         }
@@ -100,8 +164,8 @@ fn matches_log_entry(
 
     // 2. Time range check - end time (inclusive)
     if let Some(ref end) = criteria.end_time {
-        if let Some(ts) = parse_log_timestamp(&log.timestamp) {
-            if ts > *end {
+        if let Some(ts) = log.time {
+            if ts > end.naive_local() {
                 return false;
             }
         }
@@ -126,9 +190,19 @@ fn matches_log_entry(
         }
     }
 
-    // 5. Content regex check
+    // 5. Content regex check (structured payload pairs count as content, on a line
+    // of their own after it)
     if let Some(ref re) = content_re {
-        if !re.is_match(&log.content) {
+        let text = if log.json_payload.is_some() {
+            scratch.clear();
+            scratch.push_str(&log.content);
+            scratch.push('\n');
+            log.write_payload_text(scratch);
+            scratch.as_str()
+        } else {
+            &log.content
+        };
+        if !re.is_match(text) {
             return false;
         }
     }
@@ -156,22 +230,25 @@ pub fn count_matching(entries: &[DisplayEntry], criteria: &SearchCriteria) -> us
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::EntryKind;
     use crate::search::LogLevel;
     use serde_json::Value;
 
     fn make_test_log(timestamp: &str, level: &str, content: &str, source: &str) -> DisplayEntry {
         DisplayEntry::Normal(LogEntry {
-            timestamp: timestamp.to_string(),
-            pid: "1234".to_string(),
-            tid: "5678".to_string(),
-            level: level.to_string(),
-            content: content.to_string(),
-            source_file: source.to_string(),
+            timestamp: timestamp.into(),
+            time: crate::time_parser::parse_log_time(timestamp, None, crate::time_parser::LogTimezone::Local),
+            pid: "1234".into(),
+            tid: "5678".into(),
+            level: level.into(),
+            content: content.into(),
+            source_file: source.into(),
             line_num: 1,
             json_payload: None,
             delta_ms: None,
             source_id: 0,
             line_index: 0,
+            kind: EntryKind::Record,
         })
     }
 
@@ -300,4 +377,79 @@ mod tests {
         };
         assert_eq!(count_matching(&entries, &criteria), 1);
     }
+
+    #[test]
+    fn test_query_predicate() {
+        let mut slow = make_test_log("2024-01-15 10:00:00.000", "ERROR", "usb reset", "UsbCtrl.cpp");
+        if let DisplayEntry::Normal(log) = &mut slow {
+            log.delta_ms = Some(800);
+            log.line_index = 1200;
+        }
+        let mut entries = vec![
+            slow,
+            make_test_log("2024-01-15 10:00:01.000", "ERROR", "read timeout", "UsbCtrl.cpp"),
+            make_test_log("2024-01-15 10:31:00.000", "INFO", "usb ok", "Main.cpp"),
+            DisplayEntry::Folded {
+                start_index: 0,
+                end_index: 3,
+                count: 4,
+                summary_text: "Folded 4 USB polling".to_string(),
+            },
+        ];
+        if let DisplayEntry::Normal(log) = &mut entries[2] {
+            log.json_payload = Some(Box::new(serde_json::json!({"latency": 350})));
+        }
+        let matching = |input: &str| {
+            let criteria = SearchCriteria {
+                query: SearchQuery::parse(input, crate::models::TimeDisplay::Local).unwrap(),
+                ..Default::default()
+            };
+            filter_logs(&entries, &criteria).len()
+        };
+        assert_eq!(matching(r#"level:error AND src:usb* AND NOT "timeout""#), 1);
+        assert_eq!(matching("line>1000"), 1);
+        assert_eq!(matching("delta>500ms"), 1);
+        assert_eq!(matching("tid:5678 pid:1234"), 3);
+        // A space ends the term, so a date and time need quotes
+        assert_eq!(matching("ts>=2024-01-15 10:30:00"), 0);
+        assert_eq!(matching(r#"ts>="2024-01-15 10:30:00""#), 1);
+        // Equality covers the precision typed
+        assert_eq!(matching("ts:2024-01-15"), 3);
+        assert_eq!(matching(r#"ts:"2024-01-15 10:00""#), 2);
+        assert_eq!(matching(r#"ts:"2024-01-15 10:00:01""#), 1);
+        assert_eq!(matching(r#"NOT ts:"2024-01-15 10:00:01""#), 3);
+        assert_eq!(matching(r#"ts<="2024-01-15 10:00""#), 2);
+        assert_eq!(matching(r#"ts>"2024-01-15 10:00""#), 1);
+        assert_eq!(matching(r#"ts:"2024-01-15 10:00:00.500""#), 0);
+        // Text terms also search folded summaries; field terms skip them
+        assert_eq!(matching(r#""USB" OR level:info"#), 2);
+        assert_eq!(matching("!src:main"), 3);
+
+        assert_eq!(matching("$.latency > 200 AND level:info"), 1);
+        assert_eq!(matching("NOT exists($.latency)"), 3);
+        // Payload pairs are part of the searchable text
+        assert_eq!(matching(r#""latency=350" AND ok"#), 1);
+    }
+
+    #[test]
+    fn test_content_regex_covers_payload() {
+        let mut entry = make_test_log("2024-01-15 10:00:00.000", "INFO", "request done", "a.rs");
+        if let DisplayEntry::Normal(log) = &mut entry {
+            log.json_payload = Some(Box::new(serde_json::json!({"user": "bob", "status": 502})));
+        }
+        let entries = vec![entry];
+        let matching = |pattern: &str| {
+            let criteria = SearchCriteria {
+                content_regex: Some(pattern.to_string()),
+                ..Default::default()
+            };
+            filter_logs(&entries, &criteria).len()
+        };
+        assert_eq!(matching("^request"), 1);
+        assert_eq!(matching("status=5\\d\\d"), 1);
+        assert_eq!(matching("user=bob"), 1);
+        // Content and payload don't run into each other
+        assert_eq!(matching("done.*status"), 0);
+        assert_eq!(matching("user=alice"), 0);
+    }
 }
@@ -1,8 +1,9 @@
 use chrono::Local;
 use serde::{Deserialize, Serialize};
-use std::fs;
 use std::path::PathBuf;
 
+use crate::storage;
+
 const MAX_HISTORY: usize = 1000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -12,7 +13,7 @@ pub enum CommandType {
     AiPrompt,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HistoryEntry {
     pub timestamp: String,
     pub kind: CommandType,
@@ -27,12 +28,11 @@ pub struct HistoryManager {
 
 impl HistoryManager {
     pub fn new() -> Self {
-        let file_path = dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".loginsight")
-            .join("history.json");
+        Self::with_path(storage::path("history.json"))
+    }
 
-        let entries = Self::load_from_file(&file_path).unwrap_or_default();
+    fn with_path(file_path: PathBuf) -> Self {
+        let entries: Vec<HistoryEntry> = storage::read_json(&file_path).unwrap_or_default();
 
         let selected = if entries.is_empty() { 0 } else { entries.len() - 1 };
 
@@ -43,61 +43,48 @@ impl HistoryManager {
         }
     }
 
-    fn load_from_file(path: &PathBuf) -> Option<Vec<HistoryEntry>> {
-        let content = fs::read_to_string(path).ok()?;
-        serde_json::from_str(&content).ok()
-    }
-
     pub fn add(&mut self, kind: CommandType, content: String) {
         if content.trim().is_empty() {
             return;
         }
 
-        // Dedup: skip if same as last entry
-        if let Some(last) = self.entries.last() {
-            if last.kind == kind && last.content == content {
-                return;
-            }
-        }
-
         let entry = HistoryEntry {
             timestamp: Local::now().format("%Y-%m-%d %H:%M").to_string(),
             kind,
             content,
         };
 
-        self.entries.push(entry);
-
-        // Limit size
-        if self.entries.len() > MAX_HISTORY {
-            self.entries.remove(0);
+        // Append to the file as it is now, so entries added by another running
+        // instance are kept (and picked up here)
+        let saved = storage::update_json(&self.file_path, |saved: &mut Vec<HistoryEntry>| {
+            append_entry(saved, entry.clone());
+            Ok(saved.clone())
+        });
+        match saved {
+            Ok(saved) => self.entries = saved,
+            Err(_) => append_entry(&mut self.entries, entry),
         }
-
-        let _ = self.save();
     }
 
     pub fn delete(&mut self, index: usize) {
         if index < self.entries.len() {
-            self.entries.remove(index);
+            let entry = self.entries.remove(index);
+            let _ = storage::update_json(&self.file_path, |saved: &mut Vec<HistoryEntry>| {
+                if let Some(pos) = saved.iter().position(|e| *e == entry) {
+                    saved.remove(pos);
+                }
+                Ok(())
+            });
             if self.selected >= self.entries.len() && !self.entries.is_empty() {
                 self.selected = self.entries.len() - 1;
             }
-            let _ = self.save();
         }
     }
 
     pub fn clear(&mut self) {
         self.entries.clear();
         self.selected = 0;
-        let _ = fs::remove_file(&self.file_path);
-    }
-
-    fn save(&self) -> std::io::Result<()> {
-        if let Some(parent) = self.file_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let json = serde_json::to_string_pretty(&self.entries)?;
-        fs::write(&self.file_path, json)
+        let _ = storage::write_json(&self.file_path, &self.entries);
     }
 
     pub fn next(&mut self) {
@@ -119,8 +106,47 @@ impl HistoryManager {
     }
 }
 
+/// Push `entry` unless it repeats the last one, keeping at most `MAX_HISTORY` entries
+fn append_entry(entries: &mut Vec<HistoryEntry>, entry: HistoryEntry) {
+    if let Some(last) = entries.last() {
+        if last.kind == entry.kind && last.content == entry.content {
+            return;
+        }
+    }
+    entries.push(entry);
+    if entries.len() > MAX_HISTORY {
+        entries.remove(0);
+    }
+}
+
 impl Default for HistoryManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instances_share_history_file() {
+        let dir = std::env::temp_dir().join(format!("log-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("history.json");
+        let mut first = HistoryManager::with_path(path.clone());
+        let mut second = HistoryManager::with_path(path.clone());
+
+        first.add(CommandType::Search, "timeout".to_string());
+        second.add(CommandType::Jump, "42".to_string());
+        // The second instance keeps what the first one wrote
+        assert_eq!(second.len(), 2);
+        // ...and the first one picks up the second's entry, which it now repeats
+        first.add(CommandType::Jump, "42".to_string());
+        assert_eq!(first.len(), 2);
+
+        first.delete(1);
+        assert_eq!(HistoryManager::with_path(path).entries[0].content, "timeout");
+        assert_eq!(first.len(), 1);
+    }
+}
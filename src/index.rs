@@ -0,0 +1,331 @@
+//! Trigram index over entry text, for narrowing searches in large sessions
+//!
+//! Entries are grouped into blocks of [`BLOCK_SIZE`]. For every trigram (three
+//! consecutive bytes) of an entry's searchable text the index records the blocks that
+//! contain it. Any match of a regex starts with one of its literal prefixes and ends
+//! with one of its literal suffixes, so only blocks holding every trigram of such a
+//! literal can match; the rest are skipped without building or matching their text.
+//! Trigrams are hashed into a fixed number of buckets: collisions only add candidate
+//! blocks, and candidates are always verified.
+
+use std::sync::Arc;
+
+use regex_syntax::hir::literal::{ExtractKind, Extractor, Seq};
+
+use crate::entry_list::EntryList;
+use crate::search::{SearchCriteria, SearchQuery};
+
+/// Entries per block, the unit the index records
+pub const BLOCK_SIZE: usize = 64;
+/// Blocks per segment; a snapshot shares every segment the tail doesn't touch
+const SEGMENT_BLOCKS: usize = 1024;
+/// Trigram buckets per segment, roughly one per 8 entries
+const BUCKET_BITS: u32 = 13;
+
+#[derive(Debug, Clone, Default)]
+pub struct TrigramIndex {
+    /// Postings for consecutive runs of [`SEGMENT_BLOCKS`] blocks
+    segments: Vec<Arc<Segment>>,
+    /// Entries indexed so far; later ones are always candidates
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    /// Sorted block numbers within the segment per trigram bucket
+    postings: Vec<Vec<u16>>,
+}
+
+impl Segment {
+    fn new() -> Self {
+        Self {
+            postings: vec![Vec::new(); 1 << BUCKET_BITS],
+        }
+    }
+}
+
+fn bucket(trigram: &[u8]) -> usize {
+    let key = u32::from_le_bytes([trigram[0], trigram[1], trigram[2], 0]);
+    (key.wrapping_mul(0x9E37_79B1) >> (32 - BUCKET_BITS)) as usize
+}
+
+impl TrigramIndex {
+    pub fn build(entries: &EntryList) -> Self {
+        let mut index = Self::default();
+        index.update(entries, 0);
+        index
+    }
+
+    /// Index `entries[from..]`, which were added or replaced since the last update
+    ///
+    /// Blocks recorded for replaced entries stay; they only cost a verification.
+    /// Blocks past the end are dropped when `entries` got shorter (e.g. by folding).
+    /// Only the segments holding changed entries are copied if a snapshot shares them.
+    pub fn update(&mut self, entries: &EntryList, from: usize) {
+        if entries.len() < self.len {
+            let blocks = entries.len().div_ceil(BLOCK_SIZE);
+            self.segments.truncate(blocks.div_ceil(SEGMENT_BLOCKS));
+            let kept = blocks - self.segments.len().saturating_sub(1) * SEGMENT_BLOCKS;
+            if let Some(last) = self.segments.last_mut() {
+                let past_end = |list: &Vec<u16>| list.last().is_some_and(|&b| b as usize >= kept);
+                if last.postings.iter().any(past_end) {
+                    for list in &mut Arc::make_mut(last).postings {
+                        list.truncate(list.partition_point(|&b| (b as usize) < kept));
+                    }
+                }
+            }
+        }
+        let mut text = String::new();
+        for (idx, entry) in (from..).zip(entries.iter_from(from)) {
+            text.clear();
+            entry.write_searchable_text(&mut text);
+            let block = idx / BLOCK_SIZE;
+            let (number, local) = (block / SEGMENT_BLOCKS, (block % SEGMENT_BLOCKS) as u16);
+            while self.segments.len() <= number {
+                self.segments.push(Arc::new(Segment::new()));
+            }
+            let segment = Arc::make_mut(&mut self.segments[number]);
+            for trigram in text.as_bytes().windows(3) {
+                let list = &mut segment.postings[bucket(trigram)];
+                match list.last() {
+                    Some(&last) if last == local => {}
+                    Some(&last) if last > local => {
+                        if let Err(at) = list.binary_search(&local) {
+                            list.insert(at, local);
+                        }
+                    }
+                    _ => list.push(local),
+                }
+            }
+        }
+        self.len = entries.len();
+    }
+
+    fn block_count(&self) -> usize {
+        self.len.div_ceil(BLOCK_SIZE)
+    }
+
+    /// Blocks whose text may contain `literal`; `None` when it is too short to tell
+    fn literal_blocks(&self, literal: &[u8]) -> Option<Vec<u64>> {
+        if literal.len() < 3 {
+            return None;
+        }
+        let words = self.block_count().div_ceil(64);
+        let mut bits = vec![!0u64; words];
+        for (number, segment) in self.segments.iter().enumerate() {
+            let start = (number * SEGMENT_BLOCKS / 64).min(words);
+            let end = (start + SEGMENT_BLOCKS / 64).min(words);
+            let segment_bits = &mut bits[start..end];
+            for trigram in literal.windows(3) {
+                let mut found = [0u64; SEGMENT_BLOCKS / 64];
+                for &block in &segment.postings[bucket(trigram)] {
+                    found[block as usize / 64] |= 1 << (block % 64);
+                }
+                segment_bits.iter_mut().zip(&found).for_each(|(a, b)| *a &= b);
+            }
+        }
+        Some(bits)
+    }
+
+    /// Blocks holding one of the literals of `seq`
+    fn seq_candidates(&self, seq: &Seq) -> Candidates {
+        let Some(literals) = seq.literals() else {
+            return Candidates::All;
+        };
+        // No literals at all: the regex can't match anything
+        let mut bits = vec![0u64; self.block_count().div_ceil(64)];
+        for literal in literals {
+            let Some(found) = self.literal_blocks(literal.as_bytes()) else {
+                return Candidates::All;
+            };
+            bits.iter_mut().zip(&found).for_each(|(a, b)| *a |= b);
+        }
+        Candidates::Blocks {
+            bits,
+            indexed: self.len,
+        }
+    }
+
+    /// Entries whose searchable text may match `pattern`
+    pub fn regex_candidates(&self, pattern: &str) -> Candidates {
+        let Ok(hir) = regex_syntax::parse(pattern) else {
+            return Candidates::All;
+        };
+        let mut extractor = Extractor::new();
+        let prefixes = self.seq_candidates(&extractor.kind(ExtractKind::Prefix).extract(&hir));
+        let suffixes = self.seq_candidates(&extractor.kind(ExtractKind::Suffix).extract(&hir));
+        prefixes.and(suffixes)
+    }
+
+    /// Entries that may match `query`; only text terms narrow the search
+    pub fn query_candidates(&self, query: &SearchQuery) -> Candidates {
+        match query {
+            SearchQuery::Text(re) => self.regex_candidates(re.as_str()),
+            SearchQuery::And(terms) => terms
+                .iter()
+                .fold(Candidates::All, |acc, t| acc.and(self.query_candidates(t))),
+            SearchQuery::Or(terms) => terms
+                .iter()
+                .map(|t| self.query_candidates(t))
+                .reduce(Candidates::or)
+                .unwrap_or(Candidates::All),
+            _ => Candidates::All,
+        }
+    }
+
+    /// Entries that may meet the content regex and query of `criteria`
+    pub fn criteria_candidates(&self, criteria: &SearchCriteria) -> Candidates {
+        let content = criteria
+            .content_regex
+            .as_deref()
+            .map_or(Candidates::All, |p| self.regex_candidates(p));
+        let query = criteria
+            .query
+            .as_ref()
+            .map_or(Candidates::All, |q| self.query_candidates(q));
+        content.and(query)
+    }
+}
+
+/// Entries a search has to verify
+#[derive(Debug, Clone)]
+pub enum Candidates {
+    All,
+    Blocks {
+        /// One bit per block
+        bits: Vec<u64>,
+        /// Entries the index covered; later ones are always candidates
+        indexed: usize,
+    },
+}
+
+impl Candidates {
+    pub fn contains(&self, idx: usize) -> bool {
+        match self {
+            Candidates::All => true,
+            Candidates::Blocks { bits, indexed } => {
+                let block = idx / BLOCK_SIZE;
+                idx >= *indexed || bits[block / 64] & (1 << (block % 64)) != 0
+            }
+        }
+    }
+
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Candidates::All, c) | (c, Candidates::All) => c,
+            (Candidates::Blocks { mut bits, indexed }, Candidates::Blocks { bits: other, .. }) => {
+                bits.iter_mut().zip(&other).for_each(|(a, b)| *a &= b);
+                Candidates::Blocks { bits, indexed }
+            }
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Candidates::All, _) | (_, Candidates::All) => Candidates::All,
+            (Candidates::Blocks { mut bits, indexed }, Candidates::Blocks { bits: other, .. }) => {
+                bits.iter_mut().zip(&other).for_each(|(a, b)| *a |= b);
+                Candidates::Blocks { bits, indexed }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DisplayEntry, TimeDisplay};
+
+    fn entries(n: usize) -> Vec<DisplayEntry> {
+        (0..n)
+            .map(|i| DisplayEntry::Folded {
+                start_index: i,
+                end_index: i,
+                count: 1,
+                summary_text: match i {
+                    70 => "connection timeout after 30s".to_string(),
+                    200 => "Disk FULL on /var".to_string(),
+                    _ => format!("request {} ok", i),
+                },
+            })
+            .collect()
+    }
+
+    fn stored(list: &[DisplayEntry]) -> EntryList {
+        list.to_vec().into()
+    }
+
+    fn blocks(c: &Candidates, len: usize) -> Vec<usize> {
+        (0..len)
+            .step_by(BLOCK_SIZE)
+            .filter(|&i| c.contains(i))
+            .map(|i| i / BLOCK_SIZE)
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates_narrow_to_blocks() {
+        let index = TrigramIndex::build(&stored(&entries(256)));
+        assert_eq!(blocks(&index.regex_candidates("timeout"), 256), vec![1]);
+        assert_eq!(blocks(&index.regex_candidates("time(out|d)"), 256), vec![1]);
+        assert_eq!(blocks(&index.regex_candidates("(?i)disk full"), 256), vec![3]);
+        assert_eq!(blocks(&index.regex_candidates("timeout|FULL"), 256), vec![1, 3]);
+        // Nothing to narrow on: short or unbounded literals
+        assert!(matches!(index.regex_candidates("ok"), Candidates::All));
+        assert!(matches!(index.regex_candidates(r"\d+"), Candidates::All));
+
+        let query = SearchQuery::parse(r#"level:error OR "Disk""#, TimeDisplay::Local)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(index.query_candidates(&query), Candidates::All));
+        let query = SearchQuery::parse(r#"timeout NOT "request""#, TimeDisplay::Local)
+            .unwrap()
+            .unwrap();
+        assert_eq!(blocks(&index.query_candidates(&query), 256), vec![1]);
+    }
+
+    #[test]
+    fn test_tail_updates_index() {
+        let mut list = entries(100);
+        let mut index = TrigramIndex::build(&stored(&list));
+        let timeout = index.regex_candidates("timeout");
+        // Entries the index hasn't seen are always candidates
+        assert!(timeout.contains(150));
+        assert!(!timeout.contains(10));
+
+        list.extend(entries(201).into_iter().skip(100));
+        list[10] = list[200].clone();
+        index.update(&stored(&list), 10);
+        let full = index.regex_candidates("Disk FULL");
+        assert!(full.contains(10) && full.contains(200));
+        assert!(!full.contains(70));
+    }
+
+    #[test]
+    fn test_shrunk_index_drops_blocks() {
+        let mut list = entries(4097);
+        list[4096] = list[70].clone();
+        let mut index = TrigramIndex::build(&stored(&list));
+        list.truncate(4094);
+        index.update(&stored(&list), 4093);
+        let timeout = index.regex_candidates("timeout");
+        assert_eq!(blocks(&timeout, 4094), vec![1]);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_untouched_segments() {
+        let segment = BLOCK_SIZE * SEGMENT_BLOCKS;
+        let mut list = entries(segment + 100);
+        let mut index = TrigramIndex::build(&stored(&list));
+        let snapshot = index.clone();
+
+        list.push(list[200].clone());
+        index.update(&stored(&list), segment + 100);
+        assert!(Arc::ptr_eq(&index.segments[0], &snapshot.segments[0]));
+        assert!(!Arc::ptr_eq(&index.segments[1], &snapshot.segments[1]));
+        let full = index.regex_candidates("Disk FULL");
+        assert_eq!(blocks(&full, list.len()), vec![3, SEGMENT_BLOCKS + 1]);
+        let full = snapshot.regex_candidates("Disk FULL");
+        assert_eq!(blocks(&full, segment + 100), vec![3]);
+    }
+}
@@ -1,71 +1,828 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
-use regex::Regex;
+use chrono::Local;
 
-use crate::models::LogEntry;
-use crate::parser::{decode_line, merge_multiline_bytes, parse_line};
+use crate::compression::is_compressed;
+use crate::encoding::{detect_encoding, TextEncoding};
+use crate::models::{EntryKind, LogEntry};
+use crate::parser::{detect_profile, parse_records, ParserProfile};
 
-pub struct TailState {
-    offsets: HashMap<usize, u64>,
+/// Identity of the file behind a path, so a path replaced by a new file can be told
+/// apart from one that was only appended to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileId {
+    dev: u64,
+    ino: u64,
 }
 
-impl TailState {
-    pub fn new() -> Self {
+#[cfg(unix)]
+fn file_id(file: &File) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    file.metadata().ok().map(|m| FileId {
+        dev: m.dev(),
+        ino: m.ino(),
+    })
+}
+
+/// Without a stable file identity only truncation can be detected
+#[cfg(not(unix))]
+fn file_id(_file: &File) -> Option<FileId> {
+    None
+}
+
+/// Data read but not yet parsed, because its last record may still be growing
+///
+/// A record is released once a later complete line starts a new record, or once no
+/// data has arrived for the quiet period, so flushes in the middle of a line or a
+/// multi-line record never split it into fragments.
+#[derive(Default)]
+struct CarryOver {
+    /// Lines as produced by [`TextEncoding::line_bytes`]
+    data: Vec<u8>,
+    /// Trailing byte of a UTF-16 code unit cut in half by a read
+    partial_unit: Option<u8>,
+    last_input: Option<Instant>,
+}
+
+impl CarryOver {
+    fn push(&mut self, raw: &[u8], encoding: TextEncoding) {
+        if raw.is_empty() {
+            return;
+        }
+        self.last_input = Some(Instant::now());
+        if matches!(encoding, TextEncoding::Utf16Le | TextEncoding::Utf16Be) {
+            let mut raw: Vec<u8> = self
+                .partial_unit
+                .take()
+                .into_iter()
+                .chain(raw.iter().copied())
+                .collect();
+            if raw.len() % 2 == 1 {
+                self.partial_unit = raw.pop();
+            }
+            self.data.extend_from_slice(&encoding.line_bytes(&raw));
+        } else {
+            self.data.extend_from_slice(raw);
+        }
+    }
+
+    /// Whether everything held should be released now
+    fn is_due(&self, quiet: Duration) -> bool {
+        !self.data.is_empty() && self.last_input.is_none_or(|t| t.elapsed() >= quiet)
+    }
+
+    fn take_all(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+
+    /// Take the records that are known to be complete
+    fn take_ready(&mut self, profile: &ParserProfile, quiet: Duration) -> Vec<u8> {
+        if self.is_due(quiet) {
+            return self.take_all();
+        }
+        let split = last_record_start(&self.data, profile);
+        self.data.drain(..split).collect()
+    }
+}
+
+/// Offset of the last complete line of `data` that starts a record, or 0
+///
+/// An unterminated last line is never taken as a record start: it is still being written.
+fn last_record_start(data: &[u8], profile: &ParserProfile) -> usize {
+    let complete = data.iter().rposition(|&b| b == b'\n').map_or(0, |n| n + 1);
+    let mut end = complete;
+    while end > 0 {
+        let start = data[..end - 1].iter().rposition(|&b| b == b'\n').map_or(0, |n| n + 1);
+        let line = &data[start..end - 1];
+        if profile.is_record_start(line.strip_suffix(b"\r").unwrap_or(line)) {
+            return start;
+        }
+        end = start;
+    }
+    0
+}
+
+/// Read position in one tailed file
+#[derive(Default)]
+struct TailedFile {
+    offset: u64,
+    /// The file being read; after a rename it still refers to the rotated file
+    handle: Option<File>,
+    id: Option<FileId>,
+    pending: CarryOver,
+}
+
+impl TailedFile {
+    /// Read everything past `offset`, starting over if the file shrank
+    fn read_to_end(&mut self) -> (bool, Vec<u8>) {
+        let Some(file) = self.handle.as_mut() else {
+            return (false, vec![]);
+        };
+        let Ok(size) = file.metadata().map(|m| m.len()) else {
+            return (false, vec![]);
+        };
+        let truncated = size < self.offset;
+        if truncated {
+            self.offset = 0;
+        }
+        let mut buffer = Vec::new();
+        if size > self.offset
+            && file.seek(SeekFrom::Start(self.offset)).is_ok()
+            && file.read_to_end(&mut buffer).is_ok()
+        {
+            self.offset += buffer.len() as u64;
+        }
+        (truncated, buffer)
+    }
+}
+
+/// A file followed by live tail
+struct LiveFile {
+    source_id: usize,
+    path: PathBuf,
+    /// `path` with its directory canonicalized, for matching watcher events
+    key: PathBuf,
+    profile: ParserProfile,
+}
+
+/// Picks the parser profile and encoding for a file found while tailing, or `None`
+/// while it can't be set up yet (e.g. still empty)
+pub type FileSetup = Box<dyn Fn(&Path) -> Option<(ParserProfile, TextEncoding)>>;
+
+/// The tailed files plus the input globs, re-evaluated to pick up files created
+/// after startup (such as a new daily log)
+pub struct LiveFiles {
+    tail: TailState,
+    files: Vec<LiveFile>,
+    patterns: Vec<String>,
+    setup: FileSetup,
+}
+
+/// A file picked up by [`LiveFiles::discover`]
+pub struct DiscoveredFile {
+    pub source_id: usize,
+    pub path: PathBuf,
+    pub profile: ParserProfile,
+    pub encoding: TextEncoding,
+}
+
+impl LiveFiles {
+    pub fn new(patterns: Vec<String>, setup: FileSetup, quiet: Duration) -> Self {
         Self {
-            offsets: HashMap::new(),
+            tail: TailState::new(quiet),
+            files: Vec::new(),
+            patterns,
+            setup,
         }
     }
 
-    pub fn init_offset(&mut self, source_id: usize, offset: u64) {
-        self.offsets.insert(source_id, offset);
+    /// Follow a file loaded at startup from its current end
+    pub fn add_loaded(&mut self, source_id: usize, path: PathBuf, profile: ParserProfile) {
+        self.tail.init_file(source_id, &path);
+        self.files.push(LiveFile {
+            source_id,
+            key: path_key(&path),
+            path,
+            profile,
+        });
     }
 
+    /// Directories to watch: those holding the tailed files and the fixed leading
+    /// directory of every pattern, so created files are seen too
+    pub fn watch_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .files
+            .iter()
+            .map(|f| parent_dir(&f.path).to_path_buf())
+            .chain(self.patterns.iter().map(|p| glob_base_dir(p)))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|f| f.path.as_path())
+    }
+
+    /// Tailed files whose held-back record has been quiet long enough to release
+    pub fn due_paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|f| self.tail.is_due(f.source_id))
+            .map(|f| f.path.clone())
+            .collect()
+    }
+
+    /// Source id of the tailed file at `path`, if any
+    pub fn source_id(&self, path: &Path) -> Option<usize> {
+        let key = path_key(path);
+        self.files.iter().find(|f| f.key == key).map(|f| f.source_id)
+    }
+
+    /// Parse what was appended to a tailed file since the last call
     pub fn read_new_lines(
         &mut self,
-        path: &PathBuf,
         source_id: usize,
-        re: &Regex,
+        encoding: TextEncoding,
         base_line_index: usize,
     ) -> Vec<LogEntry> {
-        let Ok(mut file) = File::open(path) else {
+        let Some(file) = self.files.iter().find(|f| f.source_id == source_id) else {
             return vec![];
         };
-        let Ok(metadata) = file.metadata() else {
+        self.tail
+            .read_new_lines(&file.path, source_id, &file.profile, encoding, base_line_index)
+    }
+
+    /// Re-run the input globs and start tailing new matches from their beginning
+    ///
+    /// New files get consecutive source ids from `next_source_id`. Files that can't be
+    /// set up yet are left for a later call. A match that is a file already tailed,
+    /// such as one renamed by rotation, is skipped so its records aren't read twice.
+    pub fn discover(&mut self, next_source_id: usize) -> Vec<DiscoveredFile> {
+        let mut found = Vec::new();
+        for pattern in &self.patterns {
+            let Ok(paths) = glob::glob(pattern) else {
+                continue;
+            };
+            for path in paths.flatten() {
+                let key = path_key(&path);
+                if !path.is_file()
+                    || self.files.iter().any(|f| f.key == key)
+                    || is_compressed(&path)
+                    || self.tail.has_seen(&path)
+                {
+                    continue;
+                }
+                let Some((profile, encoding)) = (self.setup)(&path) else {
+                    continue;
+                };
+                let source_id = next_source_id + found.len();
+                found.push(DiscoveredFile {
+                    source_id,
+                    path: path.clone(),
+                    profile: profile.clone(),
+                    encoding,
+                });
+                self.files.push(LiveFile {
+                    source_id,
+                    path,
+                    key,
+                    profile,
+                });
+            }
+        }
+        found
+    }
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+fn path_key(path: &Path) -> PathBuf {
+    let dir = parent_dir(path);
+    dir.canonicalize()
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .join(path.file_name().unwrap_or_default())
+}
+
+/// The deepest directory of `pattern` without glob metacharacters
+fn glob_base_dir(pattern: &str) -> PathBuf {
+    let mut dir = parent_dir(Path::new(pattern));
+    while dir.to_string_lossy().contains(['*', '?', '[']) {
+        dir = parent_dir(dir);
+    }
+    dir.to_path_buf()
+}
+
+pub struct TailState {
+    files: HashMap<usize, TailedFile>,
+    /// Every file identity read so far, including files since rotated away, so a
+    /// rotated file showing up under a new name isn't taken for a new file
+    seen: HashSet<FileId>,
+    /// How long a possibly unfinished record is held back without new data
+    quiet: Duration,
+}
+
+impl TailState {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            files: HashMap::new(),
+            seen: HashSet::new(),
+            quiet,
+        }
+    }
+
+    fn is_due(&self, source_id: usize) -> bool {
+        self.files.get(&source_id).is_some_and(|f| f.pending.is_due(self.quiet))
+    }
+
+    /// Start following `path` from its current end
+    pub fn init_file(&mut self, source_id: usize, path: &Path) {
+        let handle = File::open(path).ok();
+        let id = handle.as_ref().and_then(file_id);
+        self.seen.extend(id);
+        let tailed = TailedFile {
+            offset: handle
+                .as_ref()
+                .and_then(|f| f.metadata().ok())
+                .map_or(0, |m| m.len()),
+            id,
+            handle,
+            pending: CarryOver::default(),
+        };
+        self.files.insert(source_id, tailed);
+    }
+
+    /// Whether `path` names a file that is or was tailed (e.g. `app.log.1` after
+    /// `app.log` was rotated), whose records are already in
+    fn has_seen(&self, path: &Path) -> bool {
+        File::open(path)
+            .ok()
+            .as_ref()
+            .and_then(file_id)
+            .is_some_and(|id| self.seen.contains(&id))
+    }
+
+    /// Parse what was appended to `path` since the last call
+    ///
+    /// When the path now names a different file (logrotate `create`, or a rename and
+    /// recreate by the writer), the rotated file is read until the new one receives
+    /// data, then drained, and a rotation marker separates its records from the new
+    /// file's. Truncation in place restarts
+    /// from the beginning, also behind a marker. The last record read is held back
+    /// until it is known to be complete (see [`CarryOver`]); a rotation or truncation
+    /// releases it first.
+    pub fn read_new_lines(
+        &mut self,
+        path: &Path,
+        source_id: usize,
+        profile: &ParserProfile,
+        encoding: TextEncoding,
+        base_line_index: usize,
+    ) -> Vec<LogEntry> {
+        // Archives are read once at load time; rewriting one is not a tail event
+        if is_compressed(path) {
             return vec![];
+        }
+        let current = File::open(path).ok();
+        let current_id = current.as_ref().and_then(file_id);
+        // logrotate `create` makes the new file before the writer reopens; until the
+        // writer switches, its lines keep going to the rotated file
+        let current_written = current
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .is_some_and(|m| m.len() > 0);
+        let quiet = self.quiet;
+        self.seen.extend(current_id);
+        let tailed = self.files.entry(source_id).or_default();
+        let mut entries = Vec::new();
+        let append = |entries: &mut Vec<LogEntry>, data: &[u8]| {
+            let base = base_line_index + entries.len();
+            entries.extend(parse_chunk(data, source_id, profile, encoding, base));
         };
-        let file_size = metadata.len();
-        let offset = self.offsets.get(&source_id).copied().unwrap_or(0);
 
-        if file_size < offset {
-            self.offsets.insert(source_id, 0);
-            return self.read_new_lines(path, source_id, re, base_line_index);
+        match (tailed.id, current_id) {
+            (Some(old), Some(new)) if old != new && current_written => {
+                // Whatever the writer added before switching files still belongs to the old one
+                let (_, rest) = tailed.read_to_end();
+                tailed.pending.push(&rest, encoding);
+                append(&mut entries, &tailed.pending.take_all());
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                entries.push(rotation_marker(
+                    source_id,
+                    base_line_index + entries.len(),
+                    format!("文件已轮转: {}", name),
+                ));
+                *tailed = TailedFile {
+                    offset: 0,
+                    handle: current,
+                    id: current_id,
+                    pending: CarryOver::default(),
+                };
+            }
+            // No identity to compare (or nothing open yet): follow whatever the path names
+            (None, _) if current.is_some() => {
+                tailed.handle = current;
+                tailed.id = current_id;
+            }
+            _ => {}
         }
 
-        if file_size == offset {
-            return vec![];
+        let (truncated, buffer) = tailed.read_to_end();
+        if truncated {
+            append(&mut entries, &tailed.pending.take_all());
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            entries.push(rotation_marker(
+                source_id,
+                base_line_index + entries.len(),
+                format!("文件被截断，从头读取: {}", name),
+            ));
         }
+        tailed.pending.push(&buffer, encoding);
+        append(&mut entries, &tailed.pending.take_ready(profile, quiet));
+        entries
+    }
+}
 
-        if file.seek(SeekFrom::Start(offset)).is_err() {
-            return vec![];
+/// Entry marking where a tailed file was rotated or truncated
+fn rotation_marker(source_id: usize, line_index: usize, message: String) -> LogEntry {
+    let now = Local::now().naive_local();
+    LogEntry {
+        timestamp: now.format("%Y-%m-%d %H:%M:%S%.3f").to_string().into(),
+        time: Some(now),
+        pid: "".into(),
+        tid: "".into(),
+        level: "".into(),
+        content: message.into(),
+        source_file: "".into(),
+        line_num: 0,
+        json_payload: None,
+        delta_ms: None,
+        source_id,
+        line_index,
+        kind: EntryKind::Rotation,
+    }
+}
+
+/// Parse released lines (already in [`TextEncoding::line_bytes`] form)
+fn parse_chunk(
+    data: &[u8],
+    source_id: usize,
+    profile: &ParserProfile,
+    encoding: TextEncoding,
+    base_line_index: usize,
+) -> Vec<LogEntry> {
+    if data.is_empty() {
+        return vec![];
+    }
+    parse_records(data, profile, encoding, None, source_id, base_line_index, &[]).1
+}
+
+/// Lines piped into stdin, read by a background thread and parsed as they arrive
+///
+/// The profile and encoding are detected from the first batch unless forced.
+pub struct StdinSource {
+    pub source_id: usize,
+    rx: Receiver<Vec<u8>>,
+    profiles: Vec<ParserProfile>,
+    sample_lines: usize,
+    profile: Option<ParserProfile>,
+    /// Set with `--profile`; otherwise the profile is guessed and may be guessed again
+    profile_forced: bool,
+    encoding: Option<TextEncoding>,
+    pending: CarryOver,
+    quiet: Duration,
+}
+
+impl StdinSource {
+    pub fn spawn(
+        source_id: usize,
+        profiles: Vec<ParserProfile>,
+        profile: Option<ParserProfile>,
+        encoding: Option<TextEncoding>,
+        sample_lines: usize,
+        quiet: Duration,
+    ) -> Self {
+        Self::from_reader(std::io::stdin(), source_id, profiles, profile, encoding, sample_lines, quiet)
+    }
+
+    /// Read `reader` on a background thread. Raw chunks are passed on as read and
+    /// only split into lines once decoded, as a 0x0A byte may be half of a UTF-16 unit.
+    fn from_reader(
+        mut reader: impl Read + Send + 'static,
+        source_id: usize,
+        profiles: Vec<ParserProfile>,
+        profile: Option<ParserProfile>,
+        encoding: Option<TextEncoding>,
+        sample_lines: usize,
+        quiet: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = vec![0u8; 64 * 1024];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        Self {
+            source_id,
+            rx,
+            profiles,
+            sample_lines,
+            profile_forced: profile.is_some(),
+            profile,
+            encoding,
+            pending: CarryOver::default(),
+            quiet,
         }
+    }
+
+    /// The profile in use, once known
+    pub fn profile(&self) -> Option<&ParserProfile> {
+        self.profile.as_ref()
+    }
 
+    pub fn encoding(&self) -> Option<TextEncoding> {
+        self.encoding
+    }
+
+    /// Parse the records completed since the last call
+    pub fn read_new_records(&mut self, base_line_index: usize) -> Vec<LogEntry> {
         let mut buffer = Vec::new();
-        if file.read_to_end(&mut buffer).is_err() {
+        while let Ok(chunk) = self.rx.try_recv() {
+            buffer.extend_from_slice(&chunk);
+        }
+        if buffer.is_empty() && !self.pending.is_due(self.quiet) {
             return vec![];
         }
 
-        self.offsets.insert(source_id, file_size);
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => {
+                let guess = detect_encoding(&buffer);
+                let profile = self.profile.get_or_insert_with(|| {
+                    let data = guess.line_bytes(&buffer);
+                    self.profiles[detect_profile(&data, guess, &self.profiles, self.sample_lines)]
+                        .clone()
+                });
+                // Same precedence as files: forced encoding, then the profile's, then detection
+                *self.encoding.insert(profile.encoding.unwrap_or(guess))
+            }
+        };
+        self.pending.push(&buffer, encoding);
+        if self.profile.is_none() {
+            let index = detect_profile(&self.pending.data, encoding, &self.profiles, self.sample_lines);
+            self.profile = Some(self.profiles[index].clone());
+        }
+        let profile = self.profile.as_ref().unwrap();
+        let data = self.pending.take_ready(profile, self.quiet);
+        let entries = parse_chunk(&data, self.source_id, profile, encoding, base_line_index);
+        // A guess that parsed nothing is dropped, so the next lines get another try
+        if !self.profile_forced
+            && !entries.is_empty()
+            && entries.iter().all(|e| e.kind == EntryKind::Unparsed)
+        {
+            self.profile = None;
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ParserConfig;
+    use crate::parser::build_profiles;
+    use std::io::Write;
 
-        let lines = merge_multiline_bytes(&buffer);
-        lines
-            .iter()
-            .enumerate()
-            .filter_map(|(i, b)| {
-                parse_line(&decode_line(b), b, re, source_id, base_line_index + i + 1)
-            })
-            .collect()
+    fn append(path: &Path, text: &str) {
+        let mut f = std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+        f.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rotation_drains_old_file_then_switches() {
+        let dir = std::env::temp_dir().join(format!("log-tail-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let rotated = dir.join("app.log.1");
+        let _ = std::fs::remove_file(&rotated);
+        std::fs::write(&path, "2024-01-15 10:00:00.000[1:2][Info]: old (a.cpp:1)\n").unwrap();
+
+        let profile = &build_profiles(&ParserConfig::default()).unwrap()[0];
+        let mut tail = TailState::new(Duration::ZERO);
+        tail.init_file(0, &path);
+
+        // Written just before the rename, then a fresh file takes the path
+        append(&path, "2024-01-15 10:00:01.000[1:2][Info]: late (a.cpp:2)\n");
+        std::fs::rename(&path, &rotated).unwrap();
+        append(&path, "2024-01-15 10:00:02.000[1:2][Info]: new (a.cpp:3)\n");
+
+        let entries = tail.read_new_lines(&path, 0, profile, TextEncoding::Utf8, 0);
+        let kinds: Vec<_> = entries.iter().map(|e| (e.kind, e.content.trim())).collect();
+        assert_eq!(kinds[0], (EntryKind::Record, "late"));
+        assert_eq!(kinds[1].0, EntryKind::Rotation);
+        assert_eq!(kinds[2], (EntryKind::Record, "new"));
+        assert_eq!(entries.len(), 3);
+
+        // The rotated file is no longer read
+        append(&rotated, "2024-01-15 10:00:03.000[1:2][Info]: stray (a.cpp:4)\n");
+        assert!(tail.read_new_lines(&path, 0, profile, TextEncoding::Utf8, 0).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_truncation_restarts_behind_marker() {
+        let dir = std::env::temp_dir().join(format!("log-tail-truncate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        std::fs::write(&path, "2024-01-15 10:00:00.000[1:2][Info]: before truncation (a.cpp:1)\n").unwrap();
+
+        let profile = &build_profiles(&ParserConfig::default()).unwrap()[0];
+        let mut tail = TailState::new(Duration::ZERO);
+        tail.init_file(0, &path);
+        std::fs::write(&path, "2024-01-15 10:00:05.000[1:2][Info]: x (a.cpp:2)\n").unwrap();
+
+        let entries = tail.read_new_lines(&path, 0, profile, TextEncoding::Utf8, 0);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].kind, EntryKind::Rotation);
+        assert_eq!(entries[1].content.trim(), "x");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_records_held_until_complete() {
+        let dir = std::env::temp_dir().join(format!("log-tail-carry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        std::fs::write(&path, "").unwrap();
+
+        let profile = &build_profiles(&ParserConfig::default()).unwrap()[0];
+        let mut tail = TailState::new(Duration::from_secs(3600));
+        tail.init_file(0, &path);
+        let mut read = |text: &str| {
+            append(&path, text);
+            let entries = tail.read_new_lines(&path, 0, profile, TextEncoding::Utf8, 0);
+            entries.iter().map(|e| e.content.to_string()).collect::<Vec<_>>()
+        };
+
+        // A half-written line, then a finished one that may still get continuation lines
+        assert!(read("2024-01-15 10:00:00.000[1:2][Error]: fail").is_empty());
+        assert!(read("ed (a.cpp:1)\n").is_empty());
+        assert!(read("    at frame one\n2024-01-15 10:00:01.000[1:2][Info]: ").is_empty());
+        assert_eq!(read("next (a.cpp:2)\n"), vec!["failed \n    at frame one"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_quiet_period_releases_everything() {
+        let profile = &build_profiles(&ParserConfig::default()).unwrap()[0];
+        let mut pending = CarryOver::default();
+        pending.push(b"2024-01-15 10:00:00.000[1:2][Info]: a (a.cpp:1)\npartial", TextEncoding::Utf8);
+        assert!(pending.take_ready(profile, Duration::from_secs(3600)).is_empty());
+        assert_eq!(pending.take_ready(profile, Duration::ZERO).len(), 55);
+        assert!(!pending.is_due(Duration::ZERO));
+
+        // A UTF-16 code unit split between reads is joined before decoding
+        pending.push(b"h\0i", TextEncoding::Utf16Le);
+        pending.push(b"\0\n\0", TextEncoding::Utf16Le);
+        assert_eq!(pending.take_all(), b"hi\n");
+    }
+
+    #[test]
+    fn test_discover_picks_up_new_matches() {
+        let dir = std::env::temp_dir().join(format!("log-tail-discover-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("app-1.log");
+        std::fs::write(&first, "2024-01-15 10:00:00.000[1:2][Info]: one (a.cpp:1)\n").unwrap();
+
+        let profile = build_profiles(&ParserConfig::default()).unwrap().remove(0);
+        let setup_profile = profile.clone();
+        let setup: FileSetup = Box::new(move |path| {
+            (std::fs::metadata(path).ok()?.len() > 0)
+                .then(|| (setup_profile.clone(), TextEncoding::Utf8))
+        });
+        let pattern = dir.join("app-*.log").to_string_lossy().into_owned();
+        let mut live = LiveFiles::new(vec![pattern], setup, Duration::ZERO);
+        live.add_loaded(0, first.clone(), profile);
+        assert_eq!(live.watch_dirs(), vec![dir.clone()]);
+        assert!(live.discover(1).is_empty());
+
+        // Empty files wait until they have something to detect from
+        let second = dir.join("app-2.log");
+        std::fs::write(&second, "").unwrap();
+        assert!(live.discover(1).is_empty());
+        append(&second, "2024-01-15 10:00:01.000[1:2][Info]: two (a.cpp:2)\n");
+        let found = live.discover(1);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].source_id, found[0].path.as_path()), (1, second.as_path()));
+        assert_eq!(live.source_id(&second), Some(1));
+
+        // A new file is read from its beginning
+        let entries = live.read_new_lines(1, TextEncoding::Utf8, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].content.trim(), "two");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_skips_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("log-tail-rotated-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("service.log");
+        std::fs::write(&path, "2024-01-15 10:00:00.000[1:2][Info]: one (a.cpp:1)\n").unwrap();
+
+        let profile = build_profiles(&ParserConfig::default()).unwrap().remove(0);
+        let setup_profile = profile.clone();
+        let setup: FileSetup = Box::new(move |_| Some((setup_profile.clone(), TextEncoding::Utf8)));
+        let pattern = dir.join("service.log*").to_string_lossy().into_owned();
+        let mut live = LiveFiles::new(vec![pattern], setup, Duration::ZERO);
+        live.add_loaded(0, path.clone(), profile);
+
+        // Renamed away while still being drained, then again after the switch
+        std::fs::rename(&path, dir.join("service.log.1")).unwrap();
+        append(&path, "2024-01-15 10:00:01.000[1:2][Info]: two (a.cpp:2)\n");
+        assert!(live.discover(1).is_empty());
+        assert_eq!(live.read_new_lines(0, TextEncoding::Utf8, 0).len(), 2);
+        std::fs::rename(dir.join("service.log.1"), dir.join("service.log.2")).unwrap();
+        std::fs::rename(&path, dir.join("service.log.1")).unwrap();
+        append(&path, "2024-01-15 10:00:02.000[1:2][Info]: three (a.cpp:3)\n");
+        assert!(live.discover(1).is_empty());
+
+        // A file that really is new is still picked up
+        append(&dir.join("service.log.new"), "2024-01-15 10:00:03.000[1:2][Info]: x (a.cpp:4)\n");
+        assert_eq!(live.discover(1).len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_glob_base_dir_stops_at_wildcards() {
+        assert_eq!(glob_base_dir("logs/*/app.log"), PathBuf::from("logs"));
+        assert_eq!(glob_base_dir("/var/log/app-*.log"), PathBuf::from("/var/log"));
+        assert_eq!(glob_base_dir("*.log"), PathBuf::from("."));
+    }
+
+    /// Poll `source` until `count` records have come out of it
+    fn collect(source: &mut StdinSource, count: usize) -> Vec<LogEntry> {
+        let mut entries = Vec::new();
+        for _ in 0..400 {
+            entries.extend(source.read_new_records(entries.len()));
+            if entries.len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        entries
+    }
+
+    #[test]
+    fn test_stdin_utf16_lines_split_after_decoding() {
+        // "上" is 0x4E0A, so a UTF-16LE stream carries a 0x0A byte inside the record
+        let text = "2024-01-15 10:00:00.000[1:2][Info]: 上线 (a.cpp:1)\n\
+                    2024-01-15 10:00:01.000[1:2][Info]: 下线 (a.cpp:2)\n";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let reader = std::io::Cursor::new(bytes);
+        let mut source =
+            StdinSource::from_reader(reader, 0, profiles, None, None, 10, Duration::ZERO);
+        let entries = collect(&mut source, 2);
+        let contents: Vec<_> = entries.iter().map(|e| e.content.trim()).collect();
+        assert_eq!(contents, ["上线", "下线"]);
+        assert!(entries.iter().all(|e| e.kind == EntryKind::Record));
+        assert_eq!(source.encoding(), Some(TextEncoding::Utf16Le));
+    }
+
+    /// Hands out one chunk per read, as a pipe does when the writer flushes
+    struct Chunks(Receiver<Vec<u8>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Ok(chunk) = self.0.recv() else {
+                return Ok(0);
+            };
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn test_stdin_profile_detected_again_after_unparsed_batch() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let mut source =
+            StdinSource::from_reader(Chunks(rx), 0, profiles, None, None, 10, Duration::ZERO);
+
+        // A banner nothing recognizes: the first guess parses nothing
+        tx.send(b"starting up...\n".to_vec()).unwrap();
+        let entries = collect(&mut source, 1);
+        assert_eq!(entries[0].kind, EntryKind::Unparsed);
+
+        tx.send(b"{\"level\":\"error\",\"msg\":\"boom\"}\n".to_vec()).unwrap();
+        let entries = collect(&mut source, 1);
+        assert_eq!(source.profile().map(|p| p.name.as_str()), Some(crate::parser::JSON_PROFILE));
+        assert_eq!(entries[0].kind, EntryKind::Record);
     }
 }
@@ -1,60 +1,198 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use chrono::NaiveDateTime;
+
 use crate::config::FiltersConfig;
 use crate::models::{DisplayEntry, LogEntry};
+use crate::source::LazyText;
 
-pub fn fold_noise(logs: Vec<LogEntry>, config: &FiltersConfig) -> Vec<DisplayEntry> {
-    let mut result = Vec::new();
-    let mut i = 0;
-    while i < logs.len() {
-        let mut folded = false;
+/// Merge per-file entry lists, each already in file order, into one timeline
+///
+/// Entries without a parsed time keep the time of the entry before them in the same
+/// file, so they stay next to it. Ties go to the earlier file.
+pub fn merge_by_time(files: Vec<Vec<LogEntry>>) -> Vec<LogEntry> {
+    let total = files.iter().map(Vec::len).sum();
+    let mut merged = Vec::with_capacity(total);
+    let mut sources: Vec<_> = files.into_iter().map(|f| f.into_iter().peekable()).collect();
+    let mut last_time: Vec<Option<NaiveDateTime>> = vec![None; sources.len()];
+    let mut heap = BinaryHeap::new();
+    for (i, source) in sources.iter_mut().enumerate() {
+        if let Some(entry) = source.peek() {
+            last_time[i] = entry.time;
+            heap.push(Reverse((entry.time, i)));
+        }
+    }
+    while let Some(Reverse((_, i))) = heap.pop() {
+        let entry = sources[i].next().unwrap();
+        merged.push(entry);
+        if let Some(next) = sources[i].peek() {
+            if next.time.is_some() {
+                last_time[i] = next.time;
+            }
+            heap.push(Reverse((last_time[i], i)));
+        }
+    }
+    merged
+}
 
-        for rule in &config.fold_rules {
-            let matches = |e: &LogEntry| -> bool {
+/// What the entries of a folded run have in common
+#[derive(Debug, Clone, PartialEq)]
+pub enum FoldKind {
+    /// Matched by `fold_rules[i]`
+    Rule(usize),
+    /// Same content
+    Identical(LazyText),
+}
+
+impl FoldKind {
+    pub fn matches(&self, entry: &LogEntry, config: &FiltersConfig) -> bool {
+        match self {
+            FoldKind::Rule(i) => config.fold_rules.get(*i).is_some_and(|rule| {
                 match rule.match_type.as_str() {
-                    "source_file" => rule.patterns.iter().any(|p| e.source_file.contains(p)),
-                    "content" => rule.patterns.iter().any(|p| e.content.contains(p)),
-                    "level" => rule.patterns.iter().any(|p| e.level.contains(p)),
+                    "source_file" => rule.patterns.iter().any(|p| entry.source_file.contains(p)),
+                    "content" => rule.patterns.iter().any(|p| entry.content.contains(p)),
+                    "level" => rule.patterns.iter().any(|p| entry.level.contains(p)),
                     _ => false,
                 }
-            };
+            }),
+            FoldKind::Identical(content) => entry.content == *content,
+        }
+    }
 
-            let mut j = i;
-            while j < logs.len() && matches(&logs[j]) {
-                j += 1;
-            }
-            if j - i >= config.fold_threshold {
-                result.push(DisplayEntry::Folded {
-                    start_index: i,
-                    end_index: j - 1,
-                    count: j - i,
-                    summary_text: format!("Folded {} {}", j - i, rule.name),
+    /// Smallest run worth folding
+    fn threshold(&self, config: &FiltersConfig) -> usize {
+        match self {
+            FoldKind::Rule(_) => config.fold_threshold,
+            FoldKind::Identical(_) => 5,
+        }
+    }
+
+    pub fn summary(&self, count: usize, config: &FiltersConfig) -> String {
+        match self {
+            FoldKind::Rule(i) => format!("Folded {} {}", count, config.fold_rules[*i].name),
+            FoldKind::Identical(_) => format!("Folded {} identical", count),
+        }
+    }
+}
+
+/// A run `start..end` of entries to fold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub kind: FoldKind,
+}
+
+/// Find the runs of `logs` to fold; rules are tried first, then identical content
+pub fn find_folds(logs: &[LogEntry], config: &FiltersConfig) -> Vec<Fold> {
+    let mut folds = Vec::new();
+    let mut i = 0;
+    while i < logs.len() {
+        let candidates = (0..config.fold_rules.len())
+            .map(FoldKind::Rule)
+            .chain(std::iter::once(FoldKind::Identical(logs[i].content.clone())));
+        let mut folded = false;
+        for kind in candidates {
+            let run = logs[i..]
+                .iter()
+                .take_while(|e| kind.matches(e, config))
+                .count();
+            if run > 0 && run >= kind.threshold(config) {
+                folds.push(Fold {
+                    start: i,
+                    end: i + run,
+                    kind,
                 });
-                i = j;
+                i += run;
                 folded = true;
                 break;
             }
         }
-
-        if folded {
-            continue;
+        if !folded {
+            i += 1;
         }
+    }
+    folds
+}
 
-        let mut j = i;
-        while j < logs.len() && logs[i].content == logs[j].content {
-            j += 1;
+/// Replace each fold's entries with a summary, moving the rest out of `logs`
+///
+/// Fold indices are offset by `base` in the summaries.
+pub fn apply_folds(
+    logs: Vec<LogEntry>,
+    folds: &[Fold],
+    base: usize,
+    config: &FiltersConfig,
+) -> Vec<DisplayEntry> {
+    let mut result = Vec::with_capacity(logs.len());
+    let mut folds = folds.iter().peekable();
+    for (i, log) in logs.into_iter().enumerate() {
+        match folds.peek() {
+            Some(fold) if i >= fold.start => {
+                if i + 1 == fold.end {
+                    result.push(DisplayEntry::Folded {
+                        start_index: base + fold.start,
+                        end_index: base + fold.end - 1,
+                        count: fold.end - fold.start,
+                        summary_text: fold.kind.summary(fold.end - fold.start, config),
+                    });
+                    folds.next();
+                }
+            }
+            _ => result.push(DisplayEntry::Normal(log)),
         }
-        if j - i >= 5 {
-            result.push(DisplayEntry::Folded {
-                start_index: i,
-                end_index: j - 1,
-                count: j - i,
-                summary_text: format!("Folded {} identical", j - i),
-            });
-            i = j;
-            continue;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EntryKind;
+
+    fn entry(source_id: usize, ts: Option<&str>, content: &str) -> LogEntry {
+        LogEntry {
+            timestamp: ts.unwrap_or_default().into(),
+            time: ts.map(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").unwrap()),
+            pid: "".into(),
+            tid: "".into(),
+            level: "Info".into(),
+            content: content.into(),
+            source_file: "".into(),
+            line_num: 0,
+            json_payload: None,
+            delta_ms: None,
+            source_id,
+            line_index: 0,
+            kind: EntryKind::Record,
         }
+    }
 
-        result.push(DisplayEntry::Normal(logs[i].clone()));
-        i += 1;
+    #[test]
+    fn test_merge_by_time_interleaves_files() {
+        let a = vec![
+            entry(0, Some("2024-01-15 10:00:00"), "a1"),
+            entry(0, None, "a1 continued"),
+            entry(0, Some("2024-01-15 10:00:05"), "a2"),
+        ];
+        let b = vec![
+            entry(1, Some("2024-01-15 10:00:00"), "b1"),
+            entry(1, Some("2024-01-15 10:00:02"), "b2"),
+        ];
+        let merged: Vec<_> = merge_by_time(vec![a, b]).into_iter().map(|e| e.content).collect();
+        assert_eq!(merged, vec!["a1", "a1 continued", "b1", "b2", "a2"]);
+    }
+
+    #[test]
+    fn test_fold_noise_moves_entries() {
+        let mut logs: Vec<_> = (0..6).map(|_| entry(0, None, "same")).collect();
+        logs.push(entry(0, None, "other"));
+        let config = FiltersConfig { fold_rules: vec![], ..FiltersConfig::default() };
+        let folds = find_folds(&logs, &config);
+        let folded = apply_folds(logs, &folds, 0, &config);
+        assert_eq!(folded.len(), 2);
+        assert!(matches!(folded[0], DisplayEntry::Folded { start_index: 0, end_index: 5, count: 6, .. }));
+        assert!(matches!(&folded[1], DisplayEntry::Normal(log) if log.content == "other"));
     }
-    result
 }
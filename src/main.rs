@@ -1,26 +1,40 @@
 mod ai_client;
 mod analytics;
 mod app_state;
+mod compression;
 mod config;
+mod context;
+mod encoding;
+mod entry_list;
 mod export;
+mod filter_stack;
 mod filtering;
 mod history;
+mod index;
 mod live;
 mod logic;
 mod models;
 mod parser;
+mod pipeline;
 mod search;
 mod search_form;
+mod search_job;
+mod source;
+mod storage;
 mod templates;
 mod time_parser;
 mod report;
 mod tui;
 mod web;
 
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::stdout;
-use std::path::PathBuf;
+use std::io::{stdout, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use clap::{CommandFactory, Parser};
@@ -29,22 +43,25 @@ use crossterm::terminal::{
 };
 use crossterm::ExecutableCommand;
 use glob::glob;
-use memmap2::Mmap;
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use ratatui::prelude::*;
+use rayon::prelude::*;
 use tokio::sync::mpsc;
 
-use analytics::compute_dashboard_stats;
 use app_state::App;
-use config::AppConfig;
-use live::TailState;
-use logic::fold_noise;
-use models::{ChatMessage, DashboardStats, FileInfo, LogEntry};
-use parser::{
-    build_histogram, calculate_deltas, create_log_regex, decode_line, merge_multiline_bytes,
-    parse_line,
-};
+use config::{AppConfig, ParserConfig};
+use context::ContextLines;
+use encoding::{detect_encoding, TextEncoding, SAMPLE_BYTES};
+use entry_list::EntryList;
+use live::{FileSetup, LiveFiles, StdinSource};
+use logic::merge_by_time;
+use models::{ChatMessage, EntryKind, FileInfo, LogEntry, TimeDisplay, FILE_COLORS};
+use parser::{build_profiles, detect_profile, parse_records, split_at_records, ParserProfile};
+use pipeline::Pipeline;
+use source::SourceBytes;
+use time_parser::LogTimezone;
 use tui::run_app;
+use tui::syntax::Highlight;
 
 #[derive(Parser)]
 #[command(
@@ -53,31 +70,151 @@ use tui::run_app;
     about = "TUI 日志分析器 - 支持多文件、实时追踪、AI 分析"
 )]
 struct Cli {
-    /// 要分析的日志文件 (支持通配符，如 *.log)
+    /// 要分析的日志文件 (支持通配符，如 *.log；`-` 表示标准输入)
     #[arg(value_name = "FILE")]
     files: Vec<String>,
 
+    /// 从标准输入实时读取日志 (等同于文件参数 `-`)
+    #[arg(long)]
+    stdin: bool,
+
     /// 配置文件路径
     #[arg(short, long, value_name = "CONFIG")]
     config: Option<PathBuf>,
+
+    /// 项目目录：历史、模板和报告缓存保存在其中的 .loginsight/ 下，而不是 ~/.loginsight/
+    #[arg(short, long, value_name = "DIR")]
+    workspace: Option<PathBuf>,
+
+    /// 指定解析配置，跳过自动检测 (NAME 作用于全部文件，GLOB=NAME 作用于匹配的文件)
+    #[arg(short, long, value_name = "[GLOB=]NAME")]
+    profile: Vec<String>,
+
+    /// 高级搜索和专注模式中每条匹配之后显示的上下文条数
+    #[arg(short = 'A', long, value_name = "N")]
+    after_context: Option<usize>,
+
+    /// 高级搜索和专注模式中每条匹配之前显示的上下文条数
+    #[arg(short = 'B', long, value_name = "N")]
+    before_context: Option<usize>,
+
+    /// 同时设置匹配前后的上下文条数 (-A/-B 优先)
+    #[arg(short = 'C', long, value_name = "N")]
+    context: Option<usize>,
+}
+
+/// A `--profile` override: profile name, optionally restricted to files matching a glob
+struct ProfileOverride {
+    pattern: Option<glob::Pattern>,
+    name: String,
+}
+
+impl ProfileOverride {
+    fn parse(arg: &str) -> Result<Self> {
+        match arg.split_once('=') {
+            Some((pattern, name)) => Ok(Self {
+                pattern: Some(
+                    glob::Pattern::new(pattern)
+                        .with_context(|| format!("无效模式: {}", pattern))?,
+                ),
+                name: name.to_string(),
+            }),
+            None => Ok(Self {
+                pattern: None,
+                name: arg.to_string(),
+            }),
+        }
+    }
+
+    fn applies_to(&self, path: &Path) -> bool {
+        match &self.pattern {
+            None => true,
+            Some(p) => {
+                p.matches_path(path)
+                    || path
+                        .file_name()
+                        .is_some_and(|n| p.matches(&n.to_string_lossy()))
+            }
+        }
+    }
 }
 
 fn main() -> Result<()> {
     // 1. Parse CLI args
     let cli = Cli::parse();
 
-    if cli.files.is_empty() {
+    let use_stdin = cli.stdin || cli.files.iter().any(|f| f == "-");
+    if cli.files.is_empty() && !use_stdin {
         Cli::command().print_help()?;
         println!("\n\n示例: log service.log");
         println!("      log logs/*.log");
+        println!("      kubectl logs my-pod | log -");
         std::process::exit(0);
     }
+    if use_stdin && std::io::stdin().is_terminal() {
+        anyhow::bail!("标准输入不是管道，请使用 `命令 | log -`");
+    }
+    let patterns: Vec<String> = cli.files.iter().filter(|f| *f != "-").cloned().collect();
 
     // 2. Load config
     let config = AppConfig::load_from(cli.config.as_deref())?;
+    storage::init(storage::resolve_dir(
+        cli.workspace.as_deref(),
+        cli.config.as_deref(),
+    )?);
 
     // 3. Load and parse log files
-    let (entries, files, histogram, file_paths, re, stats) = load_logs(&cli.files, &config)?;
+    let overrides = cli
+        .profile
+        .iter()
+        .map(|p| ProfileOverride::parse(p))
+        .collect::<Result<Vec<_>>>()?;
+    let (entries, mut files, histogram, file_paths, parsers, pipeline) =
+        load_logs(&patterns, &config, &overrides)?;
+
+    let tail_quiet = Duration::from_millis(config.parser.tail_quiet_ms);
+
+    // stdin becomes a pseudo-file after the real ones, filled in while running
+    let mut stdin_source = if use_stdin {
+        let stdin_path = Path::new("-");
+        let profiles: Vec<ParserProfile> = build_profiles(&config.parser)?
+            .into_iter()
+            .map(|p| adjust_clock(p, stdin_path, &config.parser))
+            .collect();
+        let profile = overrides
+            .iter()
+            .rev()
+            .find(|o| o.applies_to(stdin_path))
+            .and_then(|o| profiles.iter().find(|p| p.name == o.name).cloned());
+        let encoding = config
+            .parser
+            .file_overrides
+            .iter()
+            .rev()
+            .filter(|o| o.applies_to(stdin_path))
+            .find_map(|o| o.encoding.as_deref())
+            .and_then(TextEncoding::from_name)
+            .or_else(|| profile.as_ref().and_then(|p| p.encoding));
+        files.push(FileInfo {
+            id: files.len(),
+            name: "<stdin>".into(),
+            color: Color::White,
+            enabled: true,
+            profile: profile.as_ref().map_or_else(|| "?".into(), |p| p.name.clone()),
+            encoding: encoding.unwrap_or(TextEncoding::Utf8),
+            parse_failures: 0,
+        });
+        Some(StdinSource::spawn(
+            files.len() - 1,
+            profiles,
+            profile,
+            encoding,
+            config.parser.detect_sample_lines,
+            tail_quiet,
+        ))
+    } else {
+        None
+    };
 
     // 4. Setup AI background task
     let rt = tokio::runtime::Runtime::new()?;
@@ -127,36 +264,76 @@ fn main() -> Result<()> {
         report_resp_rx,
         config.theme.page_size,
     );
+    let stats = pipeline.stats(app.time_display);
     app.stats = stats.clone();
+    app.profiles = parsers.iter().cloned().map(Some).collect();
+    if let Some(source) = &stdin_source {
+        app.profiles.push(source.profile().cloned());
+    }
+    app.pipeline = pipeline;
+    // History and templates are per project; say where they are kept
+    app.status_msg = Some((
+        format!("数据目录: {}", storage::dir().display()),
+        Instant::now(),
+    ));
+    // Piped input is only useful live
+    app.is_tailing = stdin_source.is_some();
+    app.context_lines = ContextLines {
+        before: cli.before_context.or(cli.context).unwrap_or(0),
+        after: cli.after_context.or(cli.context).unwrap_or(0),
+    };
 
     // Initialize correlation regexes for trace filtering
     app.load_correlation_patterns(&config.filters.correlation_patterns);
+    app.highlights = config
+        .highlight
+        .iter()
+        .cloned()
+        .map(Highlight::new)
+        .collect::<Result<_>>()?;
 
     // Create shared state for web server
     let web_shared_state = web::state::WebSharedState::new(stats);
+    app.web_state = Some(web_shared_state.clone());
+
+    // 6. Follow the loaded files from their current end; files created later that
+    // match the input globs are set up the same way they would have been at load
+    let setup_profiles = build_profiles(&config.parser)?;
+    let setup_config = config.parser.clone();
+    let setup: FileSetup = Box::new(move |path| {
+        // Only the start is needed to detect the encoding and profile; a new file may
+        // already be large (a copied or rotated-in log)
+        let mut raw = Vec::new();
+        File::open(path)
+            .ok()?
+            .take(SAMPLE_BYTES as u64)
+            .read_to_end(&mut raw)
+            .ok()?;
+        if raw.is_empty() {
+            return None;
+        }
+        Some(resolve_file(path, &raw, &setup_profiles, &overrides, &setup_config))
+    });
+    let mut live_files = LiveFiles::new(patterns, setup, tail_quiet);
+    for (id, (path, profile)) in file_paths.into_iter().zip(parsers).enumerate() {
+        live_files.add_loaded(id, path, profile);
+    }
 
-    // 6. Setup file watcher for live tailing
+    // Watch directories rather than files, so created and recreated files are seen
     let (file_tx, file_rx) = std_mpsc::channel();
-    let watch_paths = file_paths.clone();
     let mut watcher = RecommendedWatcher::new(
         move |res: Result<notify::Event, notify::Error>| {
             if let Ok(event) = res {
-                if event.kind.is_modify() {
+                if event.kind.is_modify() || event.kind.is_create() {
                     let _ = file_tx.send(event.paths);
                 }
             }
         },
         Config::default(),
     )?;
-    for path in &watch_paths {
-        watcher.watch(path, RecursiveMode::NonRecursive)?;
-    }
-
-    // Initialize tail state with current file sizes
-    let mut tail_state = TailState::new();
-    for (id, path) in file_paths.iter().enumerate() {
-        if let Ok(meta) = std::fs::metadata(path) {
-            tail_state.init_offset(id, meta.len());
+    for dir in live_files.watch_dirs() {
+        if dir.is_dir() {
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
         }
     }
 
@@ -175,9 +352,8 @@ fn main() -> Result<()> {
         &mut terminal,
         &mut app,
         file_rx,
-        &mut tail_state,
-        &file_paths,
-        &re,
+        &mut live_files,
+        stdin_source.as_mut(),
     );
 
     // 9. Restore terminal (always runs)
@@ -188,26 +364,36 @@ fn main() -> Result<()> {
     result
 }
 
-fn load_logs(
-    patterns: &[String],
-    config: &AppConfig,
-) -> Result<(
-    Vec<models::DisplayEntry>,
+/// Folded entries, file infos, histogram, paths, resolved profiles and the pipeline
+/// that continues with live entries
+type LoadedLogs = (
+    EntryList,
     Vec<FileInfo>,
     Vec<(String, u64)>,
     Vec<PathBuf>,
-    regex::Regex,
-    DashboardStats,
-)> {
-    let colors = [
-        Color::Red,
-        Color::Blue,
-        Color::Green,
-        Color::Yellow,
-        Color::Cyan,
-        Color::Magenta,
-    ];
-    let re = create_log_regex(&config.parser)?;
+    Vec<ParserProfile>,
+    Pipeline,
+);
+
+fn load_logs(
+    patterns: &[String],
+    config: &AppConfig,
+    overrides: &[ProfileOverride],
+) -> Result<LoadedLogs> {
+    let profiles = build_profiles(&config.parser)?;
+    for o in overrides {
+        if !profiles.iter().any(|p| p.name == o.name) {
+            anyhow::bail!("未知的解析配置: {}", o.name);
+        }
+    }
+    for o in &config.parser.file_overrides {
+        if let Some(name) = &o.encoding {
+            TextEncoding::parse(name)?;
+        }
+        if let Some(name) = &o.timezone {
+            LogTimezone::parse(name)?;
+        }
+    }
 
     let ignore_regexes: Vec<regex::Regex> = config
         .filters
@@ -222,47 +408,182 @@ fn load_logs(
             file_paths.push(entry?);
         }
     }
-    if file_paths.is_empty() {
+    // With only stdin there are no patterns, and no files is fine
+    if file_paths.is_empty() && !patterns.is_empty() {
         anyhow::bail!("没有找到匹配的文件");
     }
 
     let mut files: Vec<FileInfo> = Vec::new();
-    let mut all_entries: Vec<models::LogEntry> = Vec::new();
+    let mut parsers: Vec<ParserProfile> = Vec::new();
+    let mut per_file: Vec<Vec<models::LogEntry>> = Vec::new();
 
     for (id, path) in file_paths.iter().enumerate() {
         let file = File::open(path).with_context(|| format!("无法打开: {:?}", path))?;
-        // SAFETY: The file handle `file` is kept open for the lifetime of `mmap`.
-        // The file is read-only during parsing, and we don't rely on the file contents
-        // remaining unchanged by external processes during this short parsing window.
-        let mmap = unsafe { Mmap::map(&file)? };
-        let entries: Vec<models::LogEntry> = merge_multiline_bytes(&mmap)
-            .iter()
-            .enumerate()
-            .filter_map(|(i, b)| {
-                let line = decode_line(b);
-                if ignore_regexes.iter().any(|ig| ig.is_match(&line)) {
-                    return None;
-                }
-                parse_line(&line, b, &re, id, i + 1)
+        let mapped = SourceBytes::map(&file).with_context(|| format!("无法读取: {:?}", path))?;
+        // Rotated archives are decompressed into memory; plain files stay mapped
+        let raw = Arc::new(match compression::decompress_if_needed(&mapped, path)? {
+            Some(decompressed) => SourceBytes::new(decompressed),
+            None => mapped,
+        });
+        let (profile, encoding) = resolve_file(path, &raw, &profiles, overrides, &config.parser);
+        let data = encoding.line_bytes(&raw);
+        // Entries point into the bytes they were parsed from, so transcoded UTF-16
+        // replaces the raw file as their source and is then read as UTF-8
+        let transcoded = match data {
+            Cow::Owned(bytes) => Some(bytes),
+            Cow::Borrowed(_) => None,
+        };
+        let (source, line_encoding) = match transcoded {
+            Some(bytes) => (Arc::new(SourceBytes::new(bytes)), TextEncoding::Utf8),
+            None => (raw, encoding),
+        };
+        let data = line_encoding.line_bytes(&source);
+        // Parse chunks of at least ~1 MB in parallel, then stitch record numbering
+        let chunk_count = (data.len() >> 20).clamp(1, rayon::current_num_threads() * 4);
+        let chunks = split_at_records(&data, &profile, chunk_count);
+        let progress = LoadProgress::new(
+            format!("[{}/{}] {}", id + 1, file_paths.len(), path.display()),
+            data.len(),
+        );
+        let parsed: Vec<(usize, Vec<models::LogEntry>)> = chunks
+            .par_iter()
+            .map(|chunk| {
+                let result = parse_records(
+                    chunk,
+                    &profile,
+                    line_encoding,
+                    Some(&source),
+                    id,
+                    0,
+                    &ignore_regexes,
+                );
+                progress.advance(chunk.len());
+                result
             })
             .collect();
+        progress.finish();
+        let mut entries = Vec::with_capacity(parsed.iter().map(|(_, e)| e.len()).sum());
+        let mut records_before = 0;
+        for (count, mut chunk_entries) in parsed {
+            for entry in &mut chunk_entries {
+                entry.line_index += records_before;
+            }
+            records_before += count;
+            entries.append(&mut chunk_entries);
+        }
         files.push(FileInfo {
             id,
             name: path
                 .file_name()
                 .map(|s| s.to_string_lossy().into())
                 .unwrap_or_else(|| "?".into()),
-            color: colors[id % colors.len()],
+            color: FILE_COLORS[id % FILE_COLORS.len()],
             enabled: true,
+            profile: profile.name.clone(),
+            encoding,
+            parse_failures: entries
+                .iter()
+                .filter(|e| e.kind == EntryKind::Unparsed)
+                .count(),
         });
-        all_entries.extend(entries);
+        parsers.push(profile);
+        per_file.push(entries);
+    }
+
+    // Live-tailed entries later continue through the same pipeline
+    let mut pipeline = Pipeline::new(config.filters.clone());
+    let mut folded = EntryList::default();
+    pipeline.process(&mut folded, merge_by_time(per_file));
+    let histogram = pipeline.histogram(TimeDisplay::default());
+
+    Ok((folded, files, histogram, file_paths, parsers, pipeline))
+}
+
+/// Pick a file's parser profile and encoding from its raw bytes
+///
+/// Later `--profile` overrides win, so a file-specific GLOB=NAME can refine a global
+/// NAME. Encoding precedence: file override, then the profile's encoding, then detection.
+fn resolve_file(
+    path: &Path,
+    raw: &[u8],
+    profiles: &[ParserProfile],
+    overrides: &[ProfileOverride],
+    parser: &ParserConfig,
+) -> (ParserProfile, TextEncoding) {
+    let forced_encoding = parser
+        .file_overrides
+        .iter()
+        .rev()
+        .filter(|o| o.applies_to(path))
+        .find_map(|o| o.encoding.as_deref())
+        .and_then(TextEncoding::from_name);
+    let encoding = forced_encoding.unwrap_or_else(|| detect_encoding(raw));
+    let profile = match overrides.iter().rev().find(|o| o.applies_to(path)) {
+        Some(o) => profiles.iter().find(|p| p.name == o.name).unwrap().clone(),
+        None => profiles[detect_profile(
+            &encoding.line_bytes(raw),
+            encoding,
+            profiles,
+            parser.detect_sample_lines,
+        )]
+        .clone(),
+    };
+    let encoding = match (forced_encoding, profile.encoding) {
+        (None, Some(profile_encoding)) => profile_encoding,
+        _ => encoding,
+    };
+    (adjust_clock(profile, path, parser), encoding)
+}
+
+/// Apply the timezone and clock skew of the file overrides matching `path`
+fn adjust_clock(mut profile: ParserProfile, path: &Path, parser: &ParserConfig) -> ParserProfile {
+    let matching = || {
+        parser
+            .file_overrides
+            .iter()
+            .rev()
+            .filter(|o| o.applies_to(path))
+    };
+    if let Some(timezone) = matching()
+        .find_map(|o| o.timezone.as_deref())
+        .and_then(|name| LogTimezone::parse(name).ok())
+    {
+        profile.clock.timezone = timezone;
+    }
+    if let Some(ms) = matching().find_map(|o| o.clock_skew_ms) {
+        profile.clock.skew = chrono::Duration::milliseconds(ms);
+    }
+    profile
+}
+
+/// Parse progress for one file, printed to stderr before the TUI starts
+struct LoadProgress {
+    label: String,
+    total: usize,
+    done: AtomicUsize,
+    enabled: bool,
+}
+
+impl LoadProgress {
+    fn new(label: String, total: usize) -> Self {
+        Self {
+            label,
+            total: total.max(1),
+            done: AtomicUsize::new(0),
+            enabled: std::io::stderr().is_terminal(),
+        }
     }
 
-    all_entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-    calculate_deltas(&mut all_entries);
-    let histogram = build_histogram(&all_entries);
-    let stats = compute_dashboard_stats(&all_entries);
-    let folded = fold_noise(all_entries, &config.filters);
+    fn advance(&self, bytes: usize) {
+        let done = self.done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if self.enabled {
+            eprint!("\r正在解析 {} {:>3}%", self.label, done * 100 / self.total);
+        }
+    }
 
-    Ok((folded, files, histogram, file_paths, re, stats))
+    fn finish(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+        }
+    }
 }
@@ -1,13 +1,38 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use ratatui::prelude::Color;
 use serde::Serialize;
 use serde_json::Value;
 
+use crate::encoding::TextEncoding;
+use crate::parser::ParseDiagnosis;
+use crate::source::LazyText;
+use crate::time_parser::{parse_user_time, parse_user_time_utc};
+
+/// Sidebar colors, assigned to files by source id
+pub const FILE_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Blue,
+    Color::Green,
+    Color::Yellow,
+    Color::Cyan,
+    Color::Magenta,
+];
+
 #[derive(Debug, Clone)]
 pub struct FileInfo {
     pub id: usize,
     pub name: String,
     pub color: Color,
     pub enabled: bool,
+    /// Name of the parser profile used for this file
+    pub profile: String,
+    /// Text encoding the file is decoded with
+    pub encoding: TextEncoding,
+    /// Records kept as raw text because the profile couldn't parse them
+    pub parse_failures: usize,
 }
 
 #[derive(Default, Clone, Copy, PartialEq)]
@@ -24,25 +49,70 @@ pub enum InputMode {
     Normal,
     Editing,
     JumpInput,
+    ContextInput,
+    LayerInput,
+    TemplateRenameInput,
+    TemplateExportInput,
+    TemplateImportInput,
     AiPromptInput,
     ChatInput,
     ReportSaveInput,
     FocusCopyInput,
 }
 
+/// One parsed record
+///
+/// Text fields point into the file's bytes where they can (see [`LazyText`]) and the
+/// short per-line fields are interned, so cloning an entry never copies log text.
 #[derive(Debug, Serialize, Clone)]
 pub struct LogEntry {
-    pub timestamp: String,
-    pub pid: String,
-    pub tid: String,
-    pub level: String,
-    pub content: String,
-    pub source_file: String,
+    pub timestamp: LazyText,
+    /// `timestamp` parsed at ingest, in local time
+    #[serde(skip)]
+    pub time: Option<NaiveDateTime>,
+    pub pid: Arc<str>,
+    pub tid: Arc<str>,
+    pub level: Arc<str>,
+    pub content: LazyText,
+    pub source_file: Arc<str>,
     pub line_num: u32,
-    pub json_payload: Option<Value>,
+    pub json_payload: Option<Box<Value>>,
     pub delta_ms: Option<i64>,
     pub source_id: usize,
     pub line_index: usize,
+    #[serde(skip)]
+    pub kind: EntryKind,
+}
+
+/// What a `LogEntry` stands for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A record parsed from the log
+    #[default]
+    Record,
+    /// Inserted by live tail where a file was rotated or truncated
+    Rotation,
+    /// A record the file's profile couldn't parse, kept as raw text in `content`
+    Unparsed,
+}
+
+impl LogEntry {
+    /// Append the structured payload, flattened to `key=value` pairs for text
+    /// matching, to `out`
+    pub fn write_payload_text(&self, out: &mut String) {
+        let Some(Value::Object(map)) = self.json_payload.as_deref() else {
+            return;
+        };
+        for (i, (k, v)) in map.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            let _ = match v {
+                Value::String(s) => write!(out, "{}={}", k, s),
+                other => write!(out, "{}={}", k, other),
+            };
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -63,10 +133,15 @@ impl DisplayEntry {
             _ => None,
         }
     }
-    pub fn get_searchable_text(&self) -> String {
+    /// Append the text quick search matches against to `out`, so callers going over
+    /// many entries can reuse one buffer
+    pub fn write_searchable_text(&self, out: &mut String) {
         match self {
-            DisplayEntry::Normal(log) => format!("{} {} {}", log.content, log.source_file, log.tid),
-            DisplayEntry::Folded { summary_text, .. } => summary_text.clone(),
+            DisplayEntry::Normal(log) => {
+                let _ = write!(out, "{} {} {} ", log.content, log.source_file, log.tid);
+                log.write_payload_text(out);
+            }
+            DisplayEntry::Folded { summary_text, .. } => out.push_str(summary_text),
         }
     }
     /// Reserved for future use (e.g., performance analysis display)
@@ -136,6 +211,73 @@ impl Default for LevelVisibility {
     }
 }
 
+/// How entry times are shown; entries are always sorted and compared in local time
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TimeDisplay {
+    /// Normalized to the local timezone
+    #[default]
+    Local,
+    /// Normalized to UTC
+    Utc,
+    /// The timestamp text as written in the file
+    Original,
+}
+
+impl TimeDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            TimeDisplay::Local => TimeDisplay::Utc,
+            TimeDisplay::Utc => TimeDisplay::Original,
+            TimeDisplay::Original => TimeDisplay::Local,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeDisplay::Local => "本地时间",
+            TimeDisplay::Utc => "UTC",
+            TimeDisplay::Original => "原始时间",
+        }
+    }
+
+    /// `log`'s time in this mode, formatted with `fmt`; entries without a parsed time
+    /// show their timestamp text
+    pub fn format(self, log: &LogEntry, fmt: &str) -> String {
+        match (self, log.time) {
+            (TimeDisplay::Local | TimeDisplay::Utc, Some(t)) => self.in_zone(t).format(fmt).to_string(),
+            _ => log.timestamp.to_string(),
+        }
+    }
+
+    /// A local `time` as wall-clock time in this mode's zone; `Original` stays local,
+    /// as the zone the text was written in isn't kept
+    pub fn in_zone(self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            TimeDisplay::Utc => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map_or(time, |t| t.naive_utc()),
+            TimeDisplay::Local | TimeDisplay::Original => time,
+        }
+    }
+
+    /// A UTC `time` as wall-clock time in this mode's zone
+    pub fn utc_to_zone(self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            TimeDisplay::Utc => time,
+            TimeDisplay::Local | TimeDisplay::Original => Local.from_utc_datetime(&time).naive_local(),
+        }
+    }
+
+    /// Parse a time typed by the user, reading absolute times in the zone shown
+    pub fn parse_user_time(self, input: &str) -> Option<DateTime<Local>> {
+        match self {
+            TimeDisplay::Utc => parse_user_time_utc(input),
+            TimeDisplay::Local | TimeDisplay::Original => parse_user_time(input),
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy, PartialEq)]
 pub enum CurrentView {
     #[default]
@@ -145,6 +287,7 @@ pub enum CurrentView {
     History,
     Report,
     Focus,
+    Templates,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -180,6 +323,17 @@ pub struct DashboardStats {
     pub sparkline_data: Vec<u64>,
 }
 
+/// Content of the parse diagnostics popup: one unparsed record of a file
+#[derive(Debug, Clone)]
+pub struct ParseDiagnostics {
+    pub file: String,
+    pub profile: String,
+    pub failures: usize,
+    pub line_index: usize,
+    pub record: String,
+    pub diagnosis: ParseDiagnosis,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExportType {
     LogsCsv,
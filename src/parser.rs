@@ -1,17 +1,453 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use anyhow::Result;
 use chrono::NaiveDateTime;
-use encoding_rs::GB18030;
 use regex::bytes::Regex as BytesRegex;
 use regex::Regex;
 use serde_json::Value;
 
-use crate::config::ParserConfig;
-use crate::models::LogEntry;
+use crate::config::{ParserConfig, ProfileConfig, ProfileFormat};
+use crate::encoding::TextEncoding;
+use crate::models::{EntryKind, LogEntry};
+use crate::source::{LazyText, SourceBytes, TextStore};
+use crate::time_parser::{strftime_to_regex, ClockAdjust, LogTimezone};
 
-pub fn parse_timestamp(ts: &str) -> Option<NaiveDateTime> {
-    NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S%.3f").ok()
+/// Name of the profile built from `parser.log_pattern`
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Name of the built-in JSON Lines profile
+pub const JSON_PROFILE: &str = "json";
+
+/// Name of the built-in logfmt profile
+pub const LOGFMT_PROFILE: &str = "logfmt";
+
+/// `LogEntry` fields a parser profile can populate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryField {
+    Timestamp,
+    Pid,
+    Tid,
+    Level,
+    Content,
+    SourceFile,
+    LineNum,
+}
+
+impl EntryField {
+    const ALL: [EntryField; 7] = [
+        EntryField::Timestamp,
+        EntryField::Pid,
+        EntryField::Tid,
+        EntryField::Level,
+        EntryField::Content,
+        EntryField::SourceFile,
+        EntryField::LineNum,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            EntryField::Timestamp => "timestamp",
+            EntryField::Pid => "pid",
+            EntryField::Tid => "tid",
+            EntryField::Level => "level",
+            EntryField::Content => "content",
+            EntryField::SourceFile => "source_file",
+            EntryField::LineNum => "line_num",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+/// Reference to a capture group, by position or by name
+#[derive(Debug, Clone)]
+enum GroupRef {
+    Index(usize),
+    Name(String),
+}
+
+impl GroupRef {
+    fn parse(s: &str) -> Self {
+        s.parse().map(GroupRef::Index).unwrap_or_else(|_| GroupRef::Name(s.to_string()))
+    }
+}
+
+/// How a profile turns a record into `LogEntry` fields
+#[derive(Debug, Clone)]
+enum LineFormat {
+    /// Regex with capture groups mapped onto fields
+    Regex {
+        re: Regex,
+        fields: Vec<(EntryField, GroupRef)>,
+    },
+    /// One JSON object per record; each field lists candidate keys (dotted paths)
+    Json { fields: Vec<(EntryField, Vec<String>)> },
+    /// `key=value` pairs per record; each field lists candidate keys
+    Logfmt { fields: Vec<(EntryField, Vec<String>)> },
+}
+
+/// A compiled parser profile: line format, record-start rule and field mapping
+#[derive(Debug, Clone)]
+pub struct ParserProfile {
+    pub name: String,
+    format: LineFormat,
+    record_start: BytesRegex,
+    /// Lines matching any of these never start a record
+    continuation: Vec<BytesRegex>,
+    timestamp_format: Option<String>,
+    /// Encoding forced by the profile's config, otherwise detected per file
+    pub encoding: Option<TextEncoding>,
+    /// Timezone of the profile, plus the zone and skew file overrides set per file
+    pub clock: ClockAdjust,
+}
+
+/// Keys tried for each field by the built-in `json` profile, first match wins
+const DEFAULT_JSON_KEYS: [(EntryField, &str); 7] = [
+    (EntryField::Timestamp, "ts|time|timestamp|@timestamp"),
+    (EntryField::Pid, "pid"),
+    (EntryField::Tid, "thread|tid|thread_id"),
+    (EntryField::Level, "level|lvl|severity"),
+    (EntryField::Content, "msg|message"),
+    (EntryField::SourceFile, "caller|source|logger"),
+    (EntryField::LineNum, "line"),
+];
+
+/// Keys tried for each field by the built-in `logfmt` profile, first match wins
+const DEFAULT_LOGFMT_KEYS: [(EntryField, &str); 7] = [
+    (EntryField::Timestamp, "time|ts|t"),
+    (EntryField::Pid, "pid"),
+    (EntryField::Tid, "thread|tid|goroutine"),
+    (EntryField::Level, "level|lvl"),
+    (EntryField::Content, "msg|message"),
+    (EntryField::SourceFile, "caller|source"),
+    (EntryField::LineNum, "line"),
+];
+
+/// Merge configured `field -> "key|alt"` mappings over a format's default key candidates
+fn keyed_fields(
+    defaults: &[(EntryField, &str)],
+    configured: &[(EntryField, &String)],
+) -> Vec<(EntryField, Vec<String>)> {
+    defaults
+        .iter()
+        .map(|(f, keys)| {
+            let keys = configured
+                .iter()
+                .find(|(mapped, _)| mapped == f)
+                .map(|(_, k)| k.as_str())
+                .unwrap_or(keys);
+            (*f, keys.split('|').map(String::from).collect())
+        })
+        .collect()
+}
+
+impl ParserProfile {
+    /// Build the default profile from `parser.log_pattern` (positional groups 1-7)
+    fn from_default_pattern(config: &ParserConfig) -> Result<Self> {
+        Ok(Self {
+            name: DEFAULT_PROFILE.into(),
+            format: LineFormat::Regex {
+                re: create_log_regex(config)?,
+                fields: EntryField::ALL
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, f)| (f, GroupRef::Index(i + 1)))
+                    .collect(),
+            },
+            record_start: match &config.record_start {
+                Some(pattern) => compile_record_start(pattern)?,
+                None => BytesRegex::new(&format!("^{}", strftime_to_regex(&config.timestamp_format)))
+                    .map_err(|e| anyhow::anyhow!("无效的时间格式 '{}': {}", config.timestamp_format, e))?,
+            },
+            continuation: Vec::new(),
+            timestamp_format: Some(config.timestamp_format.clone()),
+            encoding: None,
+            clock: clock_for(config.timezone.as_deref())?,
+        })
+    }
+
+    /// Build the built-in JSON Lines profile
+    fn default_json() -> Self {
+        Self {
+            name: JSON_PROFILE.into(),
+            format: LineFormat::Json {
+                fields: keyed_fields(&DEFAULT_JSON_KEYS, &[]),
+            },
+            record_start: json_record_start(),
+            continuation: Vec::new(),
+            timestamp_format: None,
+            encoding: None,
+            clock: ClockAdjust::default(),
+        }
+    }
+
+    /// Build the built-in logfmt profile
+    fn default_logfmt() -> Self {
+        Self {
+            name: LOGFMT_PROFILE.into(),
+            format: LineFormat::Logfmt {
+                fields: keyed_fields(&DEFAULT_LOGFMT_KEYS, &[]),
+            },
+            record_start: logfmt_record_start(),
+            continuation: Vec::new(),
+            timestamp_format: None,
+            encoding: None,
+            clock: ClockAdjust::default(),
+        }
+    }
+
+    fn from_config(config: &ProfileConfig) -> Result<Self> {
+        let invalid = |e: regex::Error| anyhow::anyhow!("解析配置 '{}' 的正则无效: {}", config.name, e);
+        let mut fields = Vec::new();
+        for (field, source) in &config.fields {
+            let field = EntryField::from_name(field).ok_or_else(|| {
+                anyhow::anyhow!("解析配置 '{}' 包含未知字段: {}", config.name, field)
+            })?;
+            fields.push((field, source));
+        }
+
+        let mut profile = match config.format {
+            ProfileFormat::Regex => {
+                let re = Regex::new(&config.pattern).map_err(invalid)?;
+                let record_start = BytesRegex::new(&config.pattern).map_err(invalid)?;
+                let fields = if fields.is_empty() {
+                    EntryField::ALL
+                        .into_iter()
+                        .filter(|f| re.capture_names().flatten().any(|n| n == f.name()))
+                        .map(|f| (f, GroupRef::Name(f.name().into())))
+                        .collect()
+                } else {
+                    fields
+                        .into_iter()
+                        .map(|(f, group)| (f, GroupRef::parse(group)))
+                        .collect()
+                };
+                Self {
+                    name: config.name.clone(),
+                    format: LineFormat::Regex { re, fields },
+                    record_start,
+                    continuation: Vec::new(),
+                    timestamp_format: config.timestamp_format.clone(),
+                    encoding: None,
+                    clock: ClockAdjust::default(),
+                }
+            }
+            // Unmapped fields keep the built-in key candidates
+            ProfileFormat::Json => Self {
+                name: config.name.clone(),
+                format: LineFormat::Json {
+                    fields: keyed_fields(&DEFAULT_JSON_KEYS, &fields),
+                },
+                record_start: json_record_start(),
+                continuation: Vec::new(),
+                timestamp_format: config.timestamp_format.clone(),
+                encoding: None,
+                clock: ClockAdjust::default(),
+            },
+            ProfileFormat::Logfmt => Self {
+                name: config.name.clone(),
+                format: LineFormat::Logfmt {
+                    fields: keyed_fields(&DEFAULT_LOGFMT_KEYS, &fields),
+                },
+                record_start: logfmt_record_start(),
+                continuation: Vec::new(),
+                timestamp_format: config.timestamp_format.clone(),
+                encoding: None,
+                clock: ClockAdjust::default(),
+            },
+        };
+        if let Some(pattern) = &config.record_start {
+            profile.record_start = compile_record_start(pattern)?;
+        }
+        profile.continuation = compile_continuation(&config.continuation)?;
+        profile.clock = clock_for(config.timezone.as_deref())?;
+        profile.encoding = config
+            .encoding
+            .as_deref()
+            .map(TextEncoding::parse)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("解析配置 '{}': {}", config.name, e))?;
+        Ok(profile)
+    }
+
+    /// Whether a raw line begins a new record for this profile
+    pub fn is_record_start(&self, line: &[u8]) -> bool {
+        !self.continuation.iter().any(|re| re.is_match(line)) && self.record_start.is_match(line)
+    }
+
+    /// Whether a decoded line matches this profile's format
+    pub fn matches(&self, line: &str) -> bool {
+        match &self.format {
+            LineFormat::Regex { re, .. } => re.is_match(line),
+            LineFormat::Json { .. } => parse_json_object(line).is_some(),
+            LineFormat::Logfmt { fields } => {
+                // Every token must be a pair, and at least one must be a known field key
+                parse_logfmt(line).is_some_and(|pairs| {
+                    pairs.len() >= 2
+                        && pairs.iter().all(|(_, v)| v.is_some())
+                        && pairs.iter().any(|(k, _)| {
+                            fields.iter().any(|(_, keys)| keys.iter().any(|key| key == k))
+                        })
+                })
+            }
+        }
+    }
+}
+
+fn compile_record_start(pattern: &str) -> Result<BytesRegex> {
+    BytesRegex::new(pattern).map_err(|e| anyhow::anyhow!("无效的起始行正则 '{}': {}", pattern, e))
+}
+
+fn compile_continuation(patterns: &[String]) -> Result<Vec<BytesRegex>> {
+    patterns
+        .iter()
+        .map(|p| BytesRegex::new(p).map_err(|e| anyhow::anyhow!("无效的续行正则 '{}': {}", p, e)))
+        .collect()
+}
+
+fn clock_for(timezone: Option<&str>) -> Result<ClockAdjust> {
+    Ok(ClockAdjust {
+        timezone: timezone.map(LogTimezone::parse).transpose()?.unwrap_or_default(),
+        ..ClockAdjust::default()
+    })
+}
+
+fn logfmt_record_start() -> BytesRegex {
+    BytesRegex::new(r"^[A-Za-z_][\w.\-]*=").unwrap()
+}
+
+/// Split a logfmt line into `(key, value)` pairs; bare keys have no value
+///
+/// Values may be double-quoted with `\"`, `\\` and `\n` escapes.
+/// Returns `None` for an unterminated quote or an empty key.
+pub fn parse_logfmt(line: &str) -> Option<Vec<(String, Option<String>)>> {
+    let mut pairs = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return None;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            pairs.push((key, None));
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        other => value.push(other),
+                    },
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        pairs.push((key, Some(value)));
+    }
+    Some(pairs)
+}
+
+fn json_record_start() -> BytesRegex {
+    BytesRegex::new(r"^\s*\{").unwrap()
+}
+
+fn parse_json_object(line: &str) -> Option<Value> {
+    let value: Value = serde_json::from_str(line.trim()).ok()?;
+    value.is_object().then_some(value)
+}
+
+/// Look up the first present key among `keys`; dotted keys descend into nested objects
+fn json_field<'v>(object: &'v Value, keys: &[String]) -> Option<&'v Value> {
+    keys.iter()
+        .find_map(|key| {
+            object
+                .get(key)
+                .or_else(|| key.split('.').try_fold(object, |v, part| v.get(part)))
+        })
+        .filter(|v| !v.is_null())
+}
+
+fn json_value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compile the default profile, every configured `[[parser.profiles]]` entry and the
+/// built-in `json` and `logfmt` profiles
+///
+/// A configured profile with a built-in's name replaces that built-in.
+pub fn build_profiles(config: &ParserConfig) -> Result<Vec<ParserProfile>> {
+    let mut profiles = Vec::new();
+    if !config.profiles.iter().any(|p| p.name == DEFAULT_PROFILE) {
+        profiles.push(ParserProfile::from_default_pattern(config)?);
+    }
+    for profile in &config.profiles {
+        profiles.push(ParserProfile::from_config(profile)?);
+    }
+    if !config.profiles.iter().any(|p| p.name == JSON_PROFILE) {
+        profiles.push(ParserProfile::default_json());
+    }
+    if !config.profiles.iter().any(|p| p.name == LOGFMT_PROFILE) {
+        profiles.push(ParserProfile::default_logfmt());
+    }
+    let continuation = compile_continuation(&config.continuation_patterns)?;
+    for profile in &mut profiles {
+        profile.continuation.extend(continuation.iter().cloned());
+    }
+    Ok(profiles)
+}
+
+/// Pick the profile matching the most of the first `sample_lines` non-empty lines
+///
+/// `data` comes from [`TextEncoding::line_bytes`]. Ties go to the earlier profile,
+/// so the default profile wins when nothing matches.
+pub fn detect_profile(
+    data: &[u8],
+    encoding: TextEncoding,
+    profiles: &[ParserProfile],
+    sample_lines: usize,
+) -> usize {
+    let mut scores = vec![0usize; profiles.len()];
+    for line in data
+        .split(|&b| b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .filter(|l| !l.is_empty())
+        .take(sample_lines)
+    {
+        let line = encoding.decode(line);
+        for (score, profile) in scores.iter_mut().zip(profiles) {
+            if profile.matches(&line) {
+                *score += 1;
+            }
+        }
+    }
+    let mut best = 0;
+    for (i, score) in scores.iter().enumerate() {
+        if *score > scores[best] {
+            best = i;
+        }
+    }
+    best
 }
 
 fn extract_json_from_bytes(line_bytes: &[u8]) -> Option<Value> {
@@ -23,87 +459,785 @@ fn extract_json_from_bytes(line_bytes: &[u8]) -> Option<Value> {
 pub fn parse_line(
     line: &str,
     line_bytes: &[u8],
-    re: &Regex,
+    profile: &ParserProfile,
+    store: &mut TextStore,
     source_id: usize,
     line_index: usize,
 ) -> Option<LogEntry> {
-    let caps = re.captures(line)?;
-    Some(LogEntry {
-        timestamp: caps.get(1)?.as_str().into(),
-        pid: caps.get(2)?.as_str().into(),
-        tid: caps.get(3)?.as_str().into(),
-        level: caps.get(4)?.as_str().into(),
-        content: caps.get(5)?.as_str().into(),
-        source_file: caps.get(6)?.as_str().into(),
-        line_num: caps.get(7)?.as_str().parse().ok()?,
-        json_payload: extract_json_from_bytes(line_bytes),
+    // A regex header is matched on the first line only: run over a whole merged record,
+    // `.*` would reach into the continuation lines and trailing groups (like the source
+    // location) would bind to the last stack frame. JSON objects may span lines.
+    let whole = match profile.format {
+        LineFormat::Regex { .. } if line.contains('\n') => None,
+        _ => parse_record(line, line_bytes, profile, store, source_id, line_index),
+    };
+    let mut entry = whole
+        .or_else(|| {
+            // Continuation lines are appended to the first line's content
+            let (first, rest) = line.split_once('\n')?;
+            let mut entry = parse_record(first, line_bytes, profile, store, source_id, line_index)?;
+            let content_end = entry.content.as_ptr() as usize + entry.content.len();
+            entry.content = if !entry.content.is_empty()
+                && content_end == first.as_ptr() as usize + first.len()
+            {
+                // Content running to the end of the first line stays one slice with the rest
+                store.text(&line[first.len() - entry.content.len()..])
+            } else {
+                format!("{}\n{}", entry.content, rest).into()
+            };
+            Some(entry)
+        })?;
+    entry.time = profile
+        .clock
+        .parse(&entry.timestamp, profile.timestamp_format.as_deref());
+    Some(entry)
+}
+
+fn parse_record(
+    line: &str,
+    line_bytes: &[u8],
+    profile: &ParserProfile,
+    store: &mut TextStore,
+    source_id: usize,
+    line_index: usize,
+) -> Option<LogEntry> {
+    match &profile.format {
+        LineFormat::Regex { re, fields } => {
+            let caps = re.captures(line)?;
+            let group = |field: EntryField| -> Option<&str> {
+                let (_, group) = fields.iter().find(|(f, _)| *f == field)?;
+                match group {
+                    GroupRef::Index(i) => caps.get(*i),
+                    GroupRef::Name(n) => caps.name(n),
+                }
+                .map(|m| m.as_str())
+            };
+            let field = |f: EntryField| group(f).unwrap_or_default();
+            let line_num = match group(EntryField::LineNum) {
+                Some(n) => n.parse().ok()?,
+                None => 0,
+            };
+            Some(LogEntry {
+                timestamp: store.text(field(EntryField::Timestamp)),
+                time: None,
+                pid: store.intern(field(EntryField::Pid)),
+                tid: store.intern(field(EntryField::Tid)),
+                level: store.intern(field(EntryField::Level)),
+                content: store.text(field(EntryField::Content)),
+                source_file: store.intern(field(EntryField::SourceFile)),
+                line_num,
+                json_payload: extract_json_from_bytes(line_bytes).map(Box::new),
+                delta_ms: None,
+                source_id,
+                line_index,
+                kind: EntryKind::Record,
+            })
+        }
+        LineFormat::Json { fields } => Some(entry_from_object(
+            parse_json_object(line)?,
+            fields,
+            false,
+            store,
+            source_id,
+            line_index,
+        )),
+        // A logfmt record is a single line
+        LineFormat::Logfmt { .. } if line.contains('\n') => None,
+        LineFormat::Logfmt { fields } => {
+            let object = parse_logfmt(line)?
+                .into_iter()
+                .map(|(k, v)| (k, v.map(Value::String).unwrap_or(Value::Bool(true))))
+                .collect();
+            Some(entry_from_object(
+                Value::Object(object),
+                fields,
+                true,
+                store,
+                source_id,
+                line_index,
+            ))
+        }
+    }
+}
+
+/// Build a `LogEntry` from a key/value object using the profile's key candidates
+///
+/// With `strip_used`, keys consumed by entry fields are removed so the payload holds
+/// only the remaining pairs.
+fn entry_from_object(
+    mut object: Value,
+    fields: &[(EntryField, Vec<String>)],
+    strip_used: bool,
+    store: &mut TextStore,
+    source_id: usize,
+    line_index: usize,
+) -> LogEntry {
+    let mut used = Vec::new();
+    let mut field = |f: EntryField| {
+        let keys = fields
+            .iter()
+            .find(|(mapped, _)| *mapped == f)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default();
+        let value = json_field(&object, keys).map(json_value_to_string);
+        if let Some(key) = keys.iter().find(|k| object.get(k.as_str()).is_some()) {
+            used.push(key.clone());
+        }
+        value.unwrap_or_default()
+    };
+    let timestamp = field(EntryField::Timestamp);
+    let pid = field(EntryField::Pid);
+    let tid = field(EntryField::Tid);
+    let level = field(EntryField::Level);
+    let content = field(EntryField::Content);
+    let mut source_file = field(EntryField::SourceFile);
+    let mut line_num = field(EntryField::LineNum).parse().unwrap_or(0);
+    // Callers like "main.go:42" carry the line number themselves
+    if line_num == 0 {
+        if let Some((file, num)) = source_file.rsplit_once(':') {
+            if let Ok(num) = num.parse() {
+                line_num = num;
+                source_file = file.to_string();
+            }
+        }
+    }
+    if strip_used {
+        if let Value::Object(map) = &mut object {
+            for key in &used {
+                map.remove(key);
+            }
+        }
+    }
+    LogEntry {
+        timestamp: timestamp.into(),
+        time: None,
+        pid: store.intern(&pid),
+        tid: store.intern(&tid),
+        level: store.intern(&level),
+        content: content.into(),
+        source_file: store.intern(&source_file),
+        line_num,
+        json_payload: Some(Box::new(object)),
         delta_ms: None,
         source_id,
         line_index,
-    })
+        kind: EntryKind::Record,
+    }
 }
 
-pub fn merge_multiline_bytes(data: &[u8]) -> Vec<Vec<u8>> {
-    let ts_re = BytesRegex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d+").unwrap();
-    let mut merged = Vec::new();
-    let mut current = Vec::new();
-    for line in data.split(|&b| b == b'\n') {
-        let line = if line.last() == Some(&b'\r') {
-            &line[..line.len() - 1]
-        } else {
-            line
-        };
-        if ts_re.is_match(line) {
-            if !current.is_empty() {
-                merged.push(current);
+/// Keep a record the profile can't parse as raw text, so format drift shows up
+/// instead of losing lines
+fn unparsed_entry(
+    record: &str,
+    store: &mut TextStore,
+    source_id: usize,
+    line_index: usize,
+) -> LogEntry {
+    let empty = store.intern("");
+    LogEntry {
+        timestamp: LazyText::default(),
+        time: None,
+        pid: empty.clone(),
+        tid: empty.clone(),
+        level: empty.clone(),
+        content: store.text(record),
+        source_file: empty,
+        line_num: 0,
+        json_payload: None,
+        delta_ms: None,
+        source_id,
+        line_index,
+        kind: EntryKind::Unparsed,
+    }
+}
+
+/// Why a record doesn't parse with a profile, shown in the diagnostics popup
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseDiagnosis {
+    /// Capture groups that matched before the failure, as (group, text)
+    pub matched: Vec<(String, String)>,
+    pub problem: String,
+}
+
+/// Find where `record` stops matching `profile`
+///
+/// For regex profiles the pattern is cut after each top-level capture group in turn;
+/// the first cut that no longer matches names the failing group. Only the record's
+/// first line is examined, as that is what has to match.
+pub fn diagnose(record: &str, profile: &ParserProfile) -> ParseDiagnosis {
+    let line = record.lines().next().unwrap_or_default();
+    let problem = |problem: &str| ParseDiagnosis {
+        matched: vec![],
+        problem: problem.to_string(),
+    };
+    match &profile.format {
+        LineFormat::Regex { re, fields } => {
+            let group_name = |index: usize| {
+                re.capture_names()
+                    .nth(index)
+                    .flatten()
+                    .map_or_else(|| format!("#{}", index), |n| format!("#{} ({})", index, n))
+            };
+            if let Some(caps) = re.captures(line) {
+                let line_num = fields.iter().find(|(f, _)| *f == EntryField::LineNum);
+                let value = line_num.and_then(|(_, group)| match group {
+                    GroupRef::Index(i) => caps.get(*i),
+                    GroupRef::Name(n) => caps.name(n),
+                });
+                return match value {
+                    Some(v) if v.as_str().parse::<u32>().is_err() => problem(&format!(
+                        "行号字段的值 {:?} 不是有效的数字",
+                        v.as_str()
+                    )),
+                    _ => problem("首行可以完整匹配，记录本身应能解析"),
+                };
+            }
+            let pattern = re.as_str();
+            let mut matched = Vec::new();
+            for (index, end) in top_level_groups(pattern) {
+                let Ok(prefix) = Regex::new(&pattern[..end]) else {
+                    break;
+                };
+                match prefix.captures(line) {
+                    Some(caps) => {
+                        let text = caps.get(index).map_or("", |m| m.as_str());
+                        matched.push((group_name(index), text.to_string()));
+                    }
+                    None => {
+                        return ParseDiagnosis {
+                            matched,
+                            problem: format!("捕获组 {} 不匹配", group_name(index)),
+                        };
+                    }
+                }
+            }
+            ParseDiagnosis {
+                problem: if matched.is_empty() {
+                    "正则表达式不匹配".to_string()
+                } else {
+                    "各捕获组均匹配，但之后的部分不匹配".to_string()
+                },
+                matched,
             }
-            current = line.to_vec();
-        } else if !line.is_empty() {
-            current.extend_from_slice(line);
         }
+        LineFormat::Json { .. } => problem("不是 JSON 对象"),
+        LineFormat::Logfmt { .. } if record.contains('\n') => {
+            problem("logfmt 记录只能占一行")
+        }
+        LineFormat::Logfmt { .. } => problem("不是 key=value 格式"),
     }
-    if !current.is_empty() {
-        merged.push(current);
-    }
-    merged
 }
 
-pub fn calculate_deltas(entries: &mut [LogEntry]) {
-    let mut last_time: HashMap<String, NaiveDateTime> = HashMap::new();
-    for entry in entries.iter_mut() {
-        if let Some(ts) = parse_timestamp(&entry.timestamp) {
-            if let Some(prev) = last_time.get(&entry.tid) {
-                let delta = ts.signed_duration_since(*prev).num_milliseconds();
-                if delta > 0 {
-                    entry.delta_ms = Some(delta);
+/// End offset of each group of `pattern` not nested in another group, with the index
+/// of the first capture group it holds (itself when capturing); groups without
+/// captures are skipped
+fn top_level_groups(pattern: &str) -> Vec<(usize, usize)> {
+    let bytes = pattern.as_bytes();
+    let mut groups = Vec::new();
+    // One element per open group; the outermost one tracks its first capture
+    let mut open: Vec<Option<usize>> = Vec::new();
+    let mut captures = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' => {
+                // Skip the class, including nested classes and a leading `]`
+                let mut depth = 1;
+                i += 1;
+                if bytes.get(i) == Some(&b'^') {
+                    i += 1;
+                }
+                if bytes.get(i) == Some(&b']') {
+                    i += 1;
+                }
+                while i < bytes.len() && depth > 0 {
+                    match bytes[i] {
+                        b'\\' => i += 1,
+                        b'[' => depth += 1,
+                        b']' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
                 }
+                continue;
             }
-            last_time.insert(entry.tid.clone(), ts);
+            b'(' => {
+                let rest = &pattern[i + 1..];
+                if !rest.starts_with('?') || rest.starts_with("?P<") || rest.starts_with("?<") {
+                    captures += 1;
+                    if let Some(outer) = open.first_mut() {
+                        outer.get_or_insert(captures);
+                    }
+                    open.push(Some(captures));
+                } else {
+                    open.push(None);
+                }
+            }
+            b')' => {
+                if let Some(Some(index)) = open.pop() {
+                    if open.is_empty() {
+                        groups.push((index, i + 1));
+                    }
+                }
+            }
+            _ => {}
         }
+        i += 1;
     }
+    groups
 }
 
-pub fn build_histogram(entries: &[LogEntry]) -> Vec<(String, u64)> {
-    if entries.is_empty() {
-        return vec![];
+/// Group raw lines into records, each a slice of `data` spanning its continuation lines
+///
+/// Line breaks inside a record are kept; [`parse_records`] normalises `\r\n`.
+pub fn merge_multiline_bytes<'a>(data: &'a [u8], profile: &ParserProfile) -> Vec<&'a [u8]> {
+    let mut merged = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for raw in data.split(|&b| b == b'\n') {
+        let start = offset;
+        offset += raw.len() + 1;
+        let line = raw.strip_suffix(b"\r").unwrap_or(raw);
+        let end = start + line.len();
+        if profile.is_record_start(line) {
+            if let Some((s, e)) = current.take() {
+                merged.push(&data[s..e]);
+            }
+            current = Some((start, end));
+        } else if !line.is_empty() {
+            match &mut current {
+                Some((_, e)) => *e = end,
+                None => current = Some((start, end)),
+            }
+        }
     }
-    let mut counts: HashMap<String, u64> = HashMap::new();
-    for entry in entries {
-        if let Some(ts) = parse_timestamp(&entry.timestamp) {
-            let key = ts.format("%m-%d %H:00").to_string();
-            *counts.entry(key).or_insert(0) += 1;
+    if let Some((s, e)) = current {
+        merged.push(&data[s..e]);
+    }
+    merged
+}
+
+/// Split `data` into about `chunks` pieces, each beginning at a record start, so
+/// they can be parsed independently
+pub fn split_at_records<'a>(data: &'a [u8], profile: &ParserProfile, chunks: usize) -> Vec<&'a [u8]> {
+    let target = (data.len() / chunks.max(1)).max(1);
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let mut pos = (start + target).min(data.len());
+        while pos < data.len() {
+            // Move to the start of the next line and stop there if it begins a record
+            pos = match data[pos..].iter().position(|&b| b == b'\n') {
+                Some(nl) => pos + nl + 1,
+                None => data.len(),
+            };
+            let line_end = data[pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len(), |n| pos + n);
+            let line = &data[pos..line_end];
+            if profile.is_record_start(line.strip_suffix(b"\r").unwrap_or(line)) {
+                break;
+            }
         }
+        pieces.push(&data[start..pos]);
+        start = pos;
     }
-    let mut sorted: Vec<_> = counts.into_iter().collect();
-    sorted.sort_by(|a, b| a.0.cmp(&b.0));
-    sorted
+    pieces
 }
 
-pub fn decode_line(bytes: &[u8]) -> String {
-    let (decoded, _, _) = GB18030.decode(bytes);
-    decoded.into_owned()
+/// Merge, decode and parse the records in `data` (from [`TextEncoding::line_bytes`])
+///
+/// When `data` lies inside `source`, entry text points into it instead of being
+/// copied. Records matching an `ignore` pattern are skipped. Returns the number of
+/// records seen, which callers use to offset `line_index` of later chunks.
+pub fn parse_records(
+    data: &[u8],
+    profile: &ParserProfile,
+    encoding: TextEncoding,
+    source: Option<&Arc<SourceBytes>>,
+    source_id: usize,
+    base_line_index: usize,
+    ignore: &[Regex],
+) -> (usize, Vec<LogEntry>) {
+    let records = merge_multiline_bytes(data, profile);
+    let mut store = TextStore::new(source);
+    let entries = records
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| {
+            let mut line = encoding.decode(b);
+            if line.contains('\r') {
+                line = line.replace("\r\n", "\n").into();
+            }
+            if ignore.iter().any(|ig| ig.is_match(&line)) {
+                return None;
+            }
+            let line_index = base_line_index + i + 1;
+            Some(
+                parse_line(&line, b, profile, &mut store, source_id, line_index)
+                    .unwrap_or_else(|| unparsed_entry(&line, &mut store, source_id, line_index)),
+            )
+        })
+        .collect();
+    (records.len(), entries)
 }
 
+/// Last timestamp seen per thread, so `delta_ms` carries on across batches of
+/// live-tailed entries
+#[derive(Debug, Default)]
+pub struct DeltaTracker {
+    last_time: HashMap<Arc<str>, NaiveDateTime>,
+}
+
+impl DeltaTracker {
+    pub fn apply(&mut self, entries: &mut [LogEntry]) {
+        for entry in entries.iter_mut() {
+            if let Some(ts) = entry.time {
+                if let Some(prev) = self.last_time.get(&entry.tid) {
+                    let delta = ts.signed_duration_since(*prev).num_milliseconds();
+                    if delta > 0 {
+                        entry.delta_ms = Some(delta);
+                    }
+                }
+                self.last_time.insert(entry.tid.clone(), ts);
+            }
+        }
+    }
+}
+
+/// Compile `parser.log_pattern`, which is matched against the first line of a record
 pub fn create_log_regex(config: &ParserConfig) -> Result<Regex> {
     Regex::new(&config.log_pattern).map_err(|e| anyhow::anyhow!("无效的日志正则表达式: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::LazyText;
+
+    fn nginx_profile() -> ProfileConfig {
+        let mut fields = HashMap::new();
+        fields.insert("timestamp".to_string(), "time".to_string());
+        fields.insert("content".to_string(), "request".to_string());
+        fields.insert("tid".to_string(), "ip".to_string());
+        ProfileConfig {
+            name: "nginx".into(),
+            format: ProfileFormat::Regex,
+            pattern: r#"^(?P<ip>\S+) \S+ \S+ \[(?P<time>[^\]]+)\] "(?P<request>[^"]*)""#.into(),
+            fields,
+            ..ProfileConfig::default()
+        }
+    }
+
+    fn config_with(profiles: Vec<ProfileConfig>) -> ParserConfig {
+        ParserConfig {
+            profiles,
+            ..ParserConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_default_profile_positional_groups() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let line = "2024-01-15 10:00:00.123[1a2b:3c4d][Info]: hello (main.cpp:42)";
+        let entry = parse_line(line, line.as_bytes(), &profiles[0], &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(&*entry.tid, "3c4d");
+        assert_eq!(entry.content, "hello ");
+        assert_eq!(entry.line_num, 42);
+        assert_eq!(
+            entry.time.unwrap().format("%H:%M:%S%.3f").to_string(),
+            "10:00:00.123"
+        );
+    }
+
+    #[test]
+    fn test_configured_timestamp_format() {
+        let config = ParserConfig {
+            log_pattern: r"^(\d{2}\.\d{2}\.\d{4} \d{2}:\d{2}:\d{2}) \[(\d+):(\d+)\]\[(\w+)\]: (.*?) \((.+):(\d+)\)$".into(),
+            timestamp_format: "%d.%m.%Y %H:%M:%S".into(),
+            ..ParserConfig::default()
+        };
+        let profiles = build_profiles(&config).unwrap();
+        assert!(profiles[0].is_record_start(b"15.01.2024 10:00:00 [1:2][Info]: a (x.rs:1)"));
+        assert!(!profiles[0].is_record_start(b"2024-01-15 10:00:00.123 continuation"));
+        let line = "15.01.2024 10:00:00 [1:2][Info]: a (x.rs:1)";
+        let entry = parse_line(line, line.as_bytes(), &profiles[0], &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(
+            entry.time.unwrap().format("%Y-%m-%d %H:%M").to_string(),
+            "2024-01-15 10:00"
+        );
+    }
+
+    #[test]
+    fn test_named_group_mapping() {
+        let profiles = build_profiles(&config_with(vec![nginx_profile()])).unwrap();
+        let line = r#"10.0.0.1 - - [15/Jan/2024:10:00:00 +0000] "GET /api HTTP/1.1" 200"#;
+        let entry = parse_line(line, line.as_bytes(), &profiles[1], &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(entry.timestamp, "15/Jan/2024:10:00:00 +0000");
+        assert_eq!(entry.content, "GET /api HTTP/1.1");
+        assert_eq!(&*entry.tid, "10.0.0.1");
+        assert_eq!(&*entry.level, "");
+        assert_eq!(entry.line_num, 0);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let mut profile = nginx_profile();
+        profile.fields.insert("bogus".into(), "ip".into());
+        assert!(build_profiles(&config_with(vec![profile])).is_err());
+    }
+
+    #[test]
+    fn test_detect_profile() {
+        let profiles = build_profiles(&config_with(vec![nginx_profile()])).unwrap();
+        let nginx = b"10.0.0.1 - - [15/Jan/2024:10:00:00 +0000] \"GET / HTTP/1.1\" 200\n\
+                      10.0.0.2 - - [15/Jan/2024:10:00:01 +0000] \"GET /a HTTP/1.1\" 404\n";
+        assert_eq!(detect_profile(nginx, TextEncoding::Utf8, &profiles, 10), 1);
+
+        let native = b"2024-01-15 10:00:00.123[1a2b:3c4d][Info]: hello (main.cpp:42)\n";
+        assert_eq!(detect_profile(native, TextEncoding::Utf8, &profiles, 10), 0);
+
+        // Nothing matches: fall back to the default profile
+        assert_eq!(detect_profile(b"garbage\n", TextEncoding::Utf8, &profiles, 10), 0);
+    }
+
+    #[test]
+    fn test_json_lines_default_keys() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let json = profiles.iter().find(|p| p.name == JSON_PROFILE).unwrap();
+        let line = r#"{"ts":"2024-01-15T10:00:00Z","level":"error","msg":"boom","thread":7,"caller":"main.go:42","user":{"id":1}}"#;
+        let entry = parse_line(line, line.as_bytes(), json, &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(entry.timestamp, "2024-01-15T10:00:00Z");
+        assert_eq!(&*entry.level, "error");
+        assert_eq!(entry.content, "boom");
+        assert_eq!(&*entry.tid, "7");
+        assert_eq!(&*entry.source_file, "main.go");
+        assert_eq!(entry.line_num, 42);
+        assert_eq!(entry.json_payload.unwrap()["user"]["id"], 1);
+    }
+
+    #[test]
+    fn test_json_lines_configured_keys() {
+        let mut fields = HashMap::new();
+        fields.insert("content".to_string(), "event.text".to_string());
+        let config = config_with(vec![ProfileConfig {
+            name: "svc".into(),
+            format: ProfileFormat::Json,
+            fields,
+            ..ProfileConfig::default()
+        }]);
+        let profiles = build_profiles(&config).unwrap();
+        let line = r#"{"level":"info","event":{"text":"nested"}}"#;
+        let entry = parse_line(line, line.as_bytes(), &profiles[1], &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(entry.content, "nested");
+        assert_eq!(&*entry.level, "info");
+        assert!(parse_line("not json", b"not json", &profiles[1], &mut TextStore::new(None), 0, 1).is_none());
+    }
+
+    #[test]
+    fn test_detect_json_lines() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let data = b"{\"msg\":\"a\"}\n{\"msg\":\"b\"}\n";
+        assert_eq!(profiles[detect_profile(data, TextEncoding::Utf8, &profiles, 10)].name, JSON_PROFILE);
+    }
+
+    #[test]
+    fn test_logfmt_standard_and_extra_keys() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let logfmt = profiles.iter().find(|p| p.name == LOGFMT_PROFILE).unwrap();
+        let line = r#"time=2024-01-15T10:00:00Z level=warn msg="disk \"sda\" slow" caller=disk.go:12 dev=sda latency=120ms"#;
+        let entry = parse_line(line, line.as_bytes(), logfmt, &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(entry.timestamp, "2024-01-15T10:00:00Z");
+        assert_eq!(&*entry.level, "warn");
+        assert_eq!(entry.content, r#"disk "sda" slow"#);
+        assert_eq!(&*entry.source_file, "disk.go");
+        assert_eq!(entry.line_num, 12);
+        let payload = entry.json_payload.unwrap();
+        assert_eq!(payload["dev"], "sda");
+        assert_eq!(payload["latency"], "120ms");
+        assert!(payload.get("msg").is_none());
+    }
+
+    #[test]
+    fn test_logfmt_tokenizer_edge_cases() {
+        assert_eq!(
+            parse_logfmt(r#"a=1 flag b="x y""#).unwrap(),
+            vec![
+                ("a".to_string(), Some("1".to_string())),
+                ("flag".to_string(), None),
+                ("b".to_string(), Some("x y".to_string())),
+            ]
+        );
+        assert!(parse_logfmt(r#"msg="unterminated"#).is_none());
+        assert_eq!(parse_logfmt("empty=").unwrap(), vec![("empty".to_string(), Some(String::new()))]);
+    }
+
+    #[test]
+    fn test_detect_logfmt() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let data = b"level=info msg=start
+level=error msg=\"failed to bind\" port=80\n";
+        assert_eq!(profiles[detect_profile(data, TextEncoding::Utf8, &profiles, 10)].name, LOGFMT_PROFILE);
+        // Plain prose with a stray '=' is not logfmt
+        let logfmt = profiles.iter().find(|p| p.name == LOGFMT_PROFILE).unwrap();
+        assert!(!logfmt.matches("retry count=3 exceeded"));
+    }
+
+    #[test]
+    fn test_merge_keeps_stack_trace_lines() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let data = b"2024-01-15 10:00:00.123[1:2][Error]: boom (a.cpp:1)\r\n\
+java.lang.IllegalStateException: bad\n\
+\tat com.example.Foo.run(Foo.java:10)\n\
+Caused by: java.io.IOException\n\
+2024-01-15 10:00:01.000[1:2][Info]: next (a.cpp:2)\n";
+        let records = merge_multiline_bytes(data, &profiles[0]);
+        assert_eq!(records.len(), 2);
+        let first = TextEncoding::Utf8.decode(records[0]);
+        assert_eq!(first.lines().count(), 4);
+        let entry = parse_line(&first, records[0], &profiles[0], &mut TextStore::new(None), 0, 1).unwrap();
+        assert_eq!(&*entry.level, "Error");
+        assert!(entry.content.starts_with("boom"));
+        assert!(entry.content.ends_with("Caused by: java.io.IOException"));
+    }
+
+    #[test]
+    fn test_trace_ending_in_frame_keeps_header_location() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let record = "2024-01-15 10:00:00.123[1:2][Error]: boom (a.cpp:1)\n\
+java.lang.IllegalStateException: bad\n\
+\tat com.example.Foo.run(Foo.java:10)";
+        let entry =
+            parse_line(record, record.as_bytes(), &profiles[0], &mut TextStore::new(None), 0, 1)
+                .unwrap();
+        assert_eq!(&*entry.source_file, "a.cpp");
+        assert_eq!(entry.line_num, 1);
+        assert_eq!(
+            entry.content,
+            "boom \njava.lang.IllegalStateException: bad\n\tat com.example.Foo.run(Foo.java:10)"
+        );
+    }
+
+    #[test]
+    fn test_continuation_overrides_record_start() {
+        let config = ParserConfig {
+            record_start: Some(r"^\S".into()),
+            continuation_patterns: vec![r"^\}".into()],
+            ..ParserConfig::default()
+        };
+        let profiles = build_profiles(&config).unwrap();
+        let records = merge_multiline_bytes(b"start {\n  \"a\": 1\n}\nnext\n", &profiles[0]);
+        assert_eq!(records, vec![&b"start {\n  \"a\": 1\n}"[..], &b"next"[..]]);
+        let bad = ParserConfig {
+            continuation_patterns: vec!["(".into()],
+            ..ParserConfig::default()
+        };
+        assert!(build_profiles(&bad).is_err());
+    }
+
+    #[test]
+    fn test_chunked_parse_matches_sequential() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let mut data = Vec::new();
+        for i in 0..50 {
+            data.extend_from_slice(
+                format!("2024-01-15 10:00:{:02}.000[1:2][Info]: msg {} (a.cpp:{})\n  detail\n", i, i, i)
+                    .as_bytes(),
+            );
+        }
+        let chunks = split_at_records(&data, &profiles[0], 7);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), data);
+        let (count, sequential) = parse_records(&data, &profiles[0], TextEncoding::Utf8, None, 0, 0, &[]);
+        assert_eq!(count, 50);
+        let mut chunked = Vec::new();
+        for chunk in chunks {
+            let (n, entries) = parse_records(chunk, &profiles[0], TextEncoding::Utf8, None, 0, chunked.len(), &[]);
+            assert_eq!(n, entries.len());
+            chunked.extend(entries);
+        }
+        let key = |e: &LogEntry| (e.line_index, e.content.to_string());
+        assert_eq!(
+            chunked.iter().map(key).collect::<Vec<_>>(),
+            sequential.iter().map(key).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_entries_point_into_source() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let source = Arc::new(SourceBytes::new(
+            b"2024-01-15 10:00:00.000[1:2][Error]: boom (a.cpp:1)\n\tat Foo.run\n\
+2024-01-15 10:00:01.000[1:2][Info]: ok (a.cpp:2)\n"
+                .to_vec(),
+        ));
+        let (_, entries) =
+            parse_records(&source, &profiles[0], TextEncoding::Utf8, Some(&source), 0, 0, &[]);
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[1].content, LazyText::Slice { .. }));
+        assert_eq!(entries[1].content, "ok ");
+        assert_eq!(entries[0].content, "boom \n\tat Foo.run");
+        assert!(Arc::ptr_eq(&entries[0].tid, &entries[1].tid));
+
+        // Content ending its first line stays a slice when the trailer is appended
+        let plain = build_profiles(&config_with(vec![ProfileConfig {
+            name: "plain".into(),
+            pattern: r"^(?P<timestamp>\S+ \S+) (?P<level>\w+) (?P<content>[^\n]*)$".into(),
+            ..ProfileConfig::default()
+        }]))
+        .unwrap();
+        let source = Arc::new(SourceBytes::new(
+            b"2024-01-15 10:00:00 ERROR boom\n\tat Foo.run\n".to_vec(),
+        ));
+        let (_, entries) =
+            parse_records(&source, &plain[1], TextEncoding::Utf8, Some(&source), 0, 0, &[]);
+        assert!(matches!(entries[0].content, LazyText::Slice { .. }));
+        assert_eq!(entries[0].content, "boom\n\tat Foo.run");
+    }
+
+    #[test]
+    fn test_unparsed_records_are_kept() {
+        let profiles = build_profiles(&ParserConfig::default()).unwrap();
+        let data = b"2024-01-15 10:00:00.000[1:2][Info]: ok (a.cpp:1)\n\
+2024-01-15 10:00:01.000[1:2][Info]: line number too big (a.cpp:99999999999)\n";
+        let (count, entries) = parse_records(data, &profiles[0], TextEncoding::Utf8, None, 0, 0, &[]);
+        assert_eq!((count, entries.len()), (2, 2));
+        assert_eq!(entries[0].kind, EntryKind::Record);
+        assert_eq!(entries[1].kind, EntryKind::Unparsed);
+        assert_eq!(entries[1].line_index, 2);
+        assert!(entries[1].content.ends_with("(a.cpp:99999999999)"));
+
+        let diagnosis = diagnose(&entries[1].content, &profiles[0]);
+        assert!(diagnosis.problem.contains("\"99999999999\""), "{}", diagnosis.problem);
+    }
+
+    #[test]
+    fn test_diagnose_names_failing_group() {
+        let profiles = build_profiles(&config_with(vec![ProfileConfig {
+            name: "plain".into(),
+            pattern: r"^(?P<timestamp>\d{4}-\d{2}-\d{2}) (?:\[(?P<level>[A-Z]+)\]) (?P<content>.*)$"
+                .into(),
+            ..ProfileConfig::default()
+        }]))
+        .unwrap();
+        let diagnosis = diagnose("2024-01-15 [warn] lowercase level", &profiles[1]);
+        assert_eq!(diagnosis.matched, vec![("#1 (timestamp)".to_string(), "2024-01-15".to_string())]);
+        assert_eq!(diagnosis.problem, "捕获组 #2 (level) 不匹配");
+
+        let diagnosis = diagnose("garbage", &profiles[1]);
+        assert!(diagnosis.matched.is_empty());
+        assert_eq!(diagnosis.problem, "捕获组 #1 (timestamp) 不匹配");
+    }
+
+    #[test]
+    fn test_top_level_groups_skip_classes_and_escapes() {
+        let pattern = r"^(a)(?:b(c))\((d[()\]])(?P<e>x)";
+        let groups: Vec<_> = top_level_groups(pattern).into_iter().map(|(i, _)| i).collect();
+        // (?:b(c)) is reported as its capture; `\(` is a literal
+        assert_eq!(groups, vec![1, 2, 3, 4]);
+        let (_, end) = top_level_groups(pattern)[2];
+        assert_eq!(&pattern[..end], r"^(a)(?:b(c))\((d[()\]])");
+    }
+}
@@ -0,0 +1,188 @@
+//! Incremental processing of parsed entries
+//!
+//! Loaded files and batches that arrive while tailing go through the same steps:
+//! per-thread `delta_ms`, dashboard stats, the hourly histogram and noise folding.
+//! The state each step needs is kept here, so a batch carries on where the previous
+//! one ended.
+
+use crate::analytics::{StatsCounter, TimeHistogram};
+use crate::config::FiltersConfig;
+use crate::entry_list::EntryList;
+use crate::logic::{apply_folds, find_folds, FoldKind};
+use crate::models::{DashboardStats, DisplayEntry, LogEntry, TimeDisplay};
+use crate::parser::DeltaTracker;
+
+#[derive(Default)]
+pub struct Pipeline {
+    filters: FiltersConfig,
+    deltas: DeltaTracker,
+    stats: StatsCounter,
+    histogram: TimeHistogram,
+    /// Entries processed so far, the base of fold ranges
+    processed: usize,
+    /// Kind and size of the fold the list ends with, which later entries may extend
+    open_fold: Option<(FoldKind, usize)>,
+}
+
+impl Pipeline {
+    pub fn new(filters: FiltersConfig) -> Self {
+        Self {
+            filters,
+            ..Self::default()
+        }
+    }
+
+    pub fn stats(&self, display: TimeDisplay) -> DashboardStats {
+        self.stats.stats(display)
+    }
+
+    /// Entries per hour of `display`'s zone, for the timeline
+    pub fn histogram(&self, display: TimeDisplay) -> Vec<(String, u64)> {
+        self.histogram.hourly(display)
+    }
+
+    /// Process `entries` and append them to `list`, folded
+    ///
+    /// A run of noise may span batches, so entries already at the end of `list` can be
+    /// folded together with new ones. Returns the index of the first entry of `list`
+    /// that was replaced or added; everything from there on is new.
+    pub fn process(
+        &mut self,
+        list: &mut EntryList,
+        mut entries: Vec<LogEntry>,
+    ) -> usize {
+        self.deltas.apply(&mut entries);
+        self.stats.add_all(&entries);
+        self.histogram.add_all(&entries);
+
+        let mut changed = list.len();
+        self.processed += entries.len();
+
+        // Extend the fold the list ends with while the new entries continue its run
+        if let Some((kind, count)) = &mut self.open_fold {
+            let run = entries
+                .iter()
+                .take_while(|e| kind.matches(e, &self.filters))
+                .count();
+            if run > 0 {
+                *count += run;
+                if let Some(DisplayEntry::Folded {
+                    end_index,
+                    count: shown,
+                    summary_text,
+                    ..
+                }) = list.last_mut()
+                {
+                    *end_index += run;
+                    *shown = *count;
+                    *summary_text = kind.summary(*count, &self.filters);
+                }
+                changed -= 1;
+                entries.drain(..run);
+            }
+            if entries.is_empty() {
+                return changed;
+            }
+            self.open_fold = None;
+        }
+
+        // Entries at the end of the list may still start a run with the new ones
+        let lookback = self.filters.fold_threshold.max(5) - 1;
+        let tail_len = list
+            .iter()
+            .rev()
+            .take(lookback)
+            .take_while(|e| matches!(e, DisplayEntry::Normal(_)))
+            .count();
+        let tail_start = list.len() - tail_len;
+        let mut logs: Vec<LogEntry> = list
+            .iter_from(tail_start)
+            .filter_map(|e| match e {
+                DisplayEntry::Normal(log) => Some(log.clone()),
+                DisplayEntry::Folded { .. } => None,
+            })
+            .collect();
+        let new_count = entries.len();
+        logs.append(&mut entries);
+
+        let mut folds = find_folds(&logs, &self.filters);
+        let cut = folds.first().map_or(tail_len, |f| f.start.min(tail_len));
+        logs.drain(..cut);
+        for fold in &mut folds {
+            fold.start -= cut;
+            fold.end -= cut;
+        }
+        self.open_fold = folds
+            .last()
+            .filter(|f| f.end == logs.len())
+            .map(|f| (f.kind.clone(), f.end - f.start));
+
+        list.truncate(tail_start + cut);
+        // Position of the first entry being (re)placed among all processed entries
+        let logs_base = self.processed - new_count - tail_len + cut;
+        list.extend(apply_folds(logs, &folds, logs_base, &self.filters));
+        changed.min(tail_start + cut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EntryKind;
+
+    fn entry(content: &str) -> LogEntry {
+        LogEntry {
+            timestamp: "".into(),
+            time: None,
+            pid: "".into(),
+            tid: "".into(),
+            level: "Info".into(),
+            content: content.into(),
+            source_file: "".into(),
+            line_num: 0,
+            json_payload: None,
+            delta_ms: None,
+            source_id: 0,
+            line_index: 0,
+            kind: EntryKind::Record,
+        }
+    }
+
+    fn shown(list: &EntryList) -> Vec<String> {
+        list.iter()
+            .map(|e| match e {
+                DisplayEntry::Normal(log) => log.content.to_string(),
+                DisplayEntry::Folded { summary_text, .. } => summary_text.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_runs_fold_across_batches() {
+        let filters = FiltersConfig {
+            fold_rules: vec![],
+            ..FiltersConfig::default()
+        };
+        let mut pipeline = Pipeline::new(filters);
+        let mut list = EntryList::default();
+
+        let batch = vec![entry("start"), entry("poll"), entry("poll")];
+        assert_eq!(pipeline.process(&mut list, batch), 0);
+        assert_eq!(shown(&list), vec!["start", "poll", "poll"]);
+
+        // Five identical in a row across two batches fold into one summary
+        let batch = vec![entry("poll"), entry("poll"), entry("poll")];
+        assert_eq!(pipeline.process(&mut list, batch), 1);
+        assert_eq!(shown(&list), vec!["start", "Folded 5 identical"]);
+
+        // The open fold keeps growing, then normal entries follow it
+        let batch = vec![entry("poll"), entry("done")];
+        assert_eq!(pipeline.process(&mut list, batch), 1);
+        assert_eq!(shown(&list), vec!["start", "Folded 6 identical", "done"]);
+        assert!(matches!(
+            list[1],
+            DisplayEntry::Folded { start_index: 1, end_index: 6, count: 6, .. }
+        ));
+        assert_eq!(pipeline.stats(TimeDisplay::Local).total_logs, 8);
+    }
+}
@@ -1,11 +1,11 @@
 use std::collections::HashMap;
-use std::fs;
 use std::path::PathBuf;
 
-use chrono::{Local, NaiveDateTime};
+use chrono::Local;
 use serde::{Deserialize, Serialize};
 
 use crate::models::LogEntry;
+use crate::storage;
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum ReportPeriod {
@@ -72,14 +72,9 @@ pub fn generate_report_context(logs: &[LogEntry], period: ReportPeriod) -> Repor
 
     let filtered: Vec<&LogEntry> = logs
         .iter()
-        .filter(|log| {
-            if let Ok(ts) = NaiveDateTime::parse_from_str(&log.timestamp, "%Y-%m-%d %H:%M:%S%.f") {
-                ts >= start_time && ts < end_time
-            } else if let Ok(ts) = NaiveDateTime::parse_from_str(&log.timestamp, "%Y-%m-%d %H:%M:%S") {
-                ts >= start_time && ts < end_time
-            } else {
-                true
-            }
+        .filter(|log| match log.time {
+            Some(ts) => ts >= start_time && ts < end_time,
+            None => true,
         })
         .collect();
 
@@ -99,10 +94,10 @@ pub fn generate_report_context(logs: &[LogEntry], period: ReportPeriod) -> Repor
             warn_count += 1;
         }
 
-        *source_counts.entry(log.source_file.clone()).or_insert(0) += 1;
+        *source_counts.entry(log.source_file.to_string()).or_insert(0) += 1;
 
-        if log.timestamp.len() >= 13 {
-            let hour = format!("{}:00", &log.timestamp[11..13]);
+        if let Some(ts) = log.time {
+            let hour = ts.format("%H:00").to_string();
             *hour_counts.entry(hour).or_insert(0) += 1;
         }
     }
@@ -167,29 +162,15 @@ pub struct ReportCache {
 
 impl ReportCache {
     fn file_path() -> PathBuf {
-        dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".loginsight")
-            .join("reports.json")
+        storage::path("reports.json")
     }
 
     pub fn load() -> Self {
-        let path = Self::file_path();
-        if let Ok(content) = fs::read_to_string(&path) {
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
-            Self::default()
-        }
+        storage::read_json(&Self::file_path()).unwrap_or_default()
     }
 
     pub fn save(&self) {
-        let path = Self::file_path();
-        if let Some(parent) = path.parent() {
-            let _ = fs::create_dir_all(parent);
-        }
-        if let Ok(json) = serde_json::to_string_pretty(self) {
-            let _ = fs::write(&path, json);
-        }
+        let _ = storage::write_json(&Self::file_path(), self);
     }
 
     pub fn get(&self, period: ReportPeriod) -> Option<&String> {
@@ -3,10 +3,18 @@
 //! This module provides the data structures for multi-condition search:
 //! - `LogLevel` enum for type-safe level filtering
 //! - `SearchCriteria` struct for combining multiple filter conditions
+//! - `SearchQuery`, the boolean query language of the `/` search bar
 
-use chrono::{DateTime, Local};
+use std::ops::Range;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::HighlightRule;
+use crate::models::TimeDisplay;
 
 /// Log level enum for type-safe level filtering
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -19,8 +27,6 @@ pub enum LogLevel {
 
 impl LogLevel {
     /// Parse level from string (case-insensitive)
-    /// Reserved for future use (e.g., parsing level from config or CLI)
-    #[allow(dead_code)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "debug" => Some(LogLevel::Debug),
@@ -59,6 +65,9 @@ pub struct SearchCriteria {
     pub source_file: Option<String>,
     /// Allowed log levels (empty = all levels)
     pub levels: Vec<LogLevel>,
+    /// Query-language condition: the quick search's query, or the advanced panel's
+    /// JSON condition
+    pub query: Option<SearchQuery>,
 }
 
 /// Serializable version of SearchCriteria for saving templates
@@ -75,6 +84,10 @@ pub struct SerializableSearchCriteria {
     pub source_file: Option<String>,
     /// Allowed log levels
     pub levels: Vec<LogLevel>,
+    /// JSON payload condition in query syntax (e.g. "$.latency > 200"); absent in
+    /// templates saved before it existed
+    #[serde(default)]
+    pub json_filter: Option<String>,
 }
 
 /// A named search template for saving/loading
@@ -84,12 +97,23 @@ pub struct SearchTemplate {
     pub name: String,
     /// The search criteria
     pub criteria: SerializableSearchCriteria,
+    /// Highlight rules active when the template was saved
+    #[serde(default)]
+    pub highlights: Vec<HighlightRule>,
 }
 
 impl SearchTemplate {
     /// Create a new search template
-    pub fn new(name: String, criteria: SerializableSearchCriteria) -> Self {
-        Self { name, criteria }
+    pub fn new(
+        name: String,
+        criteria: SerializableSearchCriteria,
+        highlights: Vec<HighlightRule>,
+    ) -> Self {
+        Self {
+            name,
+            criteria,
+            highlights,
+        }
     }
 }
 
@@ -107,6 +131,7 @@ impl SearchCriteria {
             && self.content_regex.is_none()
             && self.source_file.is_none()
             && self.levels.is_empty()
+            && self.query.is_none()
     }
 
     /// Build compiled regex from content_regex string
@@ -161,6 +186,550 @@ impl SearchCriteria {
     }
 }
 
+/// Comparison operator of a `line>1000`-style query term
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Comparison {
+    pub fn holds<T: PartialOrd>(self, value: T, bound: T) -> bool {
+        match self {
+            Comparison::Lt => value < bound,
+            Comparison::Le => value <= bound,
+            Comparison::Eq => value == bound,
+            Comparison::Ne => value != bound,
+            Comparison::Ge => value >= bound,
+            Comparison::Gt => value > bound,
+        }
+    }
+
+    /// Compare `value` with a bound that covers the span `[span.start, span.end)`
+    ///
+    /// Equal means inside the span; `<` is before it and `>` after it.
+    pub fn holds_in<T: PartialOrd>(self, value: T, span: &Range<T>) -> bool {
+        match self {
+            Comparison::Lt => value < span.start,
+            Comparison::Le => value < span.end,
+            Comparison::Eq => span.contains(&value),
+            Comparison::Ne => !span.contains(&value),
+            Comparison::Ge => value >= span.start,
+            Comparison::Gt => value >= span.end,
+        }
+    }
+}
+
+/// One step of a JSON path: an object key or an array index
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A condition on an entry's JSON payload: `$.user.id == 42`, `$.latency > 200` or
+/// `exists($.error)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonFilter {
+    pub path: Vec<PathSegment>,
+    /// The comparison the value must pass; `None` only requires the path to exist
+    pub test: Option<(Comparison, Value)>,
+}
+
+impl JsonFilter {
+    /// Parse `$.a.b[0]["c d"]`; the quotes around bracketed keys are optional
+    fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+        let invalid = || anyhow!("无效的 JSON 路径: {}", path);
+        let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid());
+                }
+                segments.push(PathSegment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(invalid)?;
+                let inner = &after[..end];
+                segments.push(match inner.parse() {
+                    Ok(index) => PathSegment::Index(index),
+                    Err(_) => PathSegment::Key(inner.trim_matches(['"', '\'']).to_string()),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(segments)
+    }
+
+    pub fn matches(&self, payload: Option<&Value>) -> bool {
+        let found = payload.and_then(|payload| {
+            self.path.iter().try_fold(payload, |value, segment| match segment {
+                PathSegment::Key(key) => value.get(key),
+                PathSegment::Index(index) => value.get(index),
+            })
+        });
+        let Some(value) = found else {
+            return false;
+        };
+        let Some((op, expected)) = &self.test else {
+            return true;
+        };
+        // Numbers written as strings (`"42"`) compare as numbers
+        let number = |v: &Value| match v {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        };
+        match (number(value), number(expected), op) {
+            (Some(a), Some(b), _) => op.holds(a, b),
+            (_, _, Comparison::Eq) => value == expected,
+            (_, _, Comparison::Ne) => value != expected,
+            _ => match (value.as_str(), expected.as_str()) {
+                (Some(a), Some(b)) => op.holds(a, b),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// A compiled quick-search query
+///
+/// Plain input is a single regex, as it always was. Input containing `AND`, `OR`,
+/// `NOT`, a quoted phrase, a field term or a JSON path is read as a query instead,
+/// e.g. `level:error AND src:Usb* AND NOT "timeout"` or `$.latency > 200`. Adjacent
+/// terms are ANDed, `OR` binds looser than `AND`, and parentheses group.
+#[derive(Debug, Clone)]
+pub enum SearchQuery {
+    /// Regex or quoted phrase, matched against the entry's searchable text
+    Text(Regex),
+    Level(LogLevel),
+    /// A level name outside [`LogLevel`], matched case-insensitively as a substring
+    LevelName(String),
+    /// Source file glob, matched case-insensitively; no wildcard means "contains"
+    Source(glob::Pattern),
+    Tid(String),
+    Pid(String),
+    /// Original line number, as in the list's first column
+    Line(Comparison, usize),
+    /// Milliseconds since the previous entry of the same thread
+    Delta(Comparison, i64),
+    /// Entry time against the span the typed time covers, e.g. `ts:10:30` is the whole
+    /// minute and `ts:10:30:05` the whole second
+    Time(Comparison, Range<NaiveDateTime>),
+    Json(JsonFilter),
+    Not(Box<SearchQuery>),
+    And(Vec<SearchQuery>),
+    Or(Vec<SearchQuery>),
+}
+
+/// Field names a query term may start with
+const QUERY_FIELDS: [&str; 12] = [
+    "level", "lvl", "src", "source", "file", "tid", "thread", "pid", "line", "delta", "ts",
+    "time",
+];
+
+impl SearchQuery {
+    /// Compile search bar input; `None` when there is nothing to search for
+    ///
+    /// A leading `!` negates the whole query. Absolute times in `ts` terms are read in
+    /// the zone `time_display` shows.
+    pub fn parse(input: &str, time_display: TimeDisplay) -> Result<Option<Self>> {
+        let (negate, body) = match input.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        if body.trim().is_empty() {
+            return Ok(None);
+        }
+        let query = if is_query_syntax(body) {
+            let mut parser = QueryParser {
+                tokens: tokenize(body)?,
+                pos: 0,
+                time_display,
+            };
+            let query = parser.parse_or()?;
+            if parser.pos < parser.tokens.len() {
+                bail!("多余的右括号");
+            }
+            query
+        } else {
+            SearchQuery::Text(compile_text(body)?)
+        };
+        Ok(Some(if negate {
+            SearchQuery::Not(Box::new(query))
+        } else {
+            query
+        }))
+    }
+
+    /// Regex highlighting the text terms that are searched for (not negated ones)
+    pub fn highlight_regex(&self) -> Option<Regex> {
+        let mut patterns = Vec::new();
+        self.collect_highlights(&mut patterns);
+        match patterns.as_slice() {
+            [] => None,
+            [re] => Some((*re).clone()),
+            _ => {
+                let joined: Vec<String> =
+                    patterns.iter().map(|re| format!("(?:{})", re.as_str())).collect();
+                Regex::new(&joined.join("|")).ok()
+            }
+        }
+    }
+
+    fn collect_highlights<'a>(&'a self, out: &mut Vec<&'a Regex>) {
+        match self {
+            SearchQuery::Text(re) => out.push(re),
+            SearchQuery::And(terms) | SearchQuery::Or(terms) => {
+                for term in terms {
+                    term.collect_highlights(out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn compile_text(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|_| anyhow!("无效的正则表达式: {}", pattern))
+}
+
+/// Whether `input` uses the query language rather than being a plain regex
+///
+/// A field term only counts with its value attached (`time:5`), so text such as
+/// `response time: 5` or `file: not found` stays a plain search.
+fn is_query_syntax(input: &str) -> bool {
+    input.split_whitespace().any(|word| {
+        let word = word.trim_start_matches('(');
+        matches!(word, "AND" | "OR" | "NOT")
+            || word.starts_with('"')
+            || ["$.", "$[", "exists($"].iter().any(|p| word.starts_with(p))
+            || split_field(word.trim_end_matches(')'))
+                .is_some_and(|(_, _, value)| !value.is_empty())
+    })
+}
+
+/// Split `field:value` or `field>=value` for a known field; `None` as the operator
+/// stands for `:`
+fn split_field(word: &str) -> Option<(String, Option<Comparison>, &str)> {
+    let at = word.find([':', '<', '>', '='])?;
+    let field = word[..at].to_ascii_lowercase();
+    if !QUERY_FIELDS.contains(&field.as_str()) {
+        return None;
+    }
+    let rest = &word[at..];
+    match split_operator(rest) {
+        Some((op, value)) => Some((field, Some(op), value)),
+        None => Some((field, None, &rest[1..])),
+    }
+}
+
+/// Split a leading comparison operator (`>=`, `==`, `!=`, `<`, ...) off `text`
+fn split_operator(text: &str) -> Option<(Comparison, &str)> {
+    const OPERATORS: [(&str, Comparison); 7] = [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        ("==", Comparison::Eq),
+        ("!=", Comparison::Ne),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        ("=", Comparison::Eq),
+    ];
+    OPERATORS
+        .iter()
+        .find_map(|(symbol, op)| text.strip_prefix(symbol).map(|rest| (*op, rest)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    /// A fully quoted phrase, matched literally
+    Phrase(String),
+    Word(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                let mut unquoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c != '"' {
+                        unquoted = true;
+                        word.push(c);
+                        continue;
+                    }
+                    // Quoted text may hold spaces and parentheses, e.g. `src:"my file"`
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(escaped) => word.push(escaped),
+                                None => bail!("引号未闭合"),
+                            },
+                            Some(c) => word.push(c),
+                            None => bail!("引号未闭合"),
+                        }
+                    }
+                }
+                tokens.push(match word.as_str() {
+                    _ if quoted && !unquoted => Token::Phrase(word),
+                    "AND" if !quoted => Token::And,
+                    "OR" if !quoted => Token::Or,
+                    "NOT" if !quoted => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens: `or := and (OR and)*`,
+/// `and := unary ([AND] unary)*`, `unary := NOT unary | ( or ) | term`
+struct QueryParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    time_display: TimeDisplay,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn parse_or(&mut self) -> Result<SearchQuery> {
+        let mut terms = vec![self.parse_and()?];
+        while self.eat(&Token::Or) {
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            SearchQuery::Or(terms)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<SearchQuery> {
+        let mut terms = vec![self.parse_unary()?];
+        // Terms without an operator between them are ANDed too
+        while self.eat(&Token::And)
+            || matches!(self.peek(), Some(t) if *t != Token::Or && *t != Token::RParen)
+        {
+            terms.push(self.parse_unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            SearchQuery::And(terms)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<SearchQuery> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Not) => Ok(SearchQuery::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let query = self.parse_or()?;
+                if !self.eat(&Token::RParen) {
+                    bail!("缺少右括号");
+                }
+                Ok(query)
+            }
+            Some(Token::Phrase(text)) => compile_text(&regex::escape(&text)).map(SearchQuery::Text),
+            Some(Token::Word(word)) if word == "exists" && self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let path = match self.peek().cloned() {
+                    Some(Token::Word(path)) => path,
+                    _ => bail!("exists(...) 中缺少 JSON 路径"),
+                };
+                self.pos += 1;
+                if !self.eat(&Token::RParen) {
+                    bail!("缺少右括号");
+                }
+                Ok(SearchQuery::Json(JsonFilter {
+                    path: JsonFilter::parse_path(&path)?,
+                    test: None,
+                }))
+            }
+            Some(Token::Word(word)) if word.starts_with('$') => self.parse_json_term(&word),
+            Some(Token::Word(word)) => parse_term(&word, self.time_display),
+            Some(Token::RParen) => bail!("多余的右括号"),
+            Some(Token::And) => bail!("AND 前后缺少条件"),
+            Some(Token::Or) => bail!("OR 前后缺少条件"),
+            None => bail!("查询不完整，末尾缺少条件"),
+        }
+    }
+}
+
+impl QueryParser {
+    /// `$.path`, optionally followed by an operator and a value, with or without spaces
+    /// in between; a bare path tests that it exists
+    fn parse_json_term(&mut self, word: &str) -> Result<SearchQuery> {
+        let (path, mut rest) = match word.find(['=', '!', '<', '>']) {
+            Some(at) => (&word[..at], word[at..].to_string()),
+            None => (word, String::new()),
+        };
+        let path = JsonFilter::parse_path(path)?;
+        if rest.is_empty() {
+            match self.peek() {
+                Some(Token::Word(next)) if split_operator(next).is_some() => {
+                    rest = next.clone();
+                    self.pos += 1;
+                }
+                _ => return Ok(SearchQuery::Json(JsonFilter { path, test: None })),
+            }
+        }
+        let (op, value) = split_operator(&rest).ok_or_else(|| anyhow!("无效的比较运算: {}", rest))?;
+        let value = if value.is_empty() {
+            let token = self.peek().cloned();
+            self.pos += 1;
+            match token {
+                Some(Token::Phrase(text)) => Value::String(text),
+                Some(Token::Word(text)) => json_literal(&text),
+                _ => bail!("{} 缺少比较的值", word),
+            }
+        } else {
+            json_literal(value)
+        };
+        Ok(SearchQuery::Json(JsonFilter {
+            path,
+            test: Some((op, value)),
+        }))
+    }
+}
+
+/// An unquoted value: a JSON number, `true`, `false` or `null`, else a string
+fn json_literal(text: &str) -> Value {
+    serde_json::from_str(text)
+        .ok()
+        .filter(|v: &Value| !v.is_object() && !v.is_array())
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+fn parse_term(word: &str, time_display: TimeDisplay) -> Result<SearchQuery> {
+    let Some((field, op, value)) = split_field(word) else {
+        return compile_text(word).map(SearchQuery::Text);
+    };
+    if value.is_empty() {
+        bail!("{} 缺少值", word);
+    }
+    let text_field = || match op {
+        None => Ok(value),
+        Some(_) => Err(anyhow!("字段 {} 只能用 ':'", field)),
+    };
+    let op = op.unwrap_or(Comparison::Eq);
+    Ok(match field.as_str() {
+        "level" | "lvl" => {
+            let value = text_field()?;
+            LogLevel::from_str(value)
+                .map_or_else(|| SearchQuery::LevelName(value.to_lowercase()), SearchQuery::Level)
+        }
+        "src" | "source" | "file" => {
+            let value = text_field()?;
+            let pattern = if value.contains(['*', '?', '[']) {
+                value.to_string()
+            } else {
+                format!("*{}*", glob::Pattern::escape(value))
+            };
+            SearchQuery::Source(
+                glob::Pattern::new(&pattern).map_err(|_| anyhow!("无效的文件模式: {}", value))?,
+            )
+        }
+        "tid" | "thread" => SearchQuery::Tid(text_field()?.to_string()),
+        "pid" => SearchQuery::Pid(text_field()?.to_string()),
+        "line" => SearchQuery::Line(
+            op,
+            value.parse().map_err(|_| anyhow!("无效的行号: {}", value))?,
+        ),
+        "delta" => SearchQuery::Delta(
+            op,
+            parse_millis(value).ok_or_else(|| anyhow!("无效的间隔: {} (如 500ms、2s)", value))?,
+        ),
+        _ => {
+            let start = time_display
+                .parse_user_time(value)
+                .ok_or_else(|| anyhow!("无效的时间: {}", value))?
+                .naive_local();
+            SearchQuery::Time(op, start..start + typed_precision(value))
+        }
+    })
+}
+
+/// How much time a user-typed time covers: a day, minute, second or millisecond
+fn typed_precision(value: &str) -> Duration {
+    if value.starts_with(['-', '+']) {
+        // Relative times are counted from now to the second
+        Duration::seconds(1)
+    } else if value.contains('.') {
+        Duration::milliseconds(1)
+    } else {
+        match value.matches(':').count() {
+            0 => Duration::days(1),
+            1 => Duration::minutes(1),
+            _ => Duration::seconds(1),
+        }
+    }
+}
+
+/// `500ms`, `2s`, `1.5m`, `1h`; a bare number is milliseconds
+fn parse_millis(value: &str) -> Option<i64> {
+    let (number, scale) = if let Some(n) = value.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix('s') {
+        (n, 1000.0)
+    } else if let Some(n) = value.strip_suffix('m') {
+        (n, 60_000.0)
+    } else if let Some(n) = value.strip_suffix('h') {
+        (n, 3_600_000.0)
+    } else {
+        (value, 1.0)
+    };
+    let number: f64 = number.parse().ok()?;
+    number.is_finite().then_some((number * scale) as i64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,4 +767,103 @@ mod tests {
         assert_eq!(criteria.content_regex, Some("test".to_string()));
         assert_eq!(criteria.levels, vec![LogLevel::Error]);
     }
+
+    fn parse(input: &str) -> Result<Option<SearchQuery>> {
+        SearchQuery::parse(input, TimeDisplay::Local)
+    }
+
+    #[test]
+    fn test_plain_input_stays_regex() {
+        let Some(SearchQuery::Text(re)) = parse("foo(bar|baz) qux").unwrap() else {
+            panic!("expected a regex");
+        };
+        assert_eq!(re.as_str(), "foo(bar|baz) qux");
+        assert!(matches!(parse("!timeout").unwrap(), Some(SearchQuery::Not(_))));
+        assert!(parse("").unwrap().is_none());
+        assert!(parse("foo(").is_err());
+
+        // Field names followed by a space are ordinary words
+        let plain = ["response time: 5", "file: not found", "line: 42", "thread: main", "source: db"];
+        for input in plain {
+            let Some(SearchQuery::Text(re)) = parse(input).unwrap() else {
+                panic!("expected a regex for {:?}", input);
+            };
+            assert_eq!(re.as_str(), input);
+        }
+        // ...while an attached value still makes a field term
+        let Some(SearchQuery::And(terms)) = parse("thread:main timeout").unwrap() else {
+            panic!("expected a query");
+        };
+        assert!(matches!(&terms[..], [SearchQuery::Tid(tid), SearchQuery::Text(_)] if tid == "main"));
+    }
+
+    #[test]
+    fn test_query_precedence() {
+        let query = parse(r#"level:error src:Usb* OR NOT "time out""#).unwrap().unwrap();
+        let SearchQuery::Or(alternatives) = &query else {
+            panic!("expected OR at the top: {:?}", query);
+        };
+        assert!(matches!(
+            alternatives[0],
+            SearchQuery::And(ref terms)
+                if matches!(terms[..], [SearchQuery::Level(LogLevel::Error), SearchQuery::Source(_)])
+        ));
+        assert!(matches!(alternatives[1], SearchQuery::Not(ref t) if matches!(**t, SearchQuery::Text(_))));
+        // Negated text is not highlighted
+        assert!(query.highlight_regex().is_none());
+        let highlighted = parse(r#"level:error "a.b" OR x"#).unwrap().unwrap();
+        assert_eq!(highlighted.highlight_regex().unwrap().as_str(), r"(?:a\.b)|(?:x)");
+
+        let grouped = parse("(tid:1a2b OR pid:7) AND line>=1000 delta>1.5s").unwrap().unwrap();
+        let SearchQuery::And(terms) = grouped else { panic!() };
+        assert!(matches!(terms[0], SearchQuery::Or(_)));
+        assert!(matches!(terms[1], SearchQuery::Line(Comparison::Ge, 1000)));
+        assert!(matches!(terms[2], SearchQuery::Delta(Comparison::Gt, 1500)));
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let error = |input: &str| parse(input).unwrap_err().to_string();
+        assert_eq!(error("(level:error"), "缺少右括号");
+        assert_eq!(error("level:error)"), "多余的右括号");
+        assert_eq!(error(r#"AND "abc"#), "引号未闭合");
+        assert_eq!(error("tid: AND x"), "tid: 缺少值");
+        assert_eq!(error("level:error AND"), "查询不完整，末尾缺少条件");
+        assert!(error("line>abc").starts_with("无效的行号"));
+        assert!(error("level>3").contains("只能用"));
+        assert!(error("$..a == 1").starts_with("无效的 JSON 路径"));
+        assert_eq!(error("$.a >"), "$.a 缺少比较的值");
+    }
+
+    #[test]
+    fn test_json_filters() {
+        let payload = serde_json::json!({
+            "user": {"id": 42, "name": "alice"},
+            "latency": "250",
+            "tags": ["a", "b"],
+            "error": null,
+        });
+        let check = |input: &str| match parse(input).unwrap().unwrap() {
+            SearchQuery::Json(filter) => filter.matches(Some(&payload)),
+            other => panic!("{input}: {other:?}"),
+        };
+        // Operators may be attached or spaced; numbers in strings compare as numbers
+        assert!(check("$.user.id == 42"));
+        assert!(check("$.user.id!=7"));
+        assert!(check("$.latency > 200"));
+        assert!(!check("$.latency<=200"));
+        assert!(check(r#"$.user.name == "alice""#));
+        assert!(check("$.user.name >= al"));
+        assert!(check(r#"$["tags"][1] == b"#));
+        assert!(check("exists($.error)"));
+        assert!(check("$.error == null"));
+        assert!(!check("exists($.user.email)"));
+        assert!(!check("$.missing > 0"));
+
+        let filter = JsonFilter {
+            path: JsonFilter::parse_path("$.user").unwrap(),
+            test: None,
+        };
+        assert!(!filter.matches(None));
+    }
 }
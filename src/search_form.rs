@@ -15,6 +15,7 @@ pub enum FormField {
     EndTime,
     Content,
     Source,
+    Json,
     LevelSelect,
     SubmitBtn,
 }
@@ -26,7 +27,8 @@ impl FormField {
             FormField::StartTime => FormField::EndTime,
             FormField::EndTime => FormField::Content,
             FormField::Content => FormField::Source,
-            FormField::Source => FormField::LevelSelect,
+            FormField::Source => FormField::Json,
+            FormField::Json => FormField::LevelSelect,
             FormField::LevelSelect => FormField::SubmitBtn,
             FormField::SubmitBtn => FormField::StartTime,
         }
@@ -39,7 +41,8 @@ impl FormField {
             FormField::EndTime => FormField::StartTime,
             FormField::Content => FormField::EndTime,
             FormField::Source => FormField::Content,
-            FormField::LevelSelect => FormField::Source,
+            FormField::Json => FormField::Source,
+            FormField::LevelSelect => FormField::Json,
             FormField::SubmitBtn => FormField::LevelSelect,
         }
     }
@@ -53,6 +56,7 @@ impl FormField {
             FormField::EndTime => "结束时间",
             FormField::Content => "内容正则",
             FormField::Source => "来源文件",
+            FormField::Json => "JSON 条件",
             FormField::LevelSelect => "日志级别",
             FormField::SubmitBtn => "搜索",
         }
@@ -81,6 +85,8 @@ pub struct SearchFormState {
     pub content_input: String,
     /// Source file filter input
     pub source_input: String,
+    /// JSON payload condition input (query syntax, e.g. `$.latency > 200`)
+    pub json_input: String,
     /// Selected log levels
     pub selected_levels: HashSet<LogLevel>,
     /// Currently focused field
@@ -93,6 +99,8 @@ pub struct SearchFormState {
     pub template_mode: TemplateMode,
     /// Template name input (for saving)
     pub template_name_input: String,
+    /// Name of an existing template the user was warned about; saving it again overwrites
+    pub overwrite_name: Option<String>,
     /// Available template names (for loading)
     pub template_list: Vec<String>,
     /// Selected template index (for loading)
@@ -130,6 +138,7 @@ impl SearchFormState {
         self.end_time_input.clear();
         self.content_input.clear();
         self.source_input.clear();
+        self.json_input.clear();
         self.selected_levels.clear();
         self.error_message = None;
         self.status_message = None;
@@ -161,6 +170,7 @@ impl SearchFormState {
             FormField::EndTime => Some(&mut self.end_time_input),
             FormField::Content => Some(&mut self.content_input),
             FormField::Source => Some(&mut self.source_input),
+            FormField::Json => Some(&mut self.json_input),
             FormField::LevelSelect | FormField::SubmitBtn => None,
         }
     }
@@ -173,6 +183,7 @@ impl SearchFormState {
             || !self.end_time_input.is_empty()
             || !self.content_input.is_empty()
             || !self.source_input.is_empty()
+            || !self.json_input.is_empty()
             || !self.selected_levels.is_empty()
     }
 
@@ -192,6 +203,7 @@ impl SearchFormState {
     pub fn start_save_template(&mut self) {
         self.template_mode = TemplateMode::Saving;
         self.template_name_input.clear();
+        self.overwrite_name = None;
         self.error_message = None;
     }
 
@@ -253,6 +265,11 @@ impl SearchFormState {
                 Some(self.source_input.clone())
             },
             levels: self.selected_levels.iter().cloned().collect(),
+            json_filter: if self.json_input.is_empty() {
+                None
+            } else {
+                Some(self.json_input.clone())
+            },
         }
     }
 
@@ -262,6 +279,7 @@ impl SearchFormState {
         self.end_time_input = criteria.end_time.clone().unwrap_or_default();
         self.content_input = criteria.content_regex.clone().unwrap_or_default();
         self.source_input = criteria.source_file.clone().unwrap_or_default();
+        self.json_input = criteria.json_filter.clone().unwrap_or_default();
         self.selected_levels = criteria.levels.iter().cloned().collect();
     }
 
@@ -279,7 +297,7 @@ mod tests {
     #[test]
     fn test_field_cycle() {
         let mut field = FormField::StartTime;
-        for _ in 0..6 {
+        for _ in 0..7 {
             field = field.next();
         }
         assert_eq!(field, FormField::StartTime);
@@ -0,0 +1,136 @@
+//! Quick-search matching off the UI thread
+//!
+//! A search runs over a snapshot of the entries and the [`TrigramIndex`] (both share
+//! their chunks with the app's, nothing is copied) on the rayon pool, narrowed through
+//! the index first. Starting another search cancels the running one. Entries tailed
+//! meanwhile don't: the app appends them next to the snapshot, matches the new rows
+//! itself and merges them with the results.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::entry_list::EntryList;
+use crate::filtering::matches_criteria;
+use crate::index::TrigramIndex;
+use crate::search::SearchCriteria;
+
+pub struct SearchJob {
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    rx: Receiver<Vec<usize>>,
+    started: Instant,
+}
+
+impl SearchJob {
+    /// Find the positions in `rows` (indices into `entries`) of the entries matching
+    /// `criteria`
+    pub fn spawn(
+        entries: EntryList,
+        index: TrigramIndex,
+        rows: Vec<usize>,
+        criteria: SearchCriteria,
+    ) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::clone(&cancel);
+        let handle = std::thread::spawn(move || {
+            let candidates = index.criteria_candidates(&criteria);
+            let content_re = criteria.compile_content_regex();
+            let matches: Vec<usize> = rows
+                .par_iter()
+                .enumerate()
+                .filter(|&(_, &idx)| {
+                    !cancelled.load(Ordering::Relaxed)
+                        && candidates.contains(idx)
+                        && matches_criteria(&entries[idx], &criteria, &content_re)
+                })
+                .map(|(pos, _)| pos)
+                .collect();
+            if !cancelled.load(Ordering::Relaxed) {
+                let _ = tx.send(matches);
+            }
+        });
+        Self {
+            cancel,
+            handle: Some(handle),
+            rx,
+            started: Instant::now(),
+        }
+    }
+
+    /// The match positions once the search is done, with how long it took
+    pub fn try_finish(&mut self) -> Option<(Vec<usize>, Duration)> {
+        match self.rx.try_recv() {
+            Ok(matches) => {
+                self.join();
+                Some((matches, self.started.elapsed()))
+            }
+            Err(TryRecvError::Empty) => None,
+            // The worker panicked; report no matches rather than waiting forever
+            Err(TryRecvError::Disconnected) => {
+                self.join();
+                Some((Vec::new(), self.started.elapsed()))
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Stop the search and wait for the worker to let go of the entries
+    pub fn cancel(mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.join();
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DisplayEntry, TimeDisplay};
+    use crate::search::SearchQuery;
+
+    #[test]
+    fn test_search_reports_row_positions() {
+        let entries: EntryList = (0..500)
+            .map(|i| DisplayEntry::Folded {
+                start_index: i,
+                end_index: i,
+                count: 1,
+                summary_text: if i % 100 == 7 {
+                    format!("upstream timeout {}", i)
+                } else {
+                    format!("request {} ok", i)
+                },
+            })
+            .collect::<Vec<_>>()
+            .into();
+        let index = TrigramIndex::build(&entries);
+        let criteria = SearchCriteria {
+            query: SearchQuery::parse("timeout", TimeDisplay::Local).unwrap(),
+            ..SearchCriteria::default()
+        };
+        // Only the odd entries are shown, so entry 107 is at row 53
+        let rows: Vec<usize> = (0..500).filter(|i| i % 2 == 1).collect();
+        let mut job = SearchJob::spawn(entries, index, rows, criteria);
+        let matches = loop {
+            if let Some((matches, _)) = job.try_finish() {
+                break matches;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(matches, vec![3, 53, 103, 153, 203]);
+    }
+}
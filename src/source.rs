@@ -0,0 +1,239 @@
+//! Compact text storage for parsed entries
+//!
+//! A loaded file's bytes are kept once in a shared [`SourceBytes`]. Entry text that
+//! is a plain UTF-8 substring of those bytes is stored as an offset/length
+//! [`LazyText`] into them instead of a copy. Short values repeated on every line
+//! (pid, tid, level, source file) are interned with a [`TextStore`].
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use serde::{Serialize, Serializer};
+
+/// The bytes a file's entries point into
+pub enum SourceBytes {
+    /// A plain file, mapped for as long as its entries are alive
+    Mapped(Mmap),
+    /// A decompressed archive, a transcoded UTF-16 file or test input
+    Owned(Vec<u8>),
+}
+
+impl SourceBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SourceBytes::Owned(bytes)
+    }
+
+    /// Map a plain file instead of reading it
+    ///
+    /// Loaded files are tailed, so the mapped bytes can change under the entries when
+    /// the file is rewritten in place; text read from a mapping is therefore checked
+    /// again before use (see [`LazyText::as_str`]).
+    pub fn map(file: &File) -> io::Result<Self> {
+        // SAFETY: the mapping is read-only and outlives `file`; writes by other
+        // processes can change its contents, which `LazyText::as_str` tolerates.
+        // A file truncated in place (copytruncate rotation) can still fault on
+        // access to pages past its new end.
+        unsafe { Mmap::map(file) }.map(SourceBytes::Mapped)
+    }
+}
+
+impl Deref for SourceBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SourceBytes::Mapped(m) => m,
+            SourceBytes::Owned(v) => v,
+        }
+    }
+}
+
+/// Entry text that is either owned or a UTF-8 slice of a [`SourceBytes`]
+#[derive(Clone)]
+pub enum LazyText {
+    Owned(Box<str>),
+    Slice {
+        source: Arc<SourceBytes>,
+        start: usize,
+        len: u32,
+    },
+}
+
+impl LazyText {
+    /// Point into `source` when `text` lies inside it, otherwise copy `text`
+    pub fn new(text: &str, source: Option<&Arc<SourceBytes>>) -> Self {
+        if let (Some(source), Ok(len)) = (source, u32::try_from(text.len())) {
+            let base = source.as_ptr() as usize;
+            let ptr = text.as_ptr() as usize;
+            if len > 0 && ptr >= base && ptr + text.len() <= base + source.len() {
+                return LazyText::Slice {
+                    source: Arc::clone(source),
+                    start: ptr - base,
+                    len,
+                };
+            }
+        }
+        LazyText::Owned(text.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            LazyText::Owned(s) => s,
+            LazyText::Slice { source, start, len } => {
+                let bytes = &source[*start..*start + *len as usize];
+                match &**source {
+                    // A mapped file may have been rewritten since it was parsed
+                    SourceBytes::Mapped(_) => std::str::from_utf8(bytes).unwrap_or("\u{FFFD}"),
+                    // SAFETY: slices are only created in `new` from a `&str` occupying
+                    // exactly these bytes, and an owned buffer never changes.
+                    SourceBytes::Owned(_) => unsafe { std::str::from_utf8_unchecked(bytes) },
+                }
+            }
+        }
+    }
+}
+
+impl Default for LazyText {
+    fn default() -> Self {
+        LazyText::Owned(Box::default())
+    }
+}
+
+impl Deref for LazyText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for LazyText {
+    fn from(s: String) -> Self {
+        LazyText::Owned(s.into_boxed_str())
+    }
+}
+
+impl From<&str> for LazyText {
+    fn from(s: &str) -> Self {
+        LazyText::Owned(s.into())
+    }
+}
+
+impl PartialEq for LazyText {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for LazyText {}
+
+impl PartialEq<str> for LazyText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for LazyText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for LazyText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for LazyText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for LazyText {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Builds entry text for one source: slices where possible, shared strings for the
+/// short fields that repeat from line to line
+pub struct TextStore<'a> {
+    source: Option<&'a Arc<SourceBytes>>,
+    strings: HashSet<Arc<str>>,
+}
+
+impl<'a> TextStore<'a> {
+    pub fn new(source: Option<&'a Arc<SourceBytes>>) -> Self {
+        Self {
+            source,
+            strings: HashSet::new(),
+        }
+    }
+
+    pub fn text(&self, text: &str) -> LazyText {
+        LazyText::new(text, self.source)
+    }
+
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(s) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = s.into();
+        self.strings.insert(Arc::clone(&shared));
+        shared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slices_only_inside_source() {
+        let source = Arc::new(SourceBytes::new(b"2024-01-15 hello".to_vec()));
+        let text = std::str::from_utf8(&source[11..]).unwrap();
+        let lazy = LazyText::new(text, Some(&source));
+        assert!(matches!(lazy, LazyText::Slice { start: 11, len: 5, .. }));
+        assert_eq!(lazy, "hello");
+
+        let copied = LazyText::new("hello", Some(&source));
+        assert!(matches!(copied, LazyText::Owned(_)));
+        assert_eq!(copied.to_string(), "hello");
+    }
+
+    #[test]
+    fn test_mapped_text_survives_rewrite() {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let path = std::env::temp_dir().join(format!("log-source-map-{}", std::process::id()));
+        std::fs::write(&path, "2024-01-15 héllo").unwrap();
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let source = Arc::new(SourceBytes::map(&file).unwrap());
+        let text = std::str::from_utf8(&source[11..]).unwrap();
+        let lazy = LazyText::new(text, Some(&source));
+        assert_eq!(lazy, "héllo");
+
+        // Rewritten in place with bytes that are no longer UTF-8
+        file.seek(SeekFrom::Start(12)).unwrap();
+        file.write_all(&[0xFF]).unwrap();
+        file.sync_all().unwrap();
+        assert_eq!(lazy, "\u{FFFD}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_intern_shares_strings() {
+        let mut store = TextStore::new(None);
+        let a = store.intern("INFO");
+        let b = store.intern("INFO");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &store.intern("WARN")));
+    }
+}
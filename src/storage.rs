@@ -0,0 +1,239 @@
+//! Where history, templates and report caches are kept, and how they are written
+//!
+//! Data lives in a per-project directory when there is one: `--workspace DIR` uses
+//! `DIR/.loginsight/`, otherwise a project with a config file keeps it in `.loginsight/`
+//! next to that file (created on the first write). Without either, everything goes
+//! to `~/.loginsight/` as before. The directory in use is shown at startup.
+//!
+//! Files are replaced atomically (written to a temporary file, then renamed), and
+//! read-modify-write cycles hold an exclusive lock on a `<name>.lock` file, so two
+//! running instances can share a directory without losing or corrupting data.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use fs4::fs_std::FileExt;
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const DIR_NAME: &str = ".loginsight";
+
+static STORAGE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Pick the storage directory from the `--workspace` and `--config` arguments
+pub fn resolve_dir(workspace: Option<&Path>, config: Option<&Path>) -> Result<PathBuf> {
+    if let Some(workspace) = workspace {
+        if !workspace.is_dir() {
+            anyhow::bail!("工作区目录不存在: {}", workspace.display());
+        }
+        return Ok(workspace.join(DIR_NAME));
+    }
+    let config = config.unwrap_or(Path::new("log_config.toml"));
+    let beside_config = config
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .join(DIR_NAME);
+    if config.is_file() || beside_config.is_dir() {
+        return Ok(beside_config);
+    }
+    Ok(global_dir())
+}
+
+fn global_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(DIR_NAME)
+}
+
+/// Set the storage directory for this run; must be called before anything is loaded
+pub fn init(dir: PathBuf) {
+    let _ = STORAGE_DIR.set(dir);
+}
+
+/// The storage directory; `~/.loginsight` if `init` wasn't called
+pub fn dir() -> &'static Path {
+    STORAGE_DIR.get_or_init(global_dir)
+}
+
+/// Path of a data file in the storage directory
+pub fn path(name: &str) -> PathBuf {
+    dir().join(name)
+}
+
+/// Read a JSON file; the default value if it doesn't exist, an error if it can't be
+/// read or parsed
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).with_context(|| format!("读取文件失败: {}", path.display())),
+    };
+    serde_json::from_str(&content).with_context(|| format!("解析文件失败: {}", path.display()))
+}
+
+/// Replace a JSON file with `value`
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let _lock = lock(path)?;
+    write_atomic(path, serde_json::to_string_pretty(value)?.as_bytes())
+}
+
+/// Read a JSON file, change it with `f` and write it back, holding the lock so no
+/// other instance writes in between. A missing file starts from the default value;
+/// nothing is written if `f` fails. A file that can't be parsed is moved to
+/// `<name>.bak` rather than overwritten, and the update fails.
+pub fn update_json<T, R>(path: &Path, f: impl FnOnce(&mut T) -> Result<R>) -> Result<R>
+where
+    T: DeserializeOwned + Serialize + Default,
+{
+    let _lock = lock(path)?;
+    let mut value = read_json(path).map_err(|e| set_aside(path, e))?;
+    let result = f(&mut value)?;
+    write_atomic(path, serde_json::to_string_pretty(&value)?.as_bytes())?;
+    Ok(result)
+}
+
+/// Move a file that failed to parse to `<name>.bak`, so the next write starts afresh
+/// without losing what was in it
+fn set_aside(path: &Path, error: anyhow::Error) -> anyhow::Error {
+    if error.downcast_ref::<serde_json::Error>().is_none() {
+        return error;
+    }
+    let backup = sibling(path, "", ".bak");
+    match fs::rename(path, &backup) {
+        Ok(()) => anyhow::anyhow!("{:#}，原文件已移至 {}", error, backup.display()),
+        Err(_) => error,
+    }
+}
+
+/// Take the exclusive lock for `path`, waiting for other holders; released when the
+/// returned file is dropped
+fn lock(path: &Path) -> Result<File> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    let lock_path = sibling(path, "", ".lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("打开锁文件失败: {}", lock_path.display()))?;
+    file.lock_exclusive()
+        .with_context(|| format!("加锁失败: {}", lock_path.display()))?;
+    Ok(file)
+}
+
+/// Write `bytes` to a temporary file next to `path` and rename it over `path`, so
+/// readers see either the old or the new content, never a partial write
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("创建目录失败: {}", dir.display()))?;
+    let tmp = sibling(path, ".", &format!(".{}.tmp", std::process::id()));
+    let written = File::create(&tmp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("写入文件失败: {}", path.display()));
+    }
+    Ok(())
+}
+
+/// `path` with its file name wrapped in `prefix` and `suffix`
+fn sibling(path: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}{}{}", prefix, name, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("log-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let path = temp_dir("update").join("history.json");
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        update_json(&path, |values: &mut Vec<u32>| {
+                            values.push(t * 100 + i);
+                            Ok(())
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let values: Vec<u32> = read_json(&path).unwrap();
+        assert_eq!(values.len(), 100);
+
+        // A failing update leaves the file as it was
+        let result: Result<()> = update_json(&path, |values: &mut Vec<u32>| {
+            values.clear();
+            anyhow::bail!("stop")
+        });
+        assert!(result.is_err());
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap().len(), 100);
+        // No temporary files are left behind
+        let leftovers = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_unparsable_file_is_not_overwritten() {
+        let path = temp_dir("corrupt").join("templates.json");
+        assert!(read_json::<Vec<u32>>(&path).unwrap().is_empty());
+        fs::write(&path, "[1, 2,").unwrap();
+        assert!(read_json::<Vec<u32>>(&path).is_err());
+
+        let push = |values: &mut Vec<u32>| {
+            values.push(3);
+            Ok(())
+        };
+        let err = update_json(&path, push).unwrap_err();
+        assert!(format!("{:#}", err).contains("templates.json.bak"));
+        assert_eq!(fs::read_to_string(sibling(&path, "", ".bak")).unwrap(), "[1, 2,");
+
+        // With the bad file out of the way, updates work again
+        update_json(&path, push).unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), [3]);
+    }
+
+    #[test]
+    fn test_resolve_dir() {
+        let project = temp_dir("resolve");
+        assert_eq!(resolve_dir(Some(&project), None).unwrap(), project.join(DIR_NAME));
+        assert!(resolve_dir(Some(&project.join("missing")), None).is_err());
+
+        // Without a config file or a .loginsight directory the global one is kept
+        let config = project.join("log_config.toml");
+        assert_eq!(resolve_dir(None, Some(&config)).unwrap(), global_dir());
+        fs::write(&config, "").unwrap();
+        assert_eq!(resolve_dir(None, Some(&config)).unwrap(), project.join(DIR_NAME));
+        fs::remove_file(&config).unwrap();
+        fs::create_dir(project.join(DIR_NAME)).unwrap();
+        assert_eq!(resolve_dir(None, Some(&config)).unwrap(), project.join(DIR_NAME));
+    }
+}
@@ -1,80 +1,253 @@
 //! Search template storage and management
 //!
 //! This module handles saving and loading search templates to/from disk.
-//! Templates are stored in a JSON file in the user's config directory.
+//! Templates are stored in `templates.json` in the storage directory, and can be
+//! exported to and imported from a TOML or JSON file for sharing.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
+use crate::config::HighlightRule;
 use crate::search::{SearchTemplate, SerializableSearchCriteria};
+use crate::storage;
 
 /// Get the path to the templates file
 fn get_templates_path() -> PathBuf {
-    let base = dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".loginsight");
-    
-    // Ensure directory exists
-    let _ = fs::create_dir_all(&base);
-    
-    base.join("templates.json")
+    storage::path("templates.json")
 }
 
 /// Load all saved templates from disk
 pub fn load_templates() -> Vec<SearchTemplate> {
-    let path = get_templates_path();
-    
-    if !path.exists() {
-        return Vec::new();
+    storage::read_json(&get_templates_path()).unwrap_or_default()
+}
+
+/// Change the saved templates with `f`, holding the storage lock so another running
+/// instance can't save in between; nothing is written if `f` fails
+fn update_templates<R>(
+    f: impl FnOnce(&mut Vec<SearchTemplate>) -> Result<R, String>,
+) -> Result<R, String> {
+    storage::update_json(&get_templates_path(), |templates| {
+        f(templates).map_err(anyhow::Error::msg)
+    })
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Save a new template (or overwrite existing with same name), with the highlight
+/// rules to restore when it is loaded
+pub fn save_template(
+    name: &str,
+    criteria: &SerializableSearchCriteria,
+    highlights: &[HighlightRule],
+) -> Result<(), String> {
+    update_templates(|templates| {
+        // Remove existing template with same name
+        templates.retain(|t| t.name != name);
+
+        // Add new template
+        templates.push(SearchTemplate::new(
+            name.to_string(),
+            criteria.clone(),
+            highlights.to_vec(),
+        ));
+        Ok(())
+    })
+}
+
+/// Whether a template with this name is saved
+pub fn template_exists(name: &str) -> bool {
+    load_templates().iter().any(|t| t.name == name)
+}
+
+/// Delete a template by name
+pub fn delete_template(name: &str) -> Result<(), String> {
+    update_templates(|templates| {
+        let original_len = templates.len();
+        templates.retain(|t| t.name != name);
+        if templates.len() == original_len {
+            return Err(format!("模板 '{}' 不存在", name));
+        }
+        Ok(())
+    })
+}
+
+/// Give a template a new name that isn't taken
+pub fn rename_template(name: &str, new_name: &str) -> Result<(), String> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return Err("模板名称不能为空".to_string());
     }
-    
-    match fs::read_to_string(&path) {
-        Ok(content) => {
-            serde_json::from_str(&content).unwrap_or_default()
+    update_templates(|templates| {
+        if new_name != name && templates.iter().any(|t| t.name == new_name) {
+            return Err(format!("模板 '{}' 已存在", new_name));
         }
-        Err(_) => Vec::new(),
+        let template = templates
+            .iter_mut()
+            .find(|t| t.name == name)
+            .ok_or_else(|| format!("模板 '{}' 不存在", name))?;
+        template.name = new_name.to_string();
+        Ok(())
+    })
+}
+
+/// Copy a template under a free "<name> 副本" name, placed after it; returns the name
+pub fn duplicate_template(name: &str) -> Result<String, String> {
+    update_templates(|templates| {
+        let pos = templates
+            .iter()
+            .position(|t| t.name == name)
+            .ok_or_else(|| format!("模板 '{}' 不存在", name))?;
+        let copy_name = copy_name(templates, name);
+        let mut copy = templates[pos].clone();
+        copy.name = copy_name.clone();
+        templates.insert(pos + 1, copy);
+        Ok(copy_name)
+    })
+}
+
+fn copy_name(templates: &[SearchTemplate], name: &str) -> String {
+    let taken = |candidate: &str| templates.iter().any(|t| t.name == candidate);
+    let mut candidate = format!("{} 副本", name);
+    let mut n = 2;
+    while taken(&candidate) {
+        candidate = format!("{} 副本 {}", name, n);
+        n += 1;
     }
+    candidate
 }
 
-/// Save all templates to disk
-fn save_templates(templates: &[SearchTemplate]) -> Result<(), String> {
-    let path = get_templates_path();
-    let content = serde_json::to_string_pretty(templates)
-        .map_err(|e| format!("序列化失败: {}", e))?;
-    
-    fs::write(&path, content)
-        .map_err(|e| format!("写入文件失败: {}", e))?;
-    
-    Ok(())
+/// The TOML layout of an exported file: one `[[template]]` table per template
+#[derive(Serialize, Deserialize)]
+struct TemplateFile {
+    #[serde(default)]
+    template: Vec<SearchTemplate>,
 }
 
-/// Save a new template (or overwrite existing with same name)
-pub fn save_template(name: &str, criteria: &SerializableSearchCriteria) -> Result<(), String> {
-    let mut templates = load_templates();
-    
-    // Remove existing template with same name
-    templates.retain(|t| t.name != name);
-    
-    // Add new template
-    templates.push(SearchTemplate::new(name.to_string(), criteria.clone()));
-    
-    save_templates(&templates)
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
 }
 
-/// Delete a template by name
-/// Reserved for future use (e.g., template management UI)
-#[allow(dead_code)]
-pub fn delete_template(name: &str) -> Result<(), String> {
-    let mut templates = load_templates();
-    let original_len = templates.len();
-    
-    templates.retain(|t| t.name != name);
-    
-    if templates.len() == original_len {
-        return Err(format!("模板 '{}' 不存在", name));
+/// Text of an export file; TOML for `.toml` paths, JSON (the storage format) otherwise
+fn format_templates(templates: &[SearchTemplate], path: &Path) -> Result<String, String> {
+    if is_toml(path) {
+        toml::to_string_pretty(&TemplateFile {
+            template: templates.to_vec(),
+        })
+        .map_err(|e| format!("序列化失败: {}", e))
+    } else {
+        serde_json::to_string_pretty(templates).map_err(|e| format!("序列化失败: {}", e))
+    }
+}
+
+fn parse_templates(content: &str, path: &Path) -> Result<Vec<SearchTemplate>, String> {
+    if is_toml(path) {
+        toml::from_str::<TemplateFile>(content)
+            .map(|file| file.template)
+            .map_err(|e| format!("无效的模板文件: {}", e))
+    } else {
+        serde_json::from_str(content).map_err(|e| format!("无效的模板文件: {}", e))
+    }
+}
+
+/// Add `imported` to `templates`, replacing those with the same name in place;
+/// returns how many were replaced
+fn merge_templates(templates: &mut Vec<SearchTemplate>, imported: Vec<SearchTemplate>) -> usize {
+    let mut replaced = 0;
+    for template in imported {
+        match templates.iter_mut().find(|t| t.name == template.name) {
+            Some(existing) => {
+                *existing = template;
+                replaced += 1;
+            }
+            None => templates.push(template),
+        }
+    }
+    replaced
+}
+
+/// Write all templates to `path`; returns how many were written
+pub fn export_templates(path: &Path) -> Result<usize, String> {
+    let templates = load_templates();
+    let content = format_templates(&templates, path)?;
+    fs::write(path, content).map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(templates.len())
+}
+
+fn read_import_file(path: &Path) -> Result<Vec<SearchTemplate>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    parse_templates(&content, path)
+}
+
+/// Names of the saved templates that importing `path` would replace
+pub fn import_conflicts(path: &Path) -> Result<Vec<String>, String> {
+    let imported = read_import_file(path)?;
+    Ok(replaced_names(&load_templates(), &imported))
+}
+
+fn replaced_names(saved: &[SearchTemplate], imported: &[SearchTemplate]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for template in imported {
+        if saved.iter().any(|t| t.name == template.name) && !names.contains(&template.name) {
+            names.push(template.name.clone());
+        }
+    }
+    names
+}
+
+/// Add the templates in `path`, replacing saved ones of the same name; returns how
+/// many were imported and how many of those replaced a saved one
+pub fn import_templates(path: &Path) -> Result<(usize, usize), String> {
+    let imported = read_import_file(path)?;
+    let count = imported.len();
+    let replaced = update_templates(|templates| Ok(merge_templates(templates, imported)))?;
+    Ok((count, replaced))
+}
+
+/// State of the template manager view (F7)
+#[derive(Default)]
+pub struct TemplateManager {
+    pub templates: Vec<SearchTemplate>,
+    pub selected: usize,
+    /// `d` was pressed once on the selected template; a second press deletes it
+    pub confirm_delete: bool,
+    /// File the user was warned about and the saved templates it replaces; submitting
+    /// the same file again imports it
+    pub overwrite_import: Option<(String, Vec<String>)>,
+}
+
+impl TemplateManager {
+    /// Read the templates from disk again, keeping the selection in range
+    pub fn reload(&mut self) {
+        self.templates = load_templates();
+        self.selected = self.selected.min(self.templates.len().saturating_sub(1));
+        self.confirm_delete = false;
+    }
+
+    /// Reload and select the template called `name`
+    pub fn reload_at(&mut self, name: &str) {
+        self.reload();
+        if let Some(pos) = self.templates.iter().position(|t| t.name == name) {
+            self.selected = pos;
+        }
+    }
+
+    pub fn selected_template(&self) -> Option<&SearchTemplate> {
+        self.templates.get(self.selected)
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.templates.len() {
+            self.selected += 1;
+        }
+        self.confirm_delete = false;
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+        self.confirm_delete = false;
     }
-    
-    save_templates(&templates)
 }
 
 /// Get a template by name
@@ -91,6 +264,54 @@ pub fn get_template_names() -> Vec<String> {
 mod tests {
     use super::*;
     use crate::search::LogLevel;
+
+    fn template(name: &str, content: &str) -> SearchTemplate {
+        let criteria = SerializableSearchCriteria {
+            content_regex: Some(content.to_string()),
+            levels: vec![LogLevel::Error],
+            ..Default::default()
+        };
+        SearchTemplate::new(name.to_string(), criteria, Vec::new())
+    }
+
+    #[test]
+    fn test_export_import_formats() {
+        let mut highlighted = template("timeouts", "timeout");
+        highlighted.highlights.push(HighlightRule {
+            pattern: "timeout".to_string(),
+            color: "Red".to_string(),
+            style: String::new(),
+        });
+        let templates = vec![highlighted, template("db", "deadlock")];
+        for file in ["team.toml", "team.json"] {
+            let path = Path::new(file);
+            let text = format_templates(&templates, path).unwrap();
+            let parsed = parse_templates(&text, path).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[0].highlights.len(), 1);
+            assert_eq!(parsed[1].criteria.content_regex.as_deref(), Some("deadlock"));
+        }
+        assert!(format_templates(&templates, Path::new("t.toml"))
+            .unwrap()
+            .contains("[[template]]"));
+        assert!(parse_templates("not json", Path::new("t.json")).is_err());
+    }
+
+    #[test]
+    fn test_merge_and_copy_names() {
+        let mut saved = vec![template("db", "old"), template("api", "5xx")];
+        let imported = vec![template("db", "new"), template("ui", "x")];
+        assert_eq!(replaced_names(&saved, &imported), vec!["db"]);
+        let replaced = merge_templates(&mut saved, imported);
+        assert_eq!(replaced, 1);
+        let names: Vec<&str> = saved.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["db", "api", "ui"]);
+        assert_eq!(saved[0].criteria.content_regex.as_deref(), Some("new"));
+
+        assert_eq!(copy_name(&saved, "db"), "db 副本");
+        saved.push(template("db 副本", ""));
+        assert_eq!(copy_name(&saved, "db"), "db 副本 2");
+    }
     
     #[test]
     fn test_serializable_criteria() {
@@ -100,6 +321,7 @@ mod tests {
             content_regex: Some("error".to_string()),
             source_file: None,
             levels: vec![LogLevel::Error, LogLevel::Warn],
+            json_filter: Some("$.latency > 200".to_string()),
         };
         
         let json = serde_json::to_string(&criteria).unwrap();
@@ -107,6 +329,12 @@ mod tests {
         
         assert_eq!(parsed.start_time, Some("-1h".to_string()));
         assert_eq!(parsed.levels.len(), 2);
+        assert_eq!(parsed.json_filter.as_deref(), Some("$.latency > 200"));
+
+        // Templates saved before JSON conditions existed still load
+        let old = r#"{"start_time":null,"end_time":null,"content_regex":"x","source_file":null,"levels":[]}"#;
+        let parsed: SerializableSearchCriteria = serde_json::from_str(old).unwrap();
+        assert_eq!(parsed.json_filter, None);
     }
     
     #[test]
@@ -116,12 +344,23 @@ mod tests {
             ..Default::default()
         };
         
-        let template = SearchTemplate::new("test".to_string(), criteria);
+        let highlight = HighlightRule {
+            pattern: "timeout".to_string(),
+            color: "Red".to_string(),
+            style: "bold".to_string(),
+        };
+        let template = SearchTemplate::new("test".to_string(), criteria, vec![highlight.clone()]);
         
         let json = serde_json::to_string(&template).unwrap();
         let parsed: SearchTemplate = serde_json::from_str(&json).unwrap();
         
         assert_eq!(parsed.name, "test");
         assert_eq!(parsed.criteria.start_time, Some("-1h".to_string()));
+        assert_eq!(parsed.highlights, vec![highlight]);
+
+        // Templates saved before highlights existed load without any
+        let old = r#"{"name":"old","criteria":{"start_time":null,"end_time":null,"content_regex":null,"source_file":null,"levels":[]}}"#;
+        let parsed: SearchTemplate = serde_json::from_str(old).unwrap();
+        assert!(parsed.highlights.is_empty());
     }
 }
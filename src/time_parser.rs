@@ -1,17 +1,25 @@
 //! Time parsing utilities for user input and log timestamps
 //!
 //! This module provides flexible parsing for various time formats:
-//! - Full datetime: "YYYY-MM-DD HH:MM:SS" or "YYYY-MM-DD HH:MM:SS.mmm"
+//! - Full datetime: "YYYY-MM-DD HH:MM", "YYYY-MM-DD HH:MM:SS" or "YYYY-MM-DD HH:MM:SS.mmm"
 //! - Date only: "YYYY-MM-DD" (assumes start of day)
 //! - Time only: "HH:MM:SS" or "HH:MM:SS.mmm" (assumes today's date)
+//!
+//! Log timestamps are parsed once at ingest by [`parse_log_time`], which accepts a
+//! configured strftime format plus ISO-8601, epoch and syslog forms. A file's
+//! [`ClockAdjust`] says which zone its offset-less timestamps are in and how far its
+//! clock is off, so entries from different machines share one local timeline.
 
-use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
+use anyhow::Result;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
 
 /// Parse user input string into DateTime<Local>
 ///
 /// Supported formats:
 /// - "HH:MM:SS" or "HH:MM:SS.mmm" - assumes today's date
-/// - "YYYY-MM-DD HH:MM:SS" or "YYYY-MM-DD HH:MM:SS.mmm" - full datetime
+/// - "YYYY-MM-DD HH:MM[:SS[.mmm]]" - full datetime
 /// - "YYYY-MM-DD" - start of that day (00:00:00)
 /// - "-1h", "-30m", "-2d" - relative time (hours, minutes, days ago)
 /// - "+1h", "+30m" - relative time in the future
@@ -31,6 +39,16 @@ use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
 /// assert!(relative.is_some());
 /// ```
 pub fn parse_user_time(input: &str) -> Option<DateTime<Local>> {
+    parse_user_time_in(input, &Local)
+}
+
+/// Parse user input like [`parse_user_time`], reading absolute times as UTC
+pub fn parse_user_time_utc(input: &str) -> Option<DateTime<Local>> {
+    parse_user_time_in(input, &Utc)
+}
+
+/// Parse user input with absolute times (and "today") taken in `tz`
+fn parse_user_time_in<Tz: TimeZone>(input: &str, tz: &Tz) -> Option<DateTime<Local>> {
     let input = input.trim();
 
     if input.is_empty() {
@@ -44,44 +62,55 @@ pub fn parse_user_time(input: &str) -> Option<DateTime<Local>> {
 
     // Try full datetime with milliseconds
     if let Ok(dt) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S%.3f") {
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, dt);
     }
 
     // Try full datetime without milliseconds
     if let Ok(dt) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S") {
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, dt);
+    }
+
+    // Try full datetime without seconds
+    if let Ok(dt) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return in_zone(tz, dt);
     }
 
     // Try date only (start of day)
     if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
-        let dt = date.and_hms_opt(0, 0, 0)?;
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, date.and_hms_opt(0, 0, 0)?);
     }
 
     // Try time only with milliseconds (assume today)
     if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M:%S%.3f") {
-        let today = Local::now().date_naive();
+        let today = Utc::now().with_timezone(tz).date_naive();
         let dt = today.and_time(time);
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, dt);
     }
 
     // Try time only without milliseconds (assume today)
     if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M:%S") {
-        let today = Local::now().date_naive();
+        let today = Utc::now().with_timezone(tz).date_naive();
         let dt = today.and_time(time);
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, dt);
     }
 
     // Try HH:MM format (no seconds)
     if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
-        let today = Local::now().date_naive();
+        let today = Utc::now().with_timezone(tz).date_naive();
         let dt = today.and_time(time);
-        return Local.from_local_datetime(&dt).single();
+        return in_zone(tz, dt);
     }
 
     None
 }
 
+/// `dt` as wall-clock time in `tz`, converted to local time
+fn in_zone<Tz: TimeZone>(tz: &Tz, dt: NaiveDateTime) -> Option<DateTime<Local>> {
+    tz.from_local_datetime(&dt)
+        .single()
+        .map(|dt| dt.with_timezone(&Local))
+}
+
 /// Parse relative time strings like "-1h", "-30m", "-2d", "+1h"
 ///
 /// Supported units:
@@ -127,24 +156,232 @@ fn parse_relative_time(input: &str) -> Option<DateTime<Local>> {
     }
 }
 
-/// Parse timestamp string from log entry into DateTime<Local>
+/// Naive timestamp layouts tried after the configured format
+const NAIVE_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y/%m/%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Timestamp layouts carrying a UTC offset
+const OFFSET_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f %z",
+    "%d/%b/%Y:%H:%M:%S %z",
+];
+
+/// Zone that a file's timestamps without a UTC offset are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogTimezone {
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl LogTimezone {
+    /// Parse `local`, `UTC` / `Z`, or an offset such as `+08:00`, `+0800`, `UTC+8`, `-5`
+    pub fn parse(name: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("无效的时区: {} (可用 local、UTC 或 +08:00 形式的偏移)", name);
+        let trimmed = name.trim();
+        if trimmed.eq_ignore_ascii_case("local") {
+            return Ok(LogTimezone::Local);
+        }
+        if trimmed.eq_ignore_ascii_case("z") {
+            return Ok(LogTimezone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+        let offset = ["UTC", "GMT", "utc", "gmt"]
+            .iter()
+            .find_map(|p| trimmed.strip_prefix(p))
+            .unwrap_or(trimmed)
+            .trim();
+        if offset.is_empty() {
+            return Ok(LogTimezone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+        let (sign, digits) = match offset.as_bytes()[0] {
+            b'+' => (1, &offset[1..]),
+            b'-' => (-1, &offset[1..]),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = match digits.split_once(':') {
+            Some((h, m)) => (h, m),
+            None if digits.len() == 4 => digits.split_at(2),
+            None => (digits, "0"),
+        };
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(LogTimezone::Fixed)
+            .ok_or_else(invalid)
+    }
+
+    /// Wall-clock time `dt` in this zone as local wall-clock time
+    fn to_local(self, dt: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            LogTimezone::Local => dt,
+            LogTimezone::Fixed(offset) => offset
+                .from_local_datetime(&dt)
+                .single()
+                .map_or(dt, |dt| dt.with_timezone(&Local).naive_local()),
+        }
+    }
+}
+
+/// How one file's timestamps map onto the local timeline
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClockAdjust {
+    pub timezone: LogTimezone,
+    /// Added to every parsed time, for a machine whose clock runs fast or slow
+    pub skew: Duration,
+}
+
+impl ClockAdjust {
+    /// Parse a timestamp like [`parse_log_time`], then normalize it to local time
+    pub fn parse(&self, ts: &str, format: Option<&str>) -> Option<NaiveDateTime> {
+        parse_log_time(ts, format, self.timezone).map(|dt| dt + self.skew)
+    }
+}
+
+/// Parse a log entry's timestamp into local wall-clock time
+///
+/// Tries `format` (strftime) first, then:
+/// - ISO-8601 / RFC 3339 with or without an offset (offsets are converted to local time)
+/// - Epoch seconds or milliseconds, optionally fractional
+/// - Syslog "MMM dd HH:MM:SS" (year inferred, never more than a day in the future)
 ///
-/// Uses the log format: "YYYY-MM-DD HH:MM:SS.mmm"
-/// This is optimized for parsing timestamps already in log entries.
-pub fn parse_log_timestamp(ts: &str) -> Option<DateTime<Local>> {
+/// Timestamps without an offset are taken to be in `zone`.
+pub fn parse_log_time(ts: &str, format: Option<&str>, zone: LogTimezone) -> Option<NaiveDateTime> {
     let ts = ts.trim();
+    if ts.is_empty() {
+        return None;
+    }
 
-    // Try with milliseconds first (most common in logs)
-    if let Ok(dt) = NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S%.3f") {
-        return Local.from_local_datetime(&dt).single();
+    if let Some(fmt) = format {
+        if let Some(dt) = parse_with_format(ts, fmt, zone) {
+            return Some(dt);
+        }
     }
 
-    // Fallback to without milliseconds
-    if let Ok(dt) = NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S") {
-        return Local.from_local_datetime(&dt).single();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(ts) {
+        return Some(dt.with_timezone(&Local).naive_local());
+    }
+    for fmt in OFFSET_FORMATS {
+        if let Ok(dt) = DateTime::parse_from_str(ts, fmt) {
+            return Some(dt.with_timezone(&Local).naive_local());
+        }
+    }
+    for fmt in NAIVE_FORMATS {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(ts, fmt) {
+            return Some(zone.to_local(dt));
+        }
     }
 
-    None
+    parse_epoch(ts).or_else(|| {
+        parse_without_year(ts, "%b %d %H:%M:%S%.f").map(|dt| zone.to_local(dt))
+    })
+}
+
+/// Parse with a strftime format, honouring `%z` and formats without a year
+fn parse_with_format(ts: &str, fmt: &str, zone: LogTimezone) -> Option<NaiveDateTime> {
+    if fmt.contains("%z") || fmt.contains("%:z") {
+        return DateTime::parse_from_str(ts, fmt)
+            .ok()
+            .map(|dt| dt.with_timezone(&Local).naive_local());
+    }
+    if fmt == "%s" {
+        return parse_epoch(ts);
+    }
+    NaiveDateTime::parse_from_str(ts, fmt)
+        .ok()
+        .or_else(|| parse_without_year(ts, fmt))
+        .map(|dt| zone.to_local(dt))
+}
+
+/// Parse a year-less timestamp, picking the latest year that is not in the future
+fn parse_without_year(ts: &str, fmt: &str) -> Option<NaiveDateTime> {
+    // Syslog pads single-digit days with a space ("Jan  5")
+    let ts = ts.split_whitespace().collect::<Vec<_>>().join(" ");
+    let now = Local::now().naive_local();
+    let dt = NaiveDateTime::parse_from_str(&format!("{} {}", now.year(), ts), &format!("%Y {}", fmt)).ok()?;
+    if dt > now + Duration::days(1) {
+        dt.with_year(now.year() - 1)
+    } else {
+        Some(dt)
+    }
+}
+
+/// Epoch seconds (10 integer digits) or milliseconds (13 digits); shorter or longer
+/// numbers are more likely counters or ids than times
+fn parse_epoch(ts: &str) -> Option<NaiveDateTime> {
+    let (int, frac) = ts.split_once('.').unwrap_or((ts, ""));
+    if int.is_empty() || !int.bytes().all(|b| b.is_ascii_digit()) || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: f64 = ts.parse().ok()?;
+    let millis = match int.len() {
+        10 => (value * 1000.0) as i64,
+        13 => value as i64,
+        _ => return None,
+    };
+    DateTime::from_timestamp_millis(millis).map(|dt| dt.with_timezone(&Local).naive_local())
+}
+
+/// Translate a strftime format into a regex source matching the text it produces
+///
+/// Used to recognise record-start lines for the configured `timestamp_format`.
+pub fn strftime_to_regex(fmt: &str) -> String {
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push_str(&regex::escape(&c.to_string()));
+            continue;
+        }
+        let mut spec = String::new();
+        // Modifiers such as `.3`, `:` or `-` precede the conversion character
+        while let Some(m) = chars.next_if(|m| matches!(m, '.' | ':' | '-' | '_' | '0'..='9')) {
+            spec.push(m);
+        }
+        let Some(conv) = chars.next() else {
+            out.push('%');
+            break;
+        };
+        spec.push(conv);
+        let piece = match spec.as_str() {
+            "Y" => r"\d{4}",
+            "y" | "m" | "d" | "H" | "M" | "S" | "I" => r"\d{2}",
+            "-m" | "-d" | "-H" | "-M" | "-S" => r"\d{1,2}",
+            "e" | "k" | "l" => r"[ \d]\d",
+            "j" => r"\d{3}",
+            "b" | "h" | "a" => r"[A-Za-z]{3}",
+            "B" | "A" => r"[A-Za-z]+",
+            "p" | "P" => r"[AaPp][Mm]",
+            "f" => r"\d+",
+            ".f" => r"(?:\.\d+)?",
+            ".3f" => r"\.\d{3}",
+            ".6f" => r"\.\d{6}",
+            ".9f" => r"\.\d{9}",
+            "3f" => r"\d{3}",
+            "6f" => r"\d{6}",
+            "9f" => r"\d{9}",
+            "z" => r"[+-]\d{4}",
+            ":z" => r"[+-]\d{2}:\d{2}",
+            "Z" => r"[A-Za-z]+",
+            "s" => r"\d+",
+            "T" => r"\d{2}:\d{2}:\d{2}",
+            "R" => r"\d{2}:\d{2}",
+            "F" => r"\d{4}-\d{2}-\d{2}",
+            "D" => r"\d{2}/\d{2}/\d{2}",
+            "%" => "%",
+            _ => r".+?",
+        };
+        out.push_str(piece);
+    }
+    out
 }
 
 /// Result type for time parsing with error message
@@ -222,10 +459,80 @@ mod tests {
 
     #[test]
     fn test_parse_log_timestamp() {
-        let result = parse_log_timestamp("2024-01-15 10:30:45.123");
+        let result = parse_log_time("2024-01-15 10:30:45.123", Some("%Y-%m-%d %H:%M:%S%.3f"), LogTimezone::Local);
         assert!(result.is_some());
     }
 
+    #[test]
+    fn test_parse_log_time_formats() {
+        let expect = NaiveDateTime::parse_from_str("2024-01-15 10:30:45", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(parse_log_time("15.01.2024 10:30:45", Some("%d.%m.%Y %H:%M:%S"), LogTimezone::Local), Some(expect));
+        assert_eq!(parse_log_time("2024-01-15T10:30:45", None, LogTimezone::Local), Some(expect));
+
+        let utc = chrono::Utc.from_utc_datetime(&expect);
+        let local = utc.with_timezone(&Local).naive_local();
+        assert_eq!(parse_log_time("2024-01-15T10:30:45Z", None, LogTimezone::Local), Some(local));
+        assert_eq!(parse_log_time("2024-01-15T12:30:45+02:00", None, LogTimezone::Local), Some(local));
+        assert_eq!(parse_log_time(&utc.timestamp().to_string(), None, LogTimezone::Local), Some(local));
+        assert_eq!(parse_log_time(&utc.timestamp_millis().to_string(), None, LogTimezone::Local), Some(local));
+
+        let syslog = parse_log_time("Jan  5 08:00:01", None, LogTimezone::Local).unwrap();
+        assert_eq!(syslog.format("%m-%d %H:%M:%S").to_string(), "01-05 08:00:01");
+        assert!(parse_log_time("not a time", None, LogTimezone::Local).is_none());
+        // Counters and ids aren't epoch times
+        for number in ["42", "20240115", "12345678901", "123456789012345"] {
+            assert!(parse_log_time(number, None, LogTimezone::Local).is_none(), "{}", number);
+        }
+        assert!(parse_log_time("1705314645.5", None, LogTimezone::Local).is_some());
+    }
+
+    #[test]
+    fn test_clock_adjust_normalizes_to_local() {
+        let written = NaiveDateTime::parse_from_str("2024-01-15 18:30:45", "%Y-%m-%d %H:%M:%S").unwrap();
+        let utc = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .from_local_datetime(&written)
+            .unwrap()
+            .with_timezone(&Utc);
+        let local = utc.with_timezone(&Local).naive_local();
+
+        let clock = ClockAdjust {
+            timezone: LogTimezone::parse("UTC+8").unwrap(),
+            skew: Duration::zero(),
+        };
+        assert_eq!(clock.parse("2024-01-15 18:30:45", None), Some(local));
+        // An explicit offset wins over the configured zone
+        assert_eq!(clock.parse("2024-01-15T10:30:45Z", None), Some(local));
+
+        let skewed = ClockAdjust {
+            timezone: LogTimezone::parse("+08:00").unwrap(),
+            skew: Duration::milliseconds(-1500),
+        };
+        assert_eq!(
+            skewed.parse("2024-01-15 18:30:45", None),
+            Some(local - Duration::milliseconds(1500))
+        );
+        assert_eq!(LogTimezone::parse("local").unwrap(), LogTimezone::Local);
+        assert_eq!(LogTimezone::parse("-0530").unwrap(), LogTimezone::Fixed(FixedOffset::west_opt(5 * 3600 + 1800).unwrap()));
+        assert!(LogTimezone::parse("Asia/Shanghai").is_err());
+        assert!(LogTimezone::parse("+25").is_err());
+    }
+
+    #[test]
+    fn test_parse_user_time_utc() {
+        let dt = parse_user_time_utc("2024-01-15 10:30:45").unwrap();
+        assert_eq!(dt.with_timezone(&Utc).format("%H:%M:%S").to_string(), "10:30:45");
+    }
+
+    #[test]
+    fn test_strftime_to_regex() {
+        let re = regex::Regex::new(&format!("^{}", strftime_to_regex("%Y-%m-%d %H:%M:%S%.3f"))).unwrap();
+        assert!(re.is_match("2024-01-15 10:30:45.123 [main] INFO"));
+        assert!(!re.is_match("    at com.example.Foo"));
+        let re = regex::Regex::new(&format!("^{}", strftime_to_regex("[%d/%b/%Y:%T %z]"))).unwrap();
+        assert!(re.is_match("[15/Jan/2024:10:30:45 +0000] GET /"));
+    }
+
     #[test]
     fn test_parse_user_time_result_error() {
         let result = parse_user_time_result("invalid");
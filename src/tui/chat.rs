@@ -155,7 +155,7 @@ fn render_context_panel(frame: &mut Frame, app: &App, area: Rect) {
             Span::styled(format!("[{}][{}]", log.tid, log.level), level_style),
         ]));
         lines.push(Line::from(Span::styled(
-            log.content.clone(),
+            log.content.to_string(),
             Style::default().fg(Color::White),
         )));
         lines.push(Line::from(""));
@@ -6,15 +6,19 @@ use regex::Regex;
 use serde_json::Value;
 
 use crate::app_state::App;
-use crate::models::{AiState, DisplayEntry, ExportState, ExportType, FileInfo, Focus, InputMode, LevelVisibility};
+use crate::context::{ContextMark, RowRole};
+use crate::models::{
+    AiState, DisplayEntry, EntryKind, ExportState, ExportType, FileInfo, Focus, InputMode,
+    LevelVisibility, TimeDisplay,
+};
 use crate::tui::layout::centered_rect;
-use crate::tui::syntax::highlight_content_default;
+use crate::tui::syntax::{apply_highlights, highlight_content_default, highlight_matches, Highlight};
 
 fn level_color(level: &str) -> Color {
-    match level {
-        "Error" => Color::Red,
-        "Warning" | "Warn" => Color::Yellow,
-        "Debug" => Color::Cyan,
+    match level.to_lowercase().as_str() {
+        "error" => Color::Red,
+        "warning" | "warn" => Color::Yellow,
+        "debug" => Color::Cyan,
         _ => Color::White,
     }
 }
@@ -36,36 +40,9 @@ fn delta_span(delta_ms: Option<i64>) -> Option<Span<'static>> {
     }
 }
 
-/// Apply search regex highlighting on top of existing syntax-highlighted spans.
-/// Splits spans at match boundaries and applies a yellow background to matched text.
-fn apply_search_highlight(spans: Vec<Span<'static>>, regex: &Regex) -> Vec<Span<'static>> {
-    let mut result: Vec<Span<'static>> = Vec::new();
-    for span in spans {
-        let text = span.content.to_string();
-        let base_style = span.style;
-        let mut last_end = 0;
-        let mut had_match = false;
-        for m in regex.find_iter(&text) {
-            had_match = true;
-            if m.start() > last_end {
-                result.push(Span::styled(
-                    text[last_end..m.start()].to_string(),
-                    base_style,
-                ));
-            }
-            result.push(Span::styled(
-                m.as_str().to_string(),
-                base_style.bg(Color::Yellow).fg(Color::Black),
-            ));
-            last_end = m.end();
-        }
-        if had_match && last_end < text.len() {
-            result.push(Span::styled(text[last_end..].to_string(), base_style));
-        } else if !had_match {
-            result.push(Span::styled(text, base_style));
-        }
-    }
-    result
+/// Yellow background on search matches, drawn over syntax and rule highlights
+fn search_match_style() -> Style {
+    Style::default().bg(Color::Yellow).fg(Color::Black)
 }
 
 /// Apply horizontal scroll offset to content string
@@ -80,37 +57,111 @@ fn apply_horizontal_scroll(content: &str, offset: usize) -> String {
     chars[offset..].iter().collect()
 }
 
+/// The rows a log list shows, fetched by row number so no list is copied per frame
+struct ListRows<'a, 'e> {
+    total: usize,
+    entry_at: &'a dyn Fn(usize) -> Option<&'e DisplayEntry>,
+    context_at: &'a dyn Fn(usize) -> Option<ContextMark>,
+}
+
+/// How every row of a log list is drawn
+#[derive(Clone, Copy)]
+struct RowStyle<'a> {
+    highlights: &'a [Highlight],
+    horizontal_scroll: usize,
+    wrap_lines: bool,
+    time_display: TimeDisplay,
+}
+
 fn render_list_item(
     entry: &DisplayEntry,
     search_regex: Option<&Regex>,
     is_match: bool,
     is_bookmarked: bool,
+    context: Option<ContextMark>,
     file_color: Color,
     display_index: Option<usize>,
-    horizontal_scroll: usize,
-    wrap_lines: bool,
     available_width: usize,
+    style: RowStyle,
 ) -> ListItem<'static> {
+    let RowStyle { highlights, horizontal_scroll, wrap_lines, time_display } = style;
+    // With context lines on, the number is followed by ':' for matches and '-' for
+    // context, as in grep output
+    let (separator, idx_style) = match context.map(|m| m.role) {
+        Some(RowRole::Match) => (':', Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+        Some(RowRole::Context) => ('-', Style::default().fg(Color::DarkGray)),
+        None => (' ', Style::default().fg(Color::DarkGray)),
+    };
     let line_idx = if let Some(n) = display_index {
-        format!("{:>5} ", n)
+        format!("{:>5}{}", n, separator)
     } else {
         entry.get_line_index()
-            .map(|n| format!("{:>5} ", n))
-            .unwrap_or_else(|| "      ".into())
+            .map(|n| format!("{:>5}{}", n, separator))
+            .unwrap_or_else(|| format!("     {}", separator))
     };
     let bookmark = if is_bookmarked { "🔖" } else { " " };
     let marker = if is_match { "●" } else { " " };
-    match entry {
+    let item = match entry {
+        DisplayEntry::Normal(log) if log.kind == EntryKind::Rotation => ListItem::new(Line::from(vec![
+            Span::styled(line_idx, idx_style),
+            Span::styled("█ ", Style::default().fg(file_color)),
+            Span::styled(bookmark.to_string(), Style::default().fg(Color::Magenta)),
+            Span::styled(marker.to_string(), Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("──── {} ────", log.content),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ])),
+        DisplayEntry::Normal(log) if log.kind == EntryKind::Unparsed => {
+            // Raw text the profile couldn't parse, first line only like other records
+            let content = log.content.lines().next().unwrap_or_default();
+            let display_content = if !wrap_lines && horizontal_scroll > 0 {
+                apply_horizontal_scroll(content, horizontal_scroll)
+            } else {
+                content.to_string()
+            };
+            let mut content_spans = apply_highlights(
+                vec![Span::styled(
+                    display_content,
+                    Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+                )],
+                highlights,
+            );
+            if let Some(re) = search_regex {
+                content_spans = highlight_matches(content_spans, re, search_match_style());
+            }
+            let mut spans = vec![
+                Span::styled(line_idx, idx_style),
+                Span::styled("█ ", Style::default().fg(file_color)),
+                Span::styled(bookmark.to_string(), Style::default().fg(Color::Magenta)),
+                Span::styled(marker.to_string(), Style::default().fg(Color::Yellow)),
+                Span::styled(
+                    "[RAW] ",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+            ];
+            spans.extend(content_spans);
+            let style = if is_bookmarked {
+                Style::default().bg(Color::Rgb(40, 40, 60))
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(spans)).style(style)
+        }
         DisplayEntry::Normal(log) => {
-            // No hard truncation - use full content
-            let content = &log.content;
+            // No hard truncation - use full content, but only the first line of a
+            // multi-line record; the detail pane shows the rest
+            let content = log.content.lines().next().unwrap_or_default();
+            let extra_lines = log.content.lines().count().saturating_sub(1);
             let mut spans: Vec<Span<'static>> = vec![
-                Span::styled(line_idx, Style::default().fg(Color::DarkGray)),
+                Span::styled(line_idx, idx_style),
                 Span::styled("█ ", Style::default().fg(file_color)),
                 Span::styled(bookmark.to_string(), Style::default().fg(Color::Magenta)),
                 Span::styled(marker.to_string(), Style::default().fg(Color::Yellow)),
                 Span::styled(
-                    log.timestamp[11..19].to_string(),
+                    time_display.format(log, "%H:%M:%S"),
                     Style::default().fg(Color::DarkGray),
                 ),
                 Span::raw(" "),
@@ -139,14 +190,15 @@ fn render_list_item(
             };
 
             let highlighted = highlight_content_default(&display_content);
-            let mut content_spans: Vec<Span<'static>> = highlighted
+            let content_spans: Vec<Span<'static>> = highlighted
                 .spans
                 .into_iter()
                 .map(|s| Span::styled(s.content.to_string(), s.style))
                 .collect();
-            // Apply search regex highlighting on top of syntax highlighting
+            // Highlight rules, then search matches, on top of syntax highlighting
+            let mut content_spans = apply_highlights(content_spans, highlights);
             if let Some(re) = search_regex {
-                content_spans = apply_search_highlight(content_spans, re);
+                content_spans = highlight_matches(content_spans, re, search_match_style());
             }
 
             // If wrap is enabled and content exceeds available width, truncate visually but show indicator
@@ -163,6 +215,12 @@ fn render_list_item(
             }
 
             spans.extend(content_spans);
+            if extra_lines > 0 {
+                spans.push(Span::styled(
+                    format!(" (+{} lines)", extra_lines),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
             let style = if is_bookmarked {
                 Style::default().bg(Color::Rgb(40, 40, 60))
             } else {
@@ -175,7 +233,7 @@ fn render_list_item(
             summary_text,
             ..
         } => ListItem::new(Line::from(vec![
-            Span::styled(line_idx, Style::default().fg(Color::DarkGray)),
+            Span::styled(line_idx, idx_style),
             Span::styled("█ ", Style::default().fg(file_color)),
             Span::styled(bookmark.to_string(), Style::default().fg(Color::Magenta)),
             Span::styled(marker.to_string(), Style::default().fg(Color::Yellow)),
@@ -187,6 +245,11 @@ fn render_list_item(
             ),
             Span::styled(summary_text.clone(), Style::default().fg(Color::DarkGray)),
         ])),
+    };
+    if context.is_some_and(|m| m.role == RowRole::Context) {
+        item.dim()
+    } else {
+        item
     }
 }
 
@@ -248,14 +311,26 @@ fn format_json(value: &Value, indent: usize) -> Vec<Line<'static>> {
     lines
 }
 
-fn render_detail(entry: Option<&DisplayEntry>) -> Text<'static> {
+fn render_detail(
+    entry: Option<&DisplayEntry>,
+    time_display: TimeDisplay,
+    highlights: &[Highlight],
+) -> Text<'static> {
     match entry {
         Some(DisplayEntry::Normal(log)) => {
+            let mut time_spans = vec![
+                Span::styled("Time: ", Style::default().fg(Color::Yellow)),
+                Span::raw(time_display.format(log, "%Y-%m-%d %H:%M:%S%.3f")),
+            ];
+            // Normalized times keep the text from the file alongside
+            if time_display != TimeDisplay::Original && log.time.is_some() {
+                time_spans.push(Span::styled(
+                    format!(" ({}) 原始: {}", time_display.label(), log.timestamp),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
             let mut lines = vec![
-                Line::from(vec![
-                    Span::styled("Time: ", Style::default().fg(Color::Yellow)),
-                    Span::raw(log.timestamp.clone()),
-                ]),
+                Line::from(time_spans),
                 Line::from(vec![
                     Span::styled("TID: ", Style::default().fg(Color::Yellow)),
                     Span::raw(format!("{}:{}", log.pid, log.tid)),
@@ -263,7 +338,7 @@ fn render_detail(entry: Option<&DisplayEntry>) -> Text<'static> {
                 Line::from(vec![
                     Span::styled("Level: ", Style::default().fg(Color::Yellow)),
                     Span::styled(
-                        log.level.clone(),
+                        log.level.to_string(),
                         Style::default().fg(level_color(&log.level)),
                     ),
                 ]),
@@ -292,7 +367,11 @@ fn render_detail(entry: Option<&DisplayEntry>) -> Text<'static> {
                 "Content: ",
                 Style::default().fg(Color::Yellow),
             )]));
-            lines.push(Line::from(log.content.clone()));
+            lines.extend(
+                log.content
+                    .lines()
+                    .map(|l| Line::from(apply_highlights(vec![Span::raw(l.to_string())], highlights))),
+            );
             if let Some(json) = &log.json_payload {
                 lines.push(Line::from(""));
                 lines.push(Line::from(vec![Span::styled(
@@ -335,6 +414,18 @@ pub fn render_sidebar(frame: &mut Frame, app: &mut App, area: Rect) {
             ListItem::new(Line::from(vec![
                 Span::styled(format!("{} ", mark), Style::default().fg(Color::White)),
                 Span::styled(&f.name, Style::default().fg(f.color)),
+                Span::styled(
+                    format!(" ({}, {})", f.profile, f.encoding.name()),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                if f.parse_failures > 0 {
+                    Span::styled(
+                        format!(" ✗{}", f.parse_failures),
+                        Style::default().fg(Color::Red),
+                    )
+                } else {
+                    Span::raw("")
+                },
             ]))
         })
         .collect();
@@ -360,25 +451,36 @@ pub fn render_sidebar(frame: &mut Frame, app: &mut App, area: Rect) {
 fn render_log_list_with_state(
     frame: &mut Frame,
     area: Rect,
-    entries: &[DisplayEntry],
+    rows: ListRows,
     selected: Option<usize>,
     match_indices: &[usize],
     bookmarks: &std::collections::BTreeSet<usize>,
     error_indices: &[usize],
-    is_tailing: bool,
+    tail_state: Option<usize>,
     visible_levels: &LevelVisibility,
     filter_tid: &Option<String>,
     filter_trace: &Option<String>,
     search_regex: &Option<Regex>,
+    search_active: bool,
     focus: Focus,
     search_mode: bool,
     files: &[FileInfo],
     is_focus_mode: bool,
     focus_query: &str,
-    horizontal_scroll: usize,
-    wrap_lines: bool,
+    style: RowStyle,
 ) {
-    let tail_indicator = if is_tailing { "[LIVE] " } else { "" };
+    let ListRows { total, entry_at, context_at } = rows;
+    let time_display = style.time_display;
+    // `Some(n)` while tailing, with `n` rows arrived below a pinned selection
+    let is_tailing = tail_state.is_some();
+    let mut tail_indicator = match tail_state {
+        Some(0) => "[LIVE] ".to_string(),
+        Some(n) => format!("[LIVE ⏸ ↓{} 条新日志, F=跟随] ", n),
+        None => String::new(),
+    };
+    if time_display != TimeDisplay::Local {
+        tail_indicator.push_str(&format!("[{}] ", time_display.label()));
+    }
 
     // Level filter status
     let level_status = format!(
@@ -393,7 +495,7 @@ fn render_log_list_with_state(
         let focus_title = format!(
             " 🔍 FOCUS: {} ({} 条) {} [Esc退出]",
             focus_query,
-            entries.len(),
+            total,
             level_status
         );
         (
@@ -403,7 +505,7 @@ fn render_log_list_with_state(
             "e=导出 c=复制 Esc=退出",
         )
     } else {
-        let title = match (filter_tid, filter_trace, search_regex) {
+        let title = match (filter_tid, filter_trace, search_active.then_some(())) {
             (Some(tid), _, Some(_)) => format!(
                 " {}[FILTER: Thread {}] [SEARCH: {} matches] {} ",
                 tail_indicator, tid, match_indices.len(), level_status
@@ -426,14 +528,14 @@ fn render_log_list_with_state(
             ),
             (None, None, None) => format!(
                 " {}Logs ({}) {} ",
-                tail_indicator, entries.len(), level_status
+                tail_indicator, total, level_status
             ),
         };
         let title_style = if is_tailing {
             Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
         } else if filter_trace.is_some() {
             Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
-        } else if filter_tid.is_some() || search_regex.is_some() {
+        } else if filter_tid.is_some() || search_active {
             Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
         } else {
             Style::default()
@@ -459,30 +561,46 @@ fn render_log_list_with_state(
             .unwrap_or(Color::White)
     };
 
-    let items: Vec<ListItem> = entries
-        .iter()
-        .enumerate()
-        .map(|(i, e)| {
-            let file_color = e.get_source_id()
-                .map(|sid| get_file_color(sid))
-                .unwrap_or(Color::White);
-            let idx = if is_focus_mode { Some(i + 1) } else { None };
-            render_list_item(
-                e,
-                search_regex.as_ref(),
-                match_indices.contains(&i),
-                bookmarks.contains(&i),
-                file_color,
-                idx,
-                horizontal_scroll,
-                wrap_lines,
-                area.width as usize,
-            )
-        })
-        .collect();
+    // Only rows that can be on screen are built; a fresh full list would scroll the
+    // same way, keeping the selection at the bottom edge once it passes the first page
+    let height = area.height.saturating_sub(2) as usize;
+    let window_start = selected.map_or(0, |s| s.saturating_sub(height.saturating_sub(1)));
+    let window_end = (window_start + height).min(total);
+    // A separator row goes above each context group that doesn't follow on from the
+    // one before, shifting the selected row down
+    let mut items: Vec<ListItem> = Vec::with_capacity(window_end - window_start);
+    let mut selected_item = None;
+    for i in window_start..window_end {
+        let Some(e) = entry_at(i) else { continue };
+        let context = context_at(i);
+        if context.is_some_and(|m| m.group_start) {
+            items.push(ListItem::new(Line::styled(
+                "   ┄┄",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        if selected == Some(i) {
+            selected_item = Some(items.len());
+        }
+        let file_color = e.get_source_id()
+            .map(|sid| get_file_color(sid))
+            .unwrap_or(Color::White);
+        let idx = if is_focus_mode { Some(i + 1) } else { None };
+        items.push(render_list_item(
+            e,
+            search_regex.as_ref(),
+            match_indices.contains(&i),
+            bookmarks.contains(&i),
+            context,
+            file_color,
+            idx,
+            area.width as usize,
+            style,
+        ));
+    }
 
     let mut list_state = ListState::default();
-    list_state.select(selected);
+    list_state.select(selected_item);
 
     let list = List::new(items)
         .block(
@@ -502,10 +620,12 @@ fn render_log_list_with_state(
     frame.render_stateful_widget(list, area, &mut list_state);
 
     // Custom scrollbar with error markers (only in normal mode)
+    let mut full_state = ListState::default();
+    full_state.select(selected);
     if !is_focus_mode {
-        render_error_scrollbar_with_state(frame, area, &list_state, entries.len(), error_indices);
+        render_error_scrollbar_with_state(frame, area, &full_state, total, error_indices);
     } else {
-        render_focus_scrollbar(frame, area, &list_state, entries.len());
+        render_focus_scrollbar(frame, area, &full_state, total);
     }
 }
 
@@ -567,86 +687,82 @@ fn render_error_scrollbar_with_state(
 
 /// Render log list using app state (convenience wrapper for normal mode)
 pub fn render_log_list_from_app(frame: &mut Frame, app: &mut App, area: Rect) {
-    // Clone the data we need for rendering
-    let entries = app.entries().to_vec();
-    let match_indices = app.match_indices.clone();
-    let bookmarks = app.bookmarks.clone();
-    let error_indices = app.error_indices.clone();
-
-    // Extract display data
-    let is_tailing = app.is_tailing;
-    let visible_levels = app.visible_levels.clone();
-    let filter_tid = app.filter_tid.clone();
-    let filter_trace = app.filter_trace.clone();
-    let search_regex = app.search_regex.clone();
-    let focus = app.focus;
-    let search_mode = app.search_mode;
-    let files = app.files.clone();
-    let horizontal_scroll = app.horizontal_scroll;
-    let wrap_lines = app.wrap_lines;
-
-    // Get the list state
-    let selected = app.list_state.selected();
-
-    // Render the list
+    let app = &*app;
+    // Render the list; rows are looked up in `all_entries` only for the visible window
     render_log_list_with_state(
         frame,
         area,
-        &entries,
-        selected,
-        &match_indices,
-        &bookmarks,
-        &error_indices,
-        is_tailing,
-        &visible_levels,
-        &filter_tid,
-        &filter_trace,
-        &search_regex,
-        focus,
-        search_mode,
-        &files,
+        ListRows {
+            total: app.filtered_entries.len(),
+            entry_at: &|i| app.filtered_entry(i),
+            context_at: &|i| {
+                app.filtered_entries
+                    .get(i)
+                    .and_then(|idx| app.context_marks.get(idx).copied())
+            },
+        },
+        app.list_state.selected(),
+        &app.match_indices,
+        &app.bookmarks,
+        &app.error_indices,
+        app.is_tailing.then_some(app.unseen_lines),
+        &app.visible_levels,
+        &app.filter_tid,
+        &app.filter_trace,
+        &app.search_regex,
+        !app.search_filter.is_empty(),
+        app.focus,
+        app.search_mode,
+        &app.files,
         false,
         "",
-        horizontal_scroll,
-        wrap_lines,
+        RowStyle {
+            highlights: &app.highlights,
+            horizontal_scroll: app.horizontal_scroll,
+            wrap_lines: app.wrap_lines,
+            time_display: app.time_display,
+        },
     );
 }
 
 /// Render log list in focus mode
 pub fn render_focus_list(frame: &mut Frame, app: &mut App, area: Rect) {
-    // Clone the data we need for rendering
-    let entries = app.focus_mode.focus_logs.clone();
-    let bookmarks = app.bookmarks.clone();
-    let focus_query = app.focus_mode.focus_query.clone();
-    let visible_levels = app.visible_levels.clone();
-    let files = app.files.clone();
-    let horizontal_scroll = app.horizontal_scroll;
-    let wrap_lines = app.wrap_lines;
-
-    // Get the list state
-    let selected = app.focus_mode.focus_table_state.selected();
-
+    let app = &*app;
     // Render the focus list (empty match_indices to hide yellow dots)
     render_log_list_with_state(
         frame,
         area,
-        &entries,
-        selected,
+        ListRows {
+            total: app.focus_mode.focus_logs.len(),
+            entry_at: &|i| app.focus_entry(i),
+            context_at: &|i| {
+                app.focus_mode
+                    .focus_logs
+                    .get(i)
+                    .and_then(|idx| app.focus_mode.context_marks.get(idx).copied())
+            },
+        },
+        app.focus_mode.focus_table_state.selected(),
         &[], // No match indices in focus mode - all entries are matches
-        &bookmarks,
+        &app.bookmarks,
         &[], // No error indices in focus mode
-        false, // Not tailing
-        &visible_levels,
+        None, // Not tailing
+        &app.visible_levels,
         &None, // No filter_tid in focus mode
         &None, // No filter_trace in focus mode
         &None, // No search_regex in focus mode
+        false,
         Focus::LogList, // Always use log list focus in focus mode
         false, // Not search mode
-        &files,
+        &app.files,
         true, // Is focus mode
-        &focus_query,
-        horizontal_scroll,
-        wrap_lines,
+        &app.focus_mode.focus_query,
+        RowStyle {
+            highlights: &app.highlights,
+            horizontal_scroll: app.horizontal_scroll,
+            wrap_lines: app.wrap_lines,
+            time_display: app.time_display,
+        },
     );
 }
 
@@ -694,16 +810,23 @@ fn render_focus_scrollbar(frame: &mut Frame, area: Rect, list_state: &ListState,
 }
 
 pub fn render_search_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let search = Paragraph::new(format!("/{}", app.search_query)).block(
+    let mut spans = vec![Span::raw(format!("/{}", app.search_query))];
+    if let Some(error) = &app.search_error {
+        spans.push(Span::styled(
+            format!("  ✗ {}", error),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    let search = Paragraph::new(Line::from(spans)).block(
         Block::default()
             .borders(Borders::ALL)
-            .title(" Search (regex) "),
+            .title(" Search (regex 或 level:error AND NOT \"timeout\") "),
     );
     frame.render_widget(search, area);
 }
 
 pub fn render_detail_pane(frame: &mut Frame, app: &App, area: Rect) {
-    let detail = render_detail(app.selected_entry());
+    let detail = render_detail(app.selected_entry(), app.time_display, &app.highlights);
     let detail_title = app
         .status_message()
         .map(|m| format!(" {} ", m))
@@ -832,7 +955,7 @@ pub fn render_help_popup(frame: &mut Frame) {
     frame.render_widget(Clear, area);
     let help_text = "\
 ━━━━━━━━━━━━━━━━━━━━ 视图切换 ━━━━━━━━━━━━━━━━━━━━
-F1 日志列表    F2 仪表盘    F3 AI聊天    F4 历史    F5 报告
+F1 日志列表    F2 仪表盘    F3 AI聊天    F4 历史    F5 报告    F7 模板
 
 ━━━━━━━━━━━━━━━━━━━━ 专注模式 (Focus Mode) ━━━━━━━━━━━━━━━━━
 F6          进入专注模式 (仅显示搜索结果)
@@ -846,15 +969,23 @@ Tab         切换文件/日志焦点
 
 ━━━━━━━━━━━━━━━━━━━━ 水平滚动/换行 ━━━━━━━━━━━━━━━
 h/l         水平左/右滚动     w          切换自动换行
-Shift+H     重置水平滚动
+Shift+H     重置水平滚动      z          时间: 本地/UTC/原始
 
 ━━━━━━━━━━━━━━━━━━━━ 搜索过滤 ━━━━━━━━━━━━━━━━━━━━
 /           正则搜索          !term      反向搜索
 Shift+S     高级搜索面板       n/N        下/上一匹配
+x           匹配上下文行数 (如 3、B2 A5，高级搜索与专注模式)
+u/Ctrl+R    撤销/重做过滤     -          按编号移除单个过滤层
+Esc         清除全部过滤层 (可撤销)
+*           保留当前搜索为高亮 (再按一次移除)
 t           线程过滤          Shift+T    链路追踪 (traceId)
 1/2/3/4     Info/Warn/Error/Debug
 Ctrl+S      保存搜索模板 (面板内)
 Ctrl+L      加载搜索模板 (面板内)
+F7          模板管理: 预览/删除/重命名/复制/导入导出
+
+━━━━━━━━━━━━━━━━━━━━ 解析诊断 ━━━━━━━━━━━━━━━━━━━━
+Shift+D     诊断解析失败原因   d          (文件列表) 诊断该文件
 
 ━━━━━━━━━━━━━━━━━━━━ 书签功能 ━━━━━━━━━━━━━━━━━━━━
 m           切换书签          b/B        下/上一书签
@@ -877,8 +1008,9 @@ c/y         复制日志/JSON      e/E        导出CSV/JSON
 r/R         导出报告/AI分析
 
 ━━━━━━━━━━━━━━━━━━━━ 其他功能 ━━━━━━━━━━━━━━━━━━━━
-f           实时追踪 (LIVE)    ?          显示帮助
-Esc         关闭/取消          q          退出程序";
+f           实时追踪 (LIVE)    Shift+F    跳到最新并跟随
+?           显示帮助          Esc        关闭/取消
+q           退出程序";
     let popup = Paragraph::new(help_text).block(
         Block::default()
             .borders(Borders::ALL)
@@ -896,7 +1028,37 @@ pub fn render_jump_popup(frame: &mut Frame, app: &App) {
     if app.input_mode != InputMode::JumpInput {
         return;
     }
+    render_line_popup(frame, 20, ":", &app.input_buffer, " Go to Line (Enter确认, Esc取消) ");
+}
 
+pub fn render_context_popup(frame: &mut Frame, app: &App) {
+    if app.input_mode != InputMode::ContextInput {
+        return;
+    }
+    render_line_popup(
+        frame,
+        40,
+        "上下文: ",
+        &app.input_buffer,
+        " 匹配前后显示的条数 (如 3、B2 A5，留空关闭) ",
+    );
+}
+
+pub fn render_layer_popup(frame: &mut Frame, app: &App) {
+    if app.input_mode != InputMode::LayerInput {
+        return;
+    }
+    render_line_popup(
+        frame,
+        40,
+        "移除第几层: ",
+        &app.input_buffer,
+        " 按面包屑中的编号移除单个过滤层 ",
+    );
+}
+
+/// A one-line input box in the middle of the screen, `width` percent wide
+pub fn render_line_popup(frame: &mut Frame, width: u16, prompt: &str, input: &str, title: &str) {
     let area = frame.area();
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -909,17 +1071,17 @@ pub fn render_jump_popup(frame: &mut Frame, app: &App) {
     let area = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(40),
-            Constraint::Percentage(20),
-            Constraint::Percentage(40),
+            Constraint::Percentage((100 - width) / 2),
+            Constraint::Percentage(width),
+            Constraint::Percentage((100 - width) / 2),
         ])
         .split(popup_layout[1])[1];
 
     frame.render_widget(Clear, area);
     let text = Line::from(vec![
-        Span::styled(":", Style::default().fg(Color::Yellow)),
+        Span::styled(prompt.to_string(), Style::default().fg(Color::Yellow)),
         Span::styled(
-            app.input_buffer.clone(),
+            input.to_string(),
             Style::default()
                 .fg(Color::White)
                 .add_modifier(Modifier::BOLD),
@@ -929,7 +1091,7 @@ pub fn render_jump_popup(frame: &mut Frame, app: &App) {
     let popup = Paragraph::new(text).block(
         Block::default()
             .borders(Borders::ALL)
-            .title(" Go to Line (Enter确认, Esc取消) ")
+            .title(title.to_string())
             .border_style(Style::default().fg(Color::Cyan)),
     );
     frame.render_widget(popup, area);
@@ -992,6 +1154,63 @@ pub fn render_ai_prompt_popup(frame: &mut Frame, app: &App) {
     frame.render_widget(popup, area);
 }
 
+/// Popup explaining why a sampled record failed to parse
+pub fn render_parse_diagnostics_popup(frame: &mut Frame, app: &App) {
+    let Some(diag) = &app.parse_diagnostics else {
+        return;
+    };
+    let area = centered_rect(70, 60, frame.area());
+    frame.render_widget(Clear, area);
+    let label = Style::default().fg(Color::Yellow);
+    let mut content = vec![
+        Line::from(vec![
+            Span::styled("文件: ", label),
+            Span::raw(diag.file.clone()),
+            Span::styled("  解析配置: ", label),
+            Span::raw(diag.profile.clone()),
+            Span::styled("  解析失败: ", label),
+            Span::styled(diag.failures.to_string(), Style::default().fg(Color::Red)),
+        ]),
+        Line::from(vec![
+            Span::styled("示例记录 (第 ", label),
+            Span::raw(diag.line_index.to_string()),
+            Span::styled(" 条):", label),
+        ]),
+    ];
+    content.extend(diag.record.lines().take(5).map(|l| {
+        Line::from(Span::styled(
+            format!("  {}", l),
+            Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC),
+        ))
+    }));
+    content.push(Line::from(""));
+    content.push(Line::from(vec![
+        Span::styled("原因: ", label),
+        Span::styled(
+            diag.diagnosis.problem.clone(),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+    ]));
+    if !diag.diagnosis.matched.is_empty() {
+        content.push(Line::from(Span::styled("已匹配的捕获组:", label)));
+        content.extend(diag.diagnosis.matched.iter().map(|(group, text)| {
+            Line::from(vec![
+                Span::styled(format!("  {} = ", group), Style::default().fg(Color::Cyan)),
+                Span::styled(format!("{:?}", text), Style::default().fg(Color::Green)),
+            ])
+        }));
+    }
+    let popup = Paragraph::new(content)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" 🩺 解析诊断 (Esc 关闭) ")
+                .border_style(Style::default().fg(Color::Red)),
+        );
+    frame.render_widget(popup, area);
+}
+
 pub fn render_export_popup(frame: &mut Frame, app: &App) {
     match &app.export_state {
         ExportState::Confirm(export_type) => {
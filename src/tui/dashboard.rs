@@ -23,8 +23,9 @@ pub fn render_header(frame: &mut Frame, app: &App, area: Rect) {
     let chat_style = tab_style(app.current_view == CurrentView::Chat);
     let history_style = tab_style(app.current_view == CurrentView::History);
     let report_style = tab_style(app.current_view == CurrentView::Report);
+    let templates_style = tab_style(app.current_view == CurrentView::Templates);
 
-    let spans = vec![
+    let mut spans = vec![
         Span::styled(" [F1] ", logs_style),
         Span::styled("Logs", logs_style),
         Span::raw("  "),
@@ -43,11 +44,25 @@ pub fn render_header(frame: &mut Frame, app: &App, area: Rect) {
         Span::styled("[F6] ", if app.current_view == CurrentView::Focus { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::DarkGray) }),
         Span::styled("Focus", if app.current_view == CurrentView::Focus { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::DarkGray) }),
         Span::raw("  "),
+        Span::styled("[F7] ", templates_style),
+        Span::styled("Templates", templates_style),
+        Span::raw("  "),
         Span::styled(
             if app.is_tailing { "● LIVE" } else { "" },
             Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
         ),
     ];
+    // Breadcrumb of the filter layers, numbered for `-` (remove a layer)
+    if !app.filter_stack.is_empty() {
+        spans.push(Span::styled("  过滤: ", Style::default().fg(Color::DarkGray)));
+        for (i, layer) in app.filter_stack.layers().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(" › ", Style::default().fg(Color::DarkGray)));
+            }
+            spans.push(Span::styled(format!("{} ", i + 1), Style::default().fg(Color::DarkGray)));
+            spans.push(Span::styled(layer.label(), Style::default().fg(Color::Yellow)));
+        }
+    }
 
     let header = Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::BOTTOM));
     frame.render_widget(header, area);
@@ -7,6 +7,7 @@ pub mod report;
 pub mod runner;
 pub mod search_modal;
 pub mod syntax;
+pub mod templates;
 
 pub use runner::run_app;
 
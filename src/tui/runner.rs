@@ -7,28 +7,29 @@ use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
-use regex::Regex;
 
 use super::chat::render_chat_interface;
 use super::components::{
-    render_ai_popup, render_ai_prompt_popup, render_detail_pane, render_export_popup,
-    render_focus_list, render_help_popup, render_histogram, render_jump_popup,
-    render_log_list_from_app, render_search_bar, render_sidebar,
+    render_ai_popup, render_ai_prompt_popup, render_context_popup, render_detail_pane,
+    render_export_popup, render_focus_list, render_help_popup, render_histogram, render_jump_popup,
+    render_layer_popup, render_log_list_from_app, render_parse_diagnostics_popup, render_search_bar, render_sidebar,
 };
 use super::dashboard::{render_dashboard, render_header};
 use super::layout::{centered_rect, create_focus_layout, create_layout};
 use super::search_modal::render_search_modal;
+use super::templates::{render_template_popup, render_templates};
 use crate::app_state::App;
-use crate::filtering::filter_logs_owned;
-use crate::live::TailState;
+use crate::live::{LiveFiles, StdinSource};
 use crate::models::{
-    AiState, CurrentView, DisplayEntry, ExportResult, ExportState, ExportType, Focus, InputMode,
+    AiState, CurrentView, ExportResult, ExportState, ExportType, FileInfo, Focus, InputMode,
+    FILE_COLORS,
 };
-use crate::search::{LogLevel, SearchCriteria};
+use crate::search::{LogLevel, SearchCriteria, SearchQuery};
 use crate::search_form::{FormField, TemplateMode};
-use crate::templates::{get_template, get_template_names, save_template};
-use crate::time_parser::parse_user_time;
+use crate::templates::{get_template, get_template_names, save_template, template_exists};
 
+/// How often tailed paths are checked for rotation without a watcher event
+const ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 fn ui(frame: &mut Frame, app: &mut App) {
     let main_chunks = Layout::default()
@@ -92,12 +93,19 @@ fn ui(frame: &mut Frame, app: &mut App) {
         CurrentView::Report => {
             super::report::render_report(frame, app, main_chunks[1]);
         }
+        CurrentView::Templates => {
+            render_templates(frame, app, main_chunks[1]);
+        }
     }
     render_ai_popup(frame, app);
     if app.show_help {
         render_help_popup(frame);
     }
+    render_parse_diagnostics_popup(frame, app);
     render_jump_popup(frame, app);
+    render_context_popup(frame, app);
+    render_layer_popup(frame, app);
+    render_template_popup(frame, app);
     render_ai_prompt_popup(frame, app);
     render_export_popup(frame, app);
     render_search_modal(frame, app);
@@ -107,10 +115,10 @@ pub fn run_app(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     app: &mut App,
     file_rx: Receiver<Vec<PathBuf>>,
-    tail_state: &mut TailState,
-    file_paths: &[PathBuf],
-    re: &Regex,
+    live_files: &mut LiveFiles,
+    mut stdin: Option<&mut StdinSource>,
 ) -> Result<()> {
+    let mut last_rotation_check = Instant::now();
     loop {
         // State updates
         if let Ok(result) = app.ai_rx.try_recv() {
@@ -145,31 +153,78 @@ pub fn run_app(
                 Err(e) => app.report_content = format!("生成报告失败: {}", e),
             }
         }
+        app.poll_search();
         if matches!(app.ai_state, AiState::Loading) && app.current_view == CurrentView::Chat {
             app.tick_spinner();
         }
 
         if app.is_tailing {
-            while let Ok(paths) = file_rx.try_recv() {
-                for changed_path in paths {
-                    if let Some((source_id, path)) = file_paths
-                        .iter()
-                        .enumerate()
-                        .find(|(_, p)| p.as_path() == changed_path.as_path())
-                    {
-                        let base_idx = app.all_entries.len();
-                        let new_entries = tail_state.read_new_lines(path, source_id, &re, base_idx);
-                        for entry in new_entries {
-                            let display = DisplayEntry::Normal(entry);
-                            app.all_entries.push(display.clone());
-                            app.filtered_entries.push(display);
-                        }
+            let mut changed: Vec<PathBuf> = file_rx.try_iter().flatten().collect();
+            // Writes to a rotated file arrive under its new name, so tailed paths are
+            // also re-read now and then to drain it
+            if last_rotation_check.elapsed() >= ROTATION_CHECK_INTERVAL {
+                last_rotation_check = Instant::now();
+                changed.extend(live_files.paths().map(PathBuf::from));
+            }
+            // Release records held back as possibly incomplete once the file goes quiet
+            changed.extend(live_files.due_paths());
+            let mut sources: Vec<usize> = Vec::new();
+            let mut unknown_changed = false;
+            for path in &changed {
+                match live_files.source_id(path) {
+                    Some(source_id) => sources.push(source_id),
+                    None => unknown_changed = true,
+                }
+            }
+            // Something else changed in a watched directory: it may be a new match
+            if unknown_changed {
+                for found in live_files.discover(app.files.len()) {
+                    app.files.push(FileInfo {
+                        id: found.source_id,
+                        name: found
+                            .path
+                            .file_name()
+                            .map(|s| s.to_string_lossy().into())
+                            .unwrap_or_else(|| "?".into()),
+                        color: FILE_COLORS[found.source_id % FILE_COLORS.len()],
+                        enabled: true,
+                        profile: found.profile.name.clone(),
+                        encoding: found.encoding,
+                        parse_failures: 0,
+                    });
+                    app.status_msg = Some((
+                        format!("新文件: {}", found.path.display()),
+                        Instant::now(),
+                    ));
+                    app.profiles.push(Some(found.profile));
+                    sources.push(found.source_id);
+                }
+            }
+            sources.sort_unstable();
+            sources.dedup();
+            for source_id in sources {
+                let base_idx = app.all_entries.len();
+                let encoding = app.files[source_id].encoding;
+                let new_entries = live_files.read_new_lines(source_id, encoding, base_idx);
+                app.append_entries(new_entries);
+            }
+            if let Some(source) = stdin.as_deref_mut() {
+                let base_idx = app.all_entries.len();
+                let new_entries = source.read_new_records(base_idx);
+                let file = &mut app.files[source.source_id];
+                if let Some(profile) = source.profile() {
+                    if file.profile != profile.name {
+                        file.profile = profile.name.clone();
+                        app.profiles[source.source_id] = Some(profile.clone());
                     }
                 }
+                if let Some(encoding) = source.encoding() {
+                    file.encoding = encoding;
+                }
+                app.append_entries(new_entries);
             }
-            let len = app.filtered_entries.len();
-            if len > 0 {
-                app.list_state.select(Some(len - 1));
+            if app.is_following() {
+                app.unseen_lines = 0;
             }
         }
 
@@ -253,6 +308,59 @@ pub fn run_app(
                     continue;
                 }
 
+                if matches!(
+                    app.input_mode,
+                    InputMode::TemplateRenameInput
+                        | InputMode::TemplateExportInput
+                        | InputMode::TemplateImportInput
+                ) {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.input_mode = InputMode::Normal;
+                            app.input_buffer.clear();
+                        }
+                        KeyCode::Enter => app.submit_template_input(),
+                        KeyCode::Backspace => {
+                            app.input_buffer.pop();
+                        }
+                        KeyCode::Char(c) => app.input_buffer.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.input_mode == InputMode::LayerInput {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.input_mode = InputMode::Normal;
+                            app.input_buffer.clear();
+                        }
+                        KeyCode::Enter => app.submit_layer(),
+                        KeyCode::Backspace => {
+                            app.input_buffer.pop();
+                        }
+                        KeyCode::Char(c) if c.is_ascii_digit() => app.input_buffer.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.input_mode == InputMode::ContextInput {
+                    match key.code {
+                        KeyCode::Esc => {
+                            app.input_mode = InputMode::Normal;
+                            app.input_buffer.clear();
+                        }
+                        KeyCode::Enter => app.submit_context(),
+                        KeyCode::Backspace => {
+                            app.input_buffer.pop();
+                        }
+                        KeyCode::Char(c) => app.input_buffer.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 if app.input_mode == InputMode::AiPromptInput {
                     match key.code {
                         KeyCode::Esc => app.exit_ai_prompt_mode(),
@@ -265,8 +373,8 @@ pub fn run_app(
                             if let Some(idx) = app.list_state.selected() {
                                 let start = idx.saturating_sub(10);
                                 let end = (idx + 11).min(app.filtered_entries.len());
-                                let context: String = app.filtered_entries[start..end]
-                                    .iter()
+                                let context: String = (start..end)
+                                    .filter_map(|i| app.filtered_entry(i))
                                     .map(|e| e.get_content())
                                     .collect::<Vec<_>>()
                                     .join("\n");
@@ -332,7 +440,8 @@ pub fn run_app(
                             let total = app.focus_mode.focus_logs.len();
                             let text: String = indices.iter()
                                 .filter(|&&i| i >= 1 && i <= total)
-                                .map(|&i| app.focus_mode.focus_logs[i - 1].get_content())
+                                .filter_map(|&i| app.focus_entry(i - 1))
+                                .map(|e| e.get_content())
                                 .collect::<Vec<_>>()
                                 .join("\n");
                             if !text.is_empty() {
@@ -363,6 +472,11 @@ pub fn run_app(
                         KeyCode::Esc => app.exit_search(),
                         KeyCode::Enter => {
                             let query = app.search_query.clone();
+                            // Keep the bar open on a query that doesn't compile
+                            app.check_search_query();
+                            if app.search_error.is_some() {
+                                continue;
+                            }
                             if app.current_view == CurrentView::Focus {
                                 // In focus mode: filter focus_logs
                                 app.focus_update_search();
@@ -371,7 +485,7 @@ pub fn run_app(
                                 // Alt+Enter: Enter focus mode with current search
                                 app.update_search();
                                 app.exit_search();
-                                app.enter_focus_mode(query.clone());
+                                app.push_focus(query.clone());
                             } else {
                                 // Normal Enter: Apply search and stay in normal mode
                                 app.update_search();
@@ -381,15 +495,24 @@ pub fn run_app(
                         }
                         KeyCode::Backspace => {
                             app.search_query.pop();
+                            app.check_search_query();
                         }
                         KeyCode::Char(c) => {
                             app.search_query.push(c);
+                            app.check_search_query();
                         }
                         _ => {}
                     }
                     continue;
                 }
 
+                if app.parse_diagnostics.is_some() {
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                        app.parse_diagnostics = None;
+                    }
+                    continue;
+                }
+
                 if app.show_help {
                     match key.code {
                         KeyCode::Esc | KeyCode::Char('?') | KeyCode::Enter => app.show_help = false,
@@ -408,12 +531,19 @@ pub fn run_app(
                                     app.search_form.exit_template_mode();
                                 }
                                 KeyCode::Enter => {
-                                    let name = app.search_form.template_name_input.trim();
+                                    let name = app.search_form.template_name_input.trim().to_string();
                                     if name.is_empty() {
                                         app.search_form.set_error("模板名称不能为空".to_string());
+                                    } else if template_exists(&name)
+                                        && app.search_form.overwrite_name.as_ref() != Some(&name)
+                                    {
+                                        app.search_form.set_error(format!("模板 '{}' 已存在，再按 Enter 覆盖", name));
+                                        app.search_form.overwrite_name = Some(name);
                                     } else {
                                         let criteria = app.search_form.to_serializable_criteria();
-                                        match save_template(name, &criteria) {
+                                        let highlights: Vec<_> =
+                                            app.highlights.iter().map(|h| h.rule.clone()).collect();
+                                        match save_template(&name, &criteria, &highlights) {
                                             Ok(()) => {
                                                 app.search_form.set_status(format!("模板 '{}' 保存成功", name));
                                                 app.search_form.exit_template_mode();
@@ -449,6 +579,7 @@ pub fn run_app(
                                     if let Some(name) = app.search_form.selected_template_name().cloned() {
                                         if let Some(template) = get_template(&name) {
                                             app.search_form.load_from_criteria(&template.criteria);
+                                            app.load_template_highlights(template.highlights);
                                             app.search_form.set_status(format!("已加载模板 '{}'", name));
                                             app.search_form.exit_template_mode();
                                         }
@@ -483,7 +614,7 @@ pub fn run_app(
                                 
                                 // Parse start time
                                 if !form.start_time_input.is_empty() {
-                                    match parse_user_time(&form.start_time_input) {
+                                    match app.time_display.parse_user_time(&form.start_time_input) {
                                         Some(t) => criteria.start_time = Some(t),
                                         None => {
                                             app.search_form.set_error(
@@ -496,7 +627,7 @@ pub fn run_app(
                                 
                                 // Parse end time
                                 if !form.end_time_input.is_empty() {
-                                    match parse_user_time(&form.end_time_input) {
+                                    match app.time_display.parse_user_time(&form.end_time_input) {
                                         Some(t) => criteria.end_time = Some(t),
                                         None => {
                                             app.search_form.set_error(
@@ -517,17 +648,20 @@ pub fn run_app(
                                     criteria.source_file = Some(form.source_input.clone());
                                 }
                                 
+                                // JSON payload condition
+                                match SearchQuery::parse(&form.json_input, app.time_display) {
+                                    Ok(query) => criteria.query = query,
+                                    Err(e) => {
+                                        app.search_form.set_error(format!("无效的 JSON 条件: {}", e));
+                                        continue;
+                                    }
+                                }
+                                
                                 // Levels
                                 criteria.levels = form.selected_levels.iter().cloned().collect();
                                 
-                                // Apply filter
-                                app.filtered_entries = filter_logs_owned(&app.all_entries, &criteria);
-                                app.list_state.select(if app.filtered_entries.is_empty() {
-                                    None
-                                } else {
-                                    Some(0)
-                                });
-                                app.update_search_matches();
+                                // Apply filter (kept for entries that arrive while tailing)
+                                app.set_search_criteria(criteria);
                                 
                                 // Close form and show status
                                 app.search_form.close();
@@ -586,16 +720,15 @@ pub fn run_app(
                 {
                     match key.code {
                         KeyCode::Char('q') => return Ok(()),
-                        KeyCode::F(1) => app.current_view = CurrentView::Logs,
-                        KeyCode::F(2) => app.current_view = CurrentView::Dashboard,
-                        KeyCode::F(3) => app.current_view = CurrentView::Chat,
-                        KeyCode::F(4) => app.current_view = CurrentView::History,
-                        KeyCode::F(5) => app.current_view = CurrentView::Report,
+                        KeyCode::F(1) => app.switch_view(CurrentView::Logs),
+                        KeyCode::F(2) => app.switch_view(CurrentView::Dashboard),
+                        KeyCode::F(3) => app.switch_view(CurrentView::Chat),
+                        KeyCode::F(4) => app.switch_view(CurrentView::History),
+                        KeyCode::F(5) => app.switch_view(CurrentView::Report),
+                        KeyCode::F(7) => app.open_template_manager(),
                         KeyCode::F(6) => {
-                            let query = app.search_regex.as_ref()
-                                .map(|r| r.as_str().to_string())
-                                .unwrap_or_else(|| app.search_query.clone());
-                            app.enter_focus_mode(if query.is_empty() { "全部".to_string() } else { query });
+                            let query = app.search_query.clone();
+                            app.push_focus(if query.is_empty() { "全部".to_string() } else { query });
                         }
                         KeyCode::Tab => {
                             app.focus = if app.focus == Focus::LogList {
@@ -634,6 +767,21 @@ pub fn run_app(
                         }
                         continue;
                     }
+                    if app.current_view == CurrentView::Templates {
+                        match key.code {
+                            KeyCode::Up | KeyCode::Char('k') => app.template_manager.previous(),
+                            KeyCode::Down | KeyCode::Char('j') => app.template_manager.next(),
+                            KeyCode::Enter => app.load_selected_template(),
+                            KeyCode::Delete | KeyCode::Char('d') => app.delete_selected_template(),
+                            KeyCode::Char('r') => app.start_template_input(InputMode::TemplateRenameInput),
+                            KeyCode::Char('c') => app.duplicate_selected_template(),
+                            KeyCode::Char('e') => app.start_template_input(InputMode::TemplateExportInput),
+                            KeyCode::Char('i') => app.start_template_input(InputMode::TemplateImportInput),
+                            KeyCode::Esc => app.current_view = CurrentView::Logs,
+                            _ => {}
+                        }
+                        continue;
+                    }
                     if app.current_view == CurrentView::Report {
                         match key.code {
                             KeyCode::Up | KeyCode::Char('k') => {
@@ -690,7 +838,12 @@ pub fn run_app(
                     // Focus Mode handling
                     if app.current_view == CurrentView::Focus {
                         match key.code {
-                            KeyCode::Esc => app.exit_focus_mode(),
+                            KeyCode::Esc => app.pop_focus(),
+                            KeyCode::Char('u') => app.undo_filter(),
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.redo_filter()
+                            }
+                            KeyCode::Char('-') => app.enter_layer_mode(),
                             KeyCode::Up | KeyCode::Char('k') => app.focus_previous(),
                             KeyCode::Down | KeyCode::Char('j') => app.focus_next(),
                             KeyCode::Left => app.focus_previous_page(),
@@ -703,12 +856,13 @@ pub fn run_app(
                                 app.focus_mode.copy_input.clear();
                                 app.input_mode = InputMode::FocusCopyInput;
                             }
+                            KeyCode::Char('x') => app.enter_context_mode(),
                             KeyCode::Char('e') => {
                                 // Export focus mode entries to file
                                 let filename = format!("focus_{}.log", chrono::Local::now().format("%Y%m%d_%H%M%S"));
                                 let content: String = app.focus_mode.focus_logs
                                     .iter()
-                                    .map(|e| e.get_content())
+                                    .map(|&idx| app.all_entries[idx].get_content())
                                     .collect::<Vec<_>>()
                                     .join("\n");
                                 match std::fs::write(&filename, content) {
@@ -773,6 +927,7 @@ pub fn run_app(
                             }
                             KeyCode::Char(' ') => app.toggle_file(),
                             KeyCode::Enter => app.solo_file(),
+                            KeyCode::Char('d') => app.diagnose_selected_file(),
                             _ => {}
                         },
                         Focus::LogList => match key.code {
@@ -789,10 +944,8 @@ pub fn run_app(
                             KeyCode::Enter => {
                                 if key.modifiers.contains(KeyModifiers::ALT) {
                                     // Alt+Enter: Enter focus mode with current search results
-                                    let query = app.search_regex.as_ref()
-                                        .map(|r| r.as_str().to_string())
-                                        .unwrap_or_else(|| app.search_query.clone());
-                                    app.enter_focus_mode(if query.is_empty() { "全部".to_string() } else { query });
+                                    let query = app.search_query.clone();
+                                    app.push_focus(if query.is_empty() { "全部".to_string() } else { query });
                                 }
                             }
                             KeyCode::Char('n') => app.next_match(),
@@ -803,11 +956,15 @@ pub fn run_app(
                             KeyCode::Char('T') if key.modifiers.contains(KeyModifiers::SHIFT) => {
                                 app.toggle_trace_filter()
                             }
-                            KeyCode::Esc => {
-                                app.filter_tid = None;
-                                app.filter_trace = None;
-                                app.apply_filter();
+                            KeyCode::Esc if app.cancel_search() => {
+                                app.status_msg = Some(("已取消搜索".to_string(), Instant::now()));
+                            }
+                            KeyCode::Esc => app.clear_filters(),
+                            KeyCode::Char('u') => app.undo_filter(),
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.redo_filter()
                             }
+                            KeyCode::Char('-') => app.enter_layer_mode(),
                             KeyCode::Char('c') => app.copy_line(),
                             KeyCode::Char('y') => app.yank_payload(),
                             KeyCode::Char('m') => app.toggle_bookmark(),
@@ -825,7 +982,10 @@ pub fn run_app(
                                 }
                             }
                             KeyCode::Char('p') => app.pin_selected_log(),
-                            KeyCode::Char('f') => app.is_tailing = !app.is_tailing,
+                            KeyCode::Char('f') => app.toggle_tailing(),
+                            KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                                app.follow_tail()
+                            }
                             KeyCode::Char('e') => app.request_export(ExportType::LogsCsv),
                             KeyCode::Char('E') if key.modifiers.contains(KeyModifiers::SHIFT) => {
                                 app.request_export(ExportType::LogsJson)
@@ -852,6 +1012,12 @@ pub fn run_app(
                                 app.reset_horizontal_scroll();
                             }
                             KeyCode::Char('w') => app.toggle_wrap_lines(),
+                            KeyCode::Char('z') => app.cycle_time_display(),
+                            KeyCode::Char('x') => app.enter_context_mode(),
+                            KeyCode::Char('*') => app.toggle_search_highlight(),
+                            KeyCode::Char('D') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                                app.diagnose_selected()
+                            }
                             _ => {}
                         },
                     }
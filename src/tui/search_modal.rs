@@ -1,7 +1,8 @@
 //! Search modal UI component
 //!
 //! This module renders the advanced search form modal with multiple input fields
-//! for time range, content regex, source file, and log level selection.
+//! for time range, content regex, source file, JSON payload condition and log level
+//! selection.
 //! Also supports saving/loading search templates.
 
 use ratatui::{
@@ -35,8 +36,8 @@ pub fn render_search_modal(frame: &mut Frame, app: &App) {
         TemplateMode::None => {}
     }
 
-    // Modal dimensions: 65% width, 60% height, centered
-    let area = centered_rect(65, 60, frame.area());
+    // Modal dimensions: 65% width, 70% height, centered
+    let area = centered_rect(65, 70, frame.area());
 
     // Clear background and draw outer border
     frame.render_widget(Clear, area);
@@ -68,6 +69,7 @@ pub fn render_search_modal(frame: &mut Frame, app: &App) {
             Constraint::Length(3), // End time
             Constraint::Length(3), // Content regex
             Constraint::Length(3), // Source file
+            Constraint::Length(3), // JSON condition
             Constraint::Length(5), // Level selection
             Constraint::Length(1), // Spacer
             Constraint::Length(3), // Submit button
@@ -128,10 +130,21 @@ pub fn render_search_modal(frame: &mut Frame, app: &App) {
         form.focused_field == FormField::Source,
     );
 
+    // JSON payload condition input
+    render_input_field(
+        frame,
+        chunks[4],
+        "JSON 条件",
+        &form.json_input,
+        "例: $.latency > 200 AND exists($.error)",
+        field_style(FormField::Json),
+        form.focused_field == FormField::Json,
+    );
+
     // Level selection - show hint that empty means all
     render_level_selector(
         frame,
-        chunks[4],
+        chunks[5],
         &form.selected_levels,
         field_style(FormField::LevelSelect),
         form.focused_field == FormField::LevelSelect,
@@ -140,7 +153,7 @@ pub fn render_search_modal(frame: &mut Frame, app: &App) {
     // Submit button
     render_submit_button(
         frame,
-        chunks[6],
+        chunks[7],
         form.focused_field == FormField::SubmitBtn,
     );
 
@@ -149,12 +162,12 @@ pub fn render_search_modal(frame: &mut Frame, app: &App) {
         let error_widget = Paragraph::new(format!("❌ {}", error))
             .style(Style::default().fg(Color::Red))
             .alignment(Alignment::Center);
-        frame.render_widget(error_widget, chunks[7]);
+        frame.render_widget(error_widget, chunks[8]);
     } else if let Some(ref status) = form.status_message {
         let status_widget = Paragraph::new(format!("✅ {}", status))
             .style(Style::default().fg(Color::Green))
             .alignment(Alignment::Center);
-        frame.render_widget(status_widget, chunks[7]);
+        frame.render_widget(status_widget, chunks[8]);
     }
 }
 
@@ -1,8 +1,9 @@
+use anyhow::{anyhow, bail, Result};
 use once_cell::sync::Lazy;
 use ratatui::prelude::*;
 use regex::Regex;
 
-use crate::config::ThemeConfig;
+use crate::config::{HighlightRule, ThemeConfig};
 
 static IP_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b").unwrap());
@@ -10,17 +11,89 @@ static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://[^\s]+").unwrap(
 static PATH_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"[a-zA-Z]:\\[^<>:\|\?\*\n\r]+\.\w{2,}").unwrap());
 
+/// Colors given in turn to highlights added from the search bar
+const HIGHLIGHT_PALETTE: [&str; 6] = ["Magenta", "Green", "Cyan", "LightRed", "Blue", "Yellow"];
+
 fn color_from_name(name: &str) -> Color {
-    match name.to_lowercase().as_str() {
-        "red" => Color::Red,
-        "green" => Color::Green,
-        "blue" => Color::Blue,
-        "yellow" => Color::Yellow,
-        "cyan" => Color::Cyan,
-        "magenta" => Color::Magenta,
-        "white" => Color::White,
-        _ => Color::Gray,
+    name.parse().unwrap_or(Color::Gray)
+}
+
+/// A highlight rule with its pattern compiled
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub rule: HighlightRule,
+    regex: Regex,
+    style: Style,
+}
+
+impl Highlight {
+    pub fn new(rule: HighlightRule) -> Result<Self> {
+        let regex = Regex::new(&rule.pattern)
+            .map_err(|e| anyhow!("无效的高亮规则 {}: {}", rule.pattern, e))?;
+        let color: Color = rule
+            .color
+            .parse()
+            .map_err(|_| anyhow!("未知的高亮颜色: {}", rule.color))?;
+        let mut style = Style::default().fg(color);
+        for word in rule
+            .style
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|w| !w.is_empty())
+        {
+            style = style.add_modifier(match word.to_lowercase().as_str() {
+                "bold" => Modifier::BOLD,
+                "italic" => Modifier::ITALIC,
+                "underline" | "underlined" => Modifier::UNDERLINED,
+                "reverse" | "reversed" => Modifier::REVERSED,
+                "dim" => Modifier::DIM,
+                _ => bail!("未知的高亮样式: {}", word),
+            });
+        }
+        Ok(Self { rule, regex, style })
+    }
+
+    /// A bold rule for `pattern`, colored by how many highlights there already are
+    pub fn from_search(pattern: &str, existing: usize) -> Result<Self> {
+        Self::new(HighlightRule {
+            pattern: pattern.to_string(),
+            color: HIGHLIGHT_PALETTE[existing % HIGHLIGHT_PALETTE.len()].to_string(),
+            style: "bold".to_string(),
+        })
+    }
+}
+
+/// Restyle the text of `spans` matching `regex`, patching `style` over each span's own
+pub fn highlight_matches(spans: Vec<Span<'static>>, regex: &Regex, style: Style) -> Vec<Span<'static>> {
+    let mut result: Vec<Span<'static>> = Vec::new();
+    for span in spans {
+        let text = span.content.to_string();
+        let base_style = span.style;
+        let mut last_end = 0;
+        for m in regex.find_iter(&text) {
+            if m.is_empty() {
+                continue;
+            }
+            if m.start() > last_end {
+                result.push(Span::styled(text[last_end..m.start()].to_string(), base_style));
+            }
+            result.push(Span::styled(m.as_str().to_string(), base_style.patch(style)));
+            last_end = m.end();
+        }
+        if last_end == 0 {
+            result.push(Span::styled(text, base_style));
+        } else if last_end < text.len() {
+            result.push(Span::styled(text[last_end..].to_string(), base_style));
+        }
+    }
+    result
+}
+
+/// Apply `highlights` in order, so later rules win where they overlap
+pub fn apply_highlights(mut spans: Vec<Span<'static>>, highlights: &[Highlight]) -> Vec<Span<'static>> {
+    for h in highlights {
+        spans = highlight_matches(spans, &h.regex, h.style);
     }
+    spans
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -77,3 +150,31 @@ pub fn highlight_content<'a>(content: &'a str, theme: &ThemeConfig) -> Line<'a>
 pub fn highlight_content_default(content: &str) -> Line<'_> {
     highlight_content(content, &ThemeConfig::default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_rules() {
+        let rule = |pattern: &str, color: &str, style: &str| HighlightRule {
+            pattern: pattern.into(),
+            color: color.into(),
+            style: style.into(),
+        };
+        let highlights = vec![
+            Highlight::new(rule("timeout", "#ff8000", "bold underline")).unwrap(),
+            Highlight::new(rule(r"\d+ms", "LightCyan", "")).unwrap(),
+        ];
+        let spans = apply_highlights(vec![Span::raw("db timeout after 300ms")], &highlights);
+        let texts: Vec<&str> = spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(texts, vec!["db ", "timeout", " after ", "300ms"]);
+        assert_eq!(spans[1].style.fg, Some(Color::Rgb(0xff, 0x80, 0)));
+        assert!(spans[1].style.add_modifier.contains(Modifier::BOLD | Modifier::UNDERLINED));
+        assert_eq!(spans[3].style.fg, Some(Color::LightCyan));
+
+        assert!(Highlight::new(rule("(", "Red", "")).is_err());
+        assert!(Highlight::new(rule("x", "Red", "blink-fast")).is_err());
+        assert!(Highlight::new(rule("x", "not-a-color", "")).is_err());
+    }
+}
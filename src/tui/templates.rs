@@ -0,0 +1,123 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+
+use crate::app_state::App;
+use crate::models::InputMode;
+use crate::search::SearchTemplate;
+use crate::tui::components::render_line_popup;
+
+pub fn render_templates(frame: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(area);
+
+    let manager = &app.template_manager;
+    let items: Vec<ListItem> = manager
+        .templates
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>3} ", i + 1), Style::default().fg(Color::DarkGray)),
+                Span::styled(&t.name, Style::default().fg(Color::White)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" 📋 Search Templates ({}) ", manager.templates.len()))
+                .title_bottom(" Enter:加载 | d:删除 | r:重命名 | c:复制 | e:导出 | i:导入 | Esc:返回 "),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+
+    let mut state = ListState::default();
+    if !manager.templates.is_empty() {
+        state.select(Some(manager.selected));
+    }
+    frame.render_stateful_widget(list, chunks[0], &mut state);
+
+    let preview = match manager.selected_template() {
+        Some(template) => template_preview(template),
+        None => Text::from(vec![
+            Line::from("暂无模板"),
+            Line::from(""),
+            Line::styled(
+                "在高级搜索面板 (Shift+S) 中按 Ctrl+S 保存，或按 i 导入团队共享的模板文件",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]),
+    };
+    let preview = Paragraph::new(preview)
+        .block(Block::default().borders(Borders::ALL).title(" 条件预览 "))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(preview, chunks[1]);
+}
+
+fn template_preview(template: &SearchTemplate) -> Text<'static> {
+    let field = |label: &str, value: Option<&String>| {
+        Line::from(vec![
+            Span::styled(format!("{}: ", label), Style::default().fg(Color::Yellow)),
+            match value {
+                Some(v) if !v.is_empty() => Span::raw(v.clone()),
+                _ => Span::styled("-", Style::default().fg(Color::DarkGray)),
+            },
+        ])
+    };
+    let criteria = &template.criteria;
+    let levels = criteria
+        .levels
+        .iter()
+        .map(|l| format!("{:?}", l))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut lines = vec![
+        Line::styled(
+            template.name.clone(),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        ),
+        Line::from(""),
+        field("开始时间", criteria.start_time.as_ref()),
+        field("结束时间", criteria.end_time.as_ref()),
+        field("内容正则", criteria.content_regex.as_ref()),
+        field("来源文件", criteria.source_file.as_ref()),
+        field("JSON 条件", criteria.json_filter.as_ref()),
+        field("日志级别", Some(&levels)),
+    ];
+    if !template.highlights.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::styled("高亮规则:", Style::default().fg(Color::Yellow)));
+        for rule in &template.highlights {
+            let color = rule.color.parse().unwrap_or(Color::Gray);
+            lines.push(Line::from(vec![
+                Span::raw("  "),
+                Span::styled(rule.pattern.clone(), Style::default().fg(color)),
+                Span::styled(
+                    format!("  ({} {})", rule.color, rule.style),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]));
+        }
+    }
+    Text::from(lines)
+}
+
+pub fn render_template_popup(frame: &mut Frame, app: &App) {
+    let (prompt, title) = match app.input_mode {
+        InputMode::TemplateRenameInput => ("新名称: ", " 重命名模板 "),
+        InputMode::TemplateExportInput => ("导出到: ", " 导出全部模板 (.toml 或 .json) "),
+        InputMode::TemplateImportInput => ("导入文件: ", " 导入模板 "),
+        _ => return,
+    };
+    // An import waiting for confirmation names the templates it replaces
+    let title = match &app.template_manager.overwrite_import {
+        Some((path, names)) if *path == app.input_buffer.trim() => {
+            format!(" 将覆盖: {} (再按 Enter 确认) ", names.join(", "))
+        }
+        _ => title.to_string(),
+    };
+    render_line_popup(frame, 60, prompt, &app.input_buffer, &title);
+}
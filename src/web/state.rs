@@ -16,9 +16,8 @@ impl WebSharedState {
         }
     }
 
-    #[allow(dead_code)]
-    pub async fn update_stats(&self, stats: DashboardStats) {
-        let mut state = self.stats.write().await;
-        *state = stats;
+    /// Replace the stats from outside the runtime (the UI thread)
+    pub fn update_stats(&self, stats: DashboardStats) {
+        *self.stats.blocking_write() = stats;
     }
 }